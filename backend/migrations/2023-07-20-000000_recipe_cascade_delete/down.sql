@@ -0,0 +1,19 @@
+ALTER TABLE instructions
+DROP CONSTRAINT instructions_recipe_id_fkey,
+ADD CONSTRAINT instructions_recipe_id_fkey FOREIGN KEY (recipe_id) REFERENCES recipes(id);
+
+ALTER TABLE recipe_ingredients
+DROP CONSTRAINT recipe_ingredients_recipe_id_fkey,
+ADD CONSTRAINT recipe_ingredients_recipe_id_fkey FOREIGN KEY (recipe_id) REFERENCES recipes(id);
+
+ALTER TABLE bookmarks
+DROP CONSTRAINT bookmarks_recipe_id_fkey,
+ADD CONSTRAINT bookmarks_recipe_id_fkey FOREIGN KEY (recipe_id) REFERENCES recipes(id);
+
+ALTER TABLE recipes_tags
+DROP CONSTRAINT recipes_tags_recipe_id_fkey,
+ADD CONSTRAINT recipes_tags_recipe_id_fkey FOREIGN KEY (recipe_id) REFERENCES recipes(id);
+
+ALTER TABLE recipes_users
+DROP CONSTRAINT recipes_users_recipe_id_fkey,
+ADD CONSTRAINT recipes_users_recipe_id_fkey FOREIGN KEY (recipe_id) REFERENCES recipes(id);
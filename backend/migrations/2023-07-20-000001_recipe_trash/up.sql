@@ -0,0 +1,2 @@
+ALTER TABLE recipes
+ADD COLUMN deleted_at TIMESTAMP;
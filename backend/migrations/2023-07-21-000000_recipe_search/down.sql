@@ -0,0 +1,2 @@
+DROP FUNCTION recipe_search_refresh(INTEGER, REGCONFIG);
+DROP TABLE recipe_search_documents;
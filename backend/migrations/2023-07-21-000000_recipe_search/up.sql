@@ -0,0 +1,41 @@
+CREATE TABLE recipe_search_documents (
+    recipe_id INTEGER PRIMARY KEY REFERENCES recipes(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    document TSVECTOR NOT NULL
+);
+
+CREATE INDEX recipe_search_documents_document_idx ON recipe_search_documents USING GIN (document);
+
+-- Rebuilds the search document of a recipe from its title, tags, ingredients and instructions.
+-- Weights: title A, tags B, ingredients C, instructions D.
+CREATE OR REPLACE FUNCTION recipe_search_refresh(_recipe_id INTEGER, _config REGCONFIG) RETURNS VOID AS $$
+    INSERT INTO recipe_search_documents (recipe_id, content, document)
+    SELECT
+        r.id,
+        concat_ws(' ', r.title, tg.labels, ing.labels, ins.texts),
+        setweight(to_tsvector(_config, r.title), 'A') ||
+        setweight(to_tsvector(_config, coalesce(tg.labels, '')), 'B') ||
+        setweight(to_tsvector(_config, coalesce(ing.labels, '')), 'C') ||
+        setweight(to_tsvector(_config, coalesce(ins.texts, '')), 'D')
+    FROM recipes r
+    CROSS JOIN LATERAL (
+        SELECT string_agg(t.label, ' ') AS labels
+        FROM recipes_tags rt JOIN tags t ON t.id = rt.tag_id
+        WHERE rt.recipe_id = r.id
+    ) tg
+    CROSS JOIN LATERAL (
+        SELECT string_agg(i.label, ' ') AS labels
+        FROM recipe_ingredients ri JOIN ingredients i ON i.id = ri.ingredient_id
+        WHERE ri.recipe_id = r.id
+    ) ing
+    CROSS JOIN LATERAL (
+        SELECT string_agg(s.instruction, ' ' ORDER BY s.display_order) AS texts
+        FROM instructions s
+        WHERE s.recipe_id = r.id
+    ) ins
+    WHERE r.id = _recipe_id
+    ON CONFLICT (recipe_id) DO UPDATE
+    SET content = EXCLUDED.content, document = EXCLUDED.document;
+$$ LANGUAGE sql;
+
+SELECT recipe_search_refresh(id, 'english') FROM recipes;
@@ -0,0 +1,6 @@
+ALTER TABLE recipes
+ALTER COLUMN servings TYPE VARCHAR
+USING CONCAT_WS(' ', servings, servings_unit);
+
+ALTER TABLE recipes
+DROP COLUMN servings_unit;
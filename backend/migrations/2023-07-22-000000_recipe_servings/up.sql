@@ -0,0 +1,10 @@
+-- split free-form servings ("4", "24 cookies") into a number and a yield unit
+ALTER TABLE recipes
+ADD COLUMN servings_unit VARCHAR;
+
+UPDATE recipes
+SET servings_unit = NULLIF(TRIM(regexp_replace(servings, '^\D*\d+([.,]\d+)?', '')), '');
+
+ALTER TABLE recipes
+ALTER COLUMN servings TYPE SMALLINT
+USING LEAST(GREATEST(COALESCE(substring(servings from '\d{1,4}')::SMALLINT, 1), 1), 1000);
@@ -0,0 +1,36 @@
+DROP TABLE ingredient_synonyms;
+
+DROP INDEX ingredients_label_trgm_idx;
+DROP INDEX ingredients_key_idx;
+
+ALTER TABLE ingredients
+ALTER COLUMN label DROP NOT NULL;
+
+ALTER TABLE ingredients
+ADD COLUMN unit VARCHAR;
+
+-- one ingredient row per label and unit again
+CREATE TEMPORARY TABLE catalogue_ingredients AS SELECT id FROM ingredients;
+
+INSERT INTO ingredients (unit, label)
+SELECT DISTINCT ri.unit, i.label
+FROM recipe_ingredients ri
+JOIN ingredients i ON i.id = ri.ingredient_id;
+
+UPDATE recipe_ingredients ri
+SET ingredient_id = n.id
+FROM ingredients o, ingredients n
+WHERE o.id = ri.ingredient_id
+    AND n.label = o.label
+    AND n.unit IS NOT DISTINCT FROM ri.unit
+    AND n.id NOT IN (SELECT id FROM catalogue_ingredients);
+
+DELETE FROM ingredients
+WHERE id IN (SELECT id FROM catalogue_ingredients);
+
+DROP TABLE catalogue_ingredients;
+
+ALTER TABLE recipe_ingredients
+DROP COLUMN unit;
+
+DROP FUNCTION ingredient_key(TEXT);
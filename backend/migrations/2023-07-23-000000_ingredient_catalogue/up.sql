@@ -0,0 +1,58 @@
+-- the unit belongs to the recipe line, the ingredient is only its name
+-- needs a superuser or the CREATE privilege, on managed Postgres enable it beforehand (see readme.md)
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Matching key of an ingredient name: lowercase, single spaces, last word in singular.
+-- "Green  Onions" and "green onion" share the key "green onion".
+CREATE OR REPLACE FUNCTION ingredient_key(_label TEXT) RETURNS TEXT AS $$
+    SELECT CASE
+        WHEN length(k) <= 3 THEN k
+        WHEN k ~ 'ies$' THEN regexp_replace(k, 'ies$', 'y')
+        WHEN k ~ '(oes|sses|xes|zes|ches|shes)$' THEN regexp_replace(k, 'es$', '')
+        WHEN k ~ '[^sui]s$' THEN regexp_replace(k, 's$', '')
+        ELSE k
+    END
+    FROM (SELECT lower(regexp_replace(trim(_label), '\s+', ' ', 'g')) AS k) n;
+$$ LANGUAGE sql IMMUTABLE;
+
+ALTER TABLE recipe_ingredients
+ADD COLUMN unit VARCHAR;
+
+UPDATE recipe_ingredients ri
+SET unit = i.unit
+FROM ingredients i
+WHERE i.id = ri.ingredient_id;
+
+UPDATE ingredients
+SET label = regexp_replace(trim(COALESCE(label, '')), '\s+', ' ', 'g');
+
+-- one ingredient per key, the oldest one stays
+UPDATE recipe_ingredients ri
+SET ingredient_id = c.keep
+FROM (
+    SELECT id, MIN(id) OVER (PARTITION BY ingredient_key(label)) AS keep
+    FROM ingredients
+) c
+WHERE ri.ingredient_id = c.id AND c.id <> c.keep;
+
+DELETE FROM ingredients
+WHERE id NOT IN (SELECT MIN(id) FROM ingredients GROUP BY ingredient_key(label));
+
+ALTER TABLE ingredients
+DROP COLUMN unit;
+
+ALTER TABLE ingredients
+ALTER COLUMN label SET NOT NULL;
+
+CREATE UNIQUE INDEX ingredients_key_idx ON ingredients (ingredient_key(label));
+CREATE INDEX ingredients_label_trgm_idx ON ingredients USING GIN (label gin_trgm_ops);
+
+-- other names of an ingredient, "scallion" for "green onion"
+CREATE TABLE ingredient_synonyms (
+    id SERIAL PRIMARY KEY,
+    ingredient_id INTEGER NOT NULL REFERENCES ingredients(id) ON DELETE CASCADE,
+    label VARCHAR NOT NULL
+);
+
+CREATE UNIQUE INDEX ingredient_synonyms_key_idx ON ingredient_synonyms (ingredient_key(label));
+CREATE INDEX ingredient_synonyms_label_trgm_idx ON ingredient_synonyms USING GIN (label gin_trgm_ops);
@@ -0,0 +1,13 @@
+UPDATE recipes
+SET kcal = COALESCE(kcal, 0),
+    carbs = COALESCE(carbs, 0),
+    proteins = COALESCE(proteins, 0),
+    fats = COALESCE(fats, 0);
+
+ALTER TABLE recipes
+ALTER COLUMN kcal SET DEFAULT 0,
+ALTER COLUMN carbs SET DEFAULT 0,
+ALTER COLUMN proteins SET DEFAULT 0,
+ALTER COLUMN fats SET DEFAULT 0;
+
+DROP TABLE ingredient_nutrients;
@@ -0,0 +1,27 @@
+-- nutrients per 100 g, filled by the `import-nutrients` command from USDA FoodData Central
+CREATE TABLE ingredient_nutrients (
+    ingredient_id INTEGER PRIMARY KEY REFERENCES ingredients(id) ON DELETE CASCADE,
+    fdc_id INTEGER NOT NULL,
+    description VARCHAR NOT NULL,
+    kcal REAL NOT NULL,
+    carbs REAL NOT NULL,
+    proteins REAL NOT NULL,
+    fats REAL NOT NULL,
+    -- weight of one ml, for volume units
+    grams_per_ml REAL,
+    -- weight of one piece, for amounts without unit or count units like clove
+    grams_per_piece REAL
+);
+
+-- nutrition on recipes is a manual override of the computed values now, 0 was the "not entered" default
+ALTER TABLE recipes
+ALTER COLUMN kcal DROP DEFAULT,
+ALTER COLUMN carbs DROP DEFAULT,
+ALTER COLUMN proteins DROP DEFAULT,
+ALTER COLUMN fats DROP DEFAULT;
+
+UPDATE recipes
+SET kcal = NULLIF(kcal, 0),
+    carbs = NULLIF(carbs, 0),
+    proteins = NULLIF(proteins, 0),
+    fats = NULLIF(fats, 0);
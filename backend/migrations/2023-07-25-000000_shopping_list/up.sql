@@ -0,0 +1,12 @@
+CREATE TABLE shopping_list_items (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ingredient_id INTEGER REFERENCES ingredients(id) ON DELETE SET NULL,
+    label VARCHAR NOT NULL,
+    amount REAL,
+    unit VARCHAR,
+    checked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX shopping_list_items_user_idx ON shopping_list_items (user_id);
@@ -0,0 +1,2 @@
+DROP TABLE meal_plan_feeds;
+DROP TABLE meal_plan_entries;
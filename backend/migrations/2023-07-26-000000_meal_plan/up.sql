@@ -0,0 +1,18 @@
+CREATE TABLE meal_plan_entries (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    recipe_id INTEGER NOT NULL REFERENCES recipes(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    slot VARCHAR NOT NULL CHECK (slot IN ('breakfast', 'lunch', 'dinner', 'snack')),
+    servings SMALLINT NOT NULL CHECK (servings > 0),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX meal_plan_entries_user_date_idx ON meal_plan_entries (user_id, date);
+
+-- secret of the calendar subscription url, calendar apps cannot send a bearer token
+CREATE TABLE meal_plan_feeds (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    token VARCHAR NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,14 @@
+-- items keep the name they were added with, names the catalogue does not know
+-- are stored without an ingredient instead of being added to the catalogue
+CREATE TABLE pantry_items (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ingredient_id INTEGER REFERENCES ingredients(id) ON DELETE SET NULL,
+    label VARCHAR NOT NULL,
+    amount REAL,
+    unit VARCHAR,
+    expires_on DATE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX pantry_items_user_idx ON pantry_items (user_id);
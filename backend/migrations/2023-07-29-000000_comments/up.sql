@@ -0,0 +1,12 @@
+CREATE TABLE comments (
+    id SERIAL PRIMARY KEY,
+    recipe_id INTEGER NOT NULL REFERENCES recipes(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES comments(id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP
+);
+
+CREATE INDEX comments_recipe_id_idx ON comments (recipe_id, created_at DESC) WHERE parent_id IS NULL;
+CREATE INDEX comments_parent_id_idx ON comments (parent_id);
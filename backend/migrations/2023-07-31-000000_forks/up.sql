@@ -0,0 +1,3 @@
+ALTER TABLE recipes ADD COLUMN forked_from INTEGER REFERENCES recipes(id) ON DELETE SET NULL;
+
+CREATE INDEX recipes_forked_from_idx ON recipes (forked_from);
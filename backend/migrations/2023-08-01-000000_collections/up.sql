@@ -0,0 +1,30 @@
+CREATE TABLE collections (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(120) NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, name)
+);
+
+-- toggled bookmarks land in the default collection
+CREATE UNIQUE INDEX collections_default_idx ON collections (user_id) WHERE is_default;
+
+CREATE TABLE collection_recipes (
+    collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+    recipe_id INTEGER NOT NULL REFERENCES recipes(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    added_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (collection_id, recipe_id)
+);
+
+CREATE INDEX collection_recipes_recipe_id_idx ON collection_recipes (recipe_id);
+
+INSERT INTO collections (user_id, name, is_default)
+SELECT DISTINCT user_id, 'Bookmarks', TRUE FROM bookmarks;
+
+INSERT INTO collection_recipes (collection_id, recipe_id, position)
+SELECT collections.id, bookmarks.recipe_id,
+    ROW_NUMBER() OVER (PARTITION BY bookmarks.user_id ORDER BY bookmarks.recipe_id)
+FROM bookmarks
+INNER JOIN collections ON collections.user_id = bookmarks.user_id AND collections.is_default;
@@ -0,0 +1,3 @@
+-- public recipes are listed, unlisted ones are reachable by link only, private ones by their owners only
+ALTER TABLE recipes ADD COLUMN visibility VARCHAR NOT NULL DEFAULT 'public'
+    CHECK (visibility IN ('public', 'unlisted', 'private'));
@@ -0,0 +1 @@
+ALTER TABLE recipes_users DROP COLUMN role;
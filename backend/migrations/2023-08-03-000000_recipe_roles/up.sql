@@ -0,0 +1,6 @@
+-- everyone attached so far created the recipe
+ALTER TABLE recipes_users ADD COLUMN role VARCHAR NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+
+-- one owner per recipe, collaborators are editors or viewers
+CREATE UNIQUE INDEX recipes_users_owner_idx ON recipes_users (recipe_id) WHERE role = 'owner';
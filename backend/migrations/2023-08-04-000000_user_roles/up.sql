@@ -0,0 +1,5 @@
+ALTER TABLE users ADD COLUMN role VARCHAR NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'admin'));
+
+-- disabled accounts cannot log in, a required reset only lets the user change the password
+ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
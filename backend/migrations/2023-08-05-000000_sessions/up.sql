@@ -0,0 +1,14 @@
+-- one row per logged in device, the refresh token changes on every refresh
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 of the refresh tokens, the previous one is kept to notice reuse of a stolen token
+    refresh_token_hash VARCHAR NOT NULL UNIQUE,
+    previous_token_hash VARCHAR UNIQUE,
+    user_agent VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
@@ -0,0 +1,10 @@
+-- Nutrition of the whole recipe computed from its ingredients, kept up to date by the backend
+-- whenever ingredients or nutrient data change. Listings sort and filter by the value entered
+-- on the recipe or else this one.
+CREATE TABLE recipe_nutrition (
+    recipe_id INTEGER PRIMARY KEY REFERENCES recipes(id) ON DELETE CASCADE,
+    kcal REAL,
+    carbs REAL,
+    proteins REAL,
+    fats REAL
+);
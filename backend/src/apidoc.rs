@@ -1,5 +1,6 @@
 use crate::controllers::*;
 use crate::models::*;
+use crate::units::UnitSystem;
 use rocket::http::Status;
 use utoipa::{
     openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
@@ -12,29 +13,101 @@ use utoipa::{
         title = "Crimson Eagle's Recipe App"
     ),
     paths(
+        admin_controller::admin_users,
+        admin_controller::update_user_role,
+        admin_controller::disable_user,
+        admin_controller::enable_user,
+        admin_controller::reset_user_password,
+        admin_controller::admin_delete_recipe,
+        admin_controller::admin_restore_recipe,
+        admin_controller::rename_tag,
+        admin_controller::delete_tag,
         bookmark_controller::bookmarked_list,
         bookmark_controller::toggle_bookmark,
         recipe_controller::recipe,
         recipe_controller::search,
+        recipe_filter_controller::filter_recipes,
         recipe_controller::single_recipe,
         recipe_create_controller::create_recipe,
         recipe_update_controller::update_recipe,
         recipe_controller::delete,
+        ingredient_controller::ingredient_list,
+        ingredient_controller::merge_ingredient,
+        ingredient_controller::add_ingredient_synonym,
+        ingredient_controller::parse_ingredients,
+        shopping_list_controller::shopping_list,
+        shopping_list_controller::export_shopping_list,
+        shopping_list_controller::add_recipes_to_shopping_list,
+        shopping_list_controller::add_shopping_item,
+        shopping_list_controller::update_shopping_item,
+        shopping_list_controller::delete_shopping_item,
+        shopping_list_controller::clear_shopping_list,
+        meal_plan_controller::meal_plan,
+        meal_plan_controller::single_meal,
+        meal_plan_controller::plan_meal,
+        meal_plan_controller::update_meal,
+        meal_plan_controller::delete_meal,
+        meal_plan_controller::export_meal_plan,
+        meal_plan_controller::create_meal_plan_feed,
+        meal_plan_controller::delete_meal_plan_feed,
+        pantry_controller::pantry,
+        pantry_controller::add_pantry_item,
+        pantry_controller::update_pantry_item,
+        pantry_controller::delete_pantry_item,
+        pantry_controller::cookable_recipes,
+        rating_controller::rate_recipe,
+        rating_controller::delete_rating,
+        rating_controller::recipe_reviews,
+        comment_controller::recipe_comments,
+        comment_controller::add_comment,
+        comment_controller::update_comment,
+        comment_controller::delete_comment,
+        revision_controller::list_revisions,
+        revision_controller::single_revision,
+        revision_controller::revision_diff,
+        revision_controller::revert_recipe,
+        fork_controller::fork_recipe,
+        fork_controller::recipe_forks,
+        collaborator_controller::collaborators,
+        collaborator_controller::invite_collaborator,
+        collaborator_controller::update_collaborator,
+        collaborator_controller::remove_collaborator,
+        collaborator_controller::transfer_ownership,
+        collection_controller::user_collections,
+        collection_controller::add_collection,
+        collection_controller::rename_collection,
+        collection_controller::delete_collection,
+        collection_controller::add_collection_recipe,
+        collection_controller::remove_collection_recipe,
+        collection_controller::reorder_collection,
         tag_controller::tag_list,
         tag_controller::single_tag,
         tag_controller::create_tag,
         tag_controller::toggle_tag,
+        trash_controller::trash_list,
+        trash_controller::restore,
+        session_controller::refresh_token,
+        session_controller::logout,
+        session_controller::user_sessions,
+        session_controller::revoke_session,
+        session_controller::revoke_other_sessions,
+        session_controller::jwks,
         user_controller::login,
         user_controller::register,
         user_controller::profile,
         user_controller::change_password
     ),
     components(
-        schemas(RecipeResultDTO, RecipesInput, RecipePutDTO, PaginatedResult<RecipeResultDTO>),
+        schemas(RecipeResultDTO, RecipesInput, RecipePutDTO, PaginatedResult<RecipeResultDTO>, FilteredResult, RecipeFacets, TagFacet, TimerFacet, TagMatch, RecipeSort, SortDirection, UnitSystem, IngredientParseDTO, ParsedIngredientDTO, IngredientDTO, IngredientCatalogDTO, IngredientMergeDTO, IngredientSynonymDTO, NutritionDTO, NutritionValues, ShoppingItemDTO, ShoppingListRecipesDTO, ShoppingRecipeDTO, ShoppingItemPostDTO, ShoppingItemPutDTO, MealSlot, MealPlanEntryDTO, MealPlanPostDTO, MealPlanPutDTO, MealPlanFeedDTO, PantryItemDTO, PantryItemPostDTO, PantryItemPutDTO, CookableSort, CookableRecipeDTO, RatingPutDTO, RatingDTO, ReviewDTO, CommentDTO, CommentPostDTO, CommentPutDTO, RevisionDTO, RecipeDiffDTO, ChangeKind, FieldChangeDTO, InstructionChangeDTO, IngredientChangeDTO, CollectionDTO, CollectionPostDTO, CollectionPutDTO, CollectionOrderDTO, Visibility, RecipeRole, CollaboratorDTO, CollaboratorPostDTO, CollaboratorPutDTO, OwnershipTransferDTO, UserRole, AdminUserDTO, UserRolePutDTO, PasswordResetDTO, TokenResponse, RefreshTokenRequest, SessionDTO, JsonWebKeySet),
     ),
     tags(
         (name = "recipes", description = "Recipes endpoints."),
+        (name = "shopping list", description = "Shopping list endpoints."),
+        (name = "meal plan", description = "Meal plan endpoints."),
+        (name = "pantry", description = "Pantry endpoints."),
+        (name = "collections", description = "Bookmark collection endpoints."),
         (name = "auth", description = "Authentication endpoints."),
+        (name = "admin", description = "Administration endpoints, admins only."),
     ),
     servers(
         (url = "http://127.0.0.1:8000", description = "Local development"),
@@ -0,0 +1,661 @@
+use bcrypt::{hash, DEFAULT_COST};
+use diesel::dsl::{exists, sql};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Text};
+use rand::distributions::{Alphanumeric, DistString};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use slug::slugify;
+use std::env;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{pagination, refresh_search_document, revoke_sessions};
+
+/// Give the admin role to a registered user
+pub fn grant_admin(c: &mut PgConnection, username: &str) -> QueryResult<usize> {
+    diesel::update(users::table.filter(users::username.eq(username)))
+        .set(users::role.eq(UserRole::Admin.as_str()))
+        .execute(c)
+}
+
+/// Run the `grant-admin` command, `args` are the command line arguments after the command name
+pub fn grant_admin_command(args: &[String]) -> Result<String, String> {
+    let username = match args.first() {
+        Some(u) => u,
+        None => return Err(String::from("Usage: grant-admin <username>")),
+    };
+
+    let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL must be set.")?;
+    let mut c = PgConnection::establish(&database_url)
+        .map_err(|err| format!("Cannot connect to the database: {}", err))?;
+
+    match grant_admin(&mut c, username) {
+        Ok(0) => Err(format!("There is no user called {}.", username)),
+        Ok(_) => Ok(format!("{} is an admin now.", username)),
+        Err(err) => Err(format!("Cannot save the role in the database: {}", err)),
+    }
+}
+
+/// Give the admin role to a registered user as long as nobody has it yet.
+/// `None` when there is an admin already, otherwise the number of users updated.
+fn bootstrap_admin(c: &mut PgConnection, username: &str) -> QueryResult<Option<usize>> {
+    let admin = UserRole::Admin.as_str();
+    let has_admin =
+        diesel::select(exists(users::table.filter(users::role.eq(admin)))).get_result::<bool>(c)?;
+    if has_admin {
+        return Ok(None);
+    }
+    // checked again in the update, for instances starting at the same time
+    diesel::update(users::table.filter(users::username.eq(username)))
+        .filter(
+            sql::<Bool>("NOT EXISTS (SELECT 1 FROM users WHERE role = ")
+                .bind::<Text, _>(admin)
+                .sql(")"),
+        )
+        .set(users::role.eq(admin))
+        .execute(c)
+        .map(Some)
+}
+
+/// Make the user named in `ADMIN_USERNAME` an admin at startup, if there is no admin yet.
+/// The account has to be registered already, so a new install needs a restart after signing up.
+/// Later admins are appointed by the first one or with `grant-admin`.
+pub fn admin_bootstrap_fairing(username: Option<String>) -> AdHoc {
+    AdHoc::on_liftoff("Admin bootstrap", move |rocket| {
+        Box::pin(async move {
+            let username = match username {
+                Some(u) => u,
+                None => return,
+            };
+            let conn = match LogsDbConn::get_one(rocket).await {
+                Some(conn) => conn,
+                None => {
+                    eprintln!("Admin bootstrap skipped: no database connection.");
+                    return;
+                }
+            };
+            let name = username.clone();
+            match conn.run(move |c| bootstrap_admin(c, &name)).await {
+                Ok(None) => println!("Admin bootstrap skipped: there is an admin already."),
+                Ok(Some(0)) => eprintln!("ADMIN_USERNAME {} is not a registered user.", username),
+                Ok(Some(_)) => println!("{} is an admin now (ADMIN_USERNAME).", username),
+                Err(err) => eprintln!("Admin bootstrap failed: {}", err),
+            }
+        })
+    })
+}
+
+fn admin_user(c: &mut PgConnection, user_id: i32) -> QueryResult<Option<AdminUserDTO>> {
+    users::table
+        .find(user_id)
+        .first::<User>(c)
+        .optional()
+        .map(|u| u.map(AdminUserDTO::from))
+}
+
+/// Set a flag on another user's account, admins cannot lock themselves out.
+/// With `end_sessions` the user is logged out on every device and the calendar feed token
+/// stops working.
+async fn update_account<T, V>(
+    conn: LogsDbConn,
+    admin: Result<AdminUser, NetworkResponse>,
+    user_id: i32,
+    values: V,
+    end_sessions: bool,
+) -> Result<AdminUserDTO, RecipeResponse<T>>
+where
+    V: AsChangeset<Target = users::table> + Send + 'static,
+    <V as AsChangeset>::Changeset: diesel::query_builder::QueryFragment<diesel::pg::Pg>,
+{
+    match admin {
+        Ok(a) if a.user_id == user_id => {
+            return Err(RecipeResponse::BadRequest(String::from(
+                "Admins cannot change their own account.",
+            )))
+        }
+        Ok(_) => (),
+        Err(err) => return Err(RecipeResponse::from(err)),
+    };
+
+    match conn
+        .run(move |c| {
+            c.transaction(|c| {
+                diesel::update(users::table.find(user_id))
+                    .set(values)
+                    .execute(c)?;
+                if end_sessions {
+                    revoke_sessions(c, user_id)?;
+                    diesel::delete(meal_plan_feeds::table.find(user_id)).execute(c)?;
+                }
+                admin_user(c, user_id)
+            })
+        })
+        .await
+    {
+        Ok(Some(res)) => Ok(res),
+        Ok(None) => Err(RecipeResponse::NotFound(String::from(
+            "The user was not found.",
+        ))),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot save the user in the database.",
+        ))),
+    }
+}
+
+/// List of users
+///
+/// Get all accounts, optionally only those whose username contains `q`.
+#[utoipa::path(
+    get,
+    path = "/admin/users?{q}&{page}&{per_page}",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Users found succesfully", body = [PaginatedResult<AdminUserDTO>]),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 500, description = "Error loading users"),
+    ),
+    params(
+        ("q" = Option<String>, Query, description = "Part of the username"),
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/admin/users?<q>&<page>&<per_page>")]
+pub async fn admin_users(
+    conn: LogsDbConn,
+    q: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<AdminUserDTO>> {
+    if let Err(err) = admin {
+        return RecipeResponse::from(err);
+    }
+
+    let pattern = format!(
+        "%{}%",
+        q.unwrap_or_default()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let result = conn
+        .run(move |c| {
+            let total = users::table
+                .filter(users::username.ilike(&pattern))
+                .count()
+                .get_result::<i64>(c)?;
+            let (current_page, per_page, offset) = pagination(page, per_page, total);
+            let records = users::table
+                .filter(users::username.ilike(&pattern))
+                .order(users::username.asc())
+                .offset(offset)
+                .limit(per_page)
+                .load::<User>(c)?
+                .into_iter()
+                .map(AdminUserDTO::from)
+                .collect::<Vec<AdminUserDTO>>();
+            Ok::<_, diesel::result::Error>(PaginatedResult {
+                records,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor: None,
+                prev_cursor: None,
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read users from the database.",
+        )),
+    }
+}
+
+/// Change user role
+///
+/// Make a user an admin, or take the admin role away.
+#[utoipa::path(
+    put,
+    path = "/admin/users/{user_id}/role",
+    tag = "admin",
+    request_body = UserRolePutDTO,
+    responses(
+        (status = 200, description = "Role changed succesfully", body = AdminUserDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "User was not found"),
+        (status = 422, description = "Admins cannot change their own role"),
+        (status = 500, description = "Error saving the user"),
+    ),
+    params(
+        ("user_id" = i32, description = "User id", example = 3),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/admin/users/<user_id>/role", data = "<update>")]
+pub async fn update_user_role(
+    conn: LogsDbConn,
+    user_id: i32,
+    update: Json<UserRolePutDTO>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<AdminUserDTO> {
+    let role = update.role.as_str();
+    match update_account(conn, admin, user_id, users::role.eq(role), false).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(response) => response,
+    }
+}
+
+/// Disable user
+///
+/// Lock a user out. Disabled users cannot log in and their tokens stop working.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/disable",
+    tag = "admin",
+    responses(
+        (status = 200, description = "User disabled succesfully", body = AdminUserDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "User was not found"),
+        (status = 422, description = "Admins cannot disable themselves"),
+        (status = 500, description = "Error saving the user"),
+    ),
+    params(
+        ("user_id" = i32, description = "User id", example = 3),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/admin/users/<user_id>/disable")]
+pub async fn disable_user(
+    conn: LogsDbConn,
+    user_id: i32,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<AdminUserDTO> {
+    match update_account(conn, admin, user_id, users::disabled.eq(true), true).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(response) => response,
+    }
+}
+
+/// Enable user
+///
+/// Let a disabled user log in again.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/enable",
+    tag = "admin",
+    responses(
+        (status = 200, description = "User enabled succesfully", body = AdminUserDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "User was not found"),
+        (status = 422, description = "Admins cannot enable themselves"),
+        (status = 500, description = "Error saving the user"),
+    ),
+    params(
+        ("user_id" = i32, description = "User id", example = 3),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/admin/users/<user_id>/enable")]
+pub async fn enable_user(
+    conn: LogsDbConn,
+    user_id: i32,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<AdminUserDTO> {
+    match update_account(conn, admin, user_id, users::disabled.eq(false), false).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(response) => response,
+    }
+}
+
+/// Force password reset
+///
+/// Replace the password of a user with a temporary one. After logging in with it
+/// the user can only change the password, existing tokens can do nothing else either.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/password_reset",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Password reset succesfully", body = PasswordResetDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "User was not found"),
+        (status = 422, description = "Admins cannot reset their own password"),
+        (status = 500, description = "Error saving the user"),
+    ),
+    params(
+        ("user_id" = i32, description = "User id", example = 3),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/admin/users/<user_id>/password_reset")]
+pub async fn reset_user_password(
+    conn: LogsDbConn,
+    user_id: i32,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<PasswordResetDTO> {
+    let temporary_password = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+    let hashed = match hash(&temporary_password, DEFAULT_COST) {
+        Ok(h) => h,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Failed to hash the password.",
+            ))
+        }
+    };
+
+    match update_account(
+        conn,
+        admin,
+        user_id,
+        (
+            users::password.eq(hashed),
+            users::password_reset_required.eq(true),
+        ),
+        true,
+    )
+    .await
+    {
+        Ok(_) => RecipeResponse::Ok(Json(PasswordResetDTO { temporary_password })),
+        Err(response) => response,
+    }
+}
+
+/// Delete any recipe
+///
+/// Move a recipe of any user to the trash, or remove it for good with `permanent`.
+/// Recipes already in the trash are always removed for good.
+#[utoipa::path(
+    delete,
+    path = "/admin/recipes/{recipe_id}",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Recipe deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = NOT_FOUND, description = "Recipe was not found"),
+        (status = 500, description = "Error deleting the recipe"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("permanent" = Option<bool>, Query, description = "Skip the trash"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/admin/recipes/<recipe_id>?<permanent>")]
+pub async fn admin_delete_recipe(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    permanent: Option<bool>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> Result<Status, RecipeResponse<RecipeResultDTO>> {
+    if let Err(err) = admin {
+        return Err(RecipeResponse::from(err));
+    }
+
+    let num_deleted = conn
+        .run(move |c| {
+            let trashed = match recipes::table
+                .find(recipe_id)
+                .select(recipes::deleted_at)
+                .first::<Option<chrono::NaiveDateTime>>(c)
+                .optional()?
+            {
+                Some(deleted_at) => deleted_at.is_some(),
+                None => return Ok(0),
+            };
+            // dependent rows are removed by ON DELETE CASCADE
+            if permanent.unwrap_or(false) || trashed {
+                diesel::delete(recipes::table.find(recipe_id)).execute(c)
+            } else {
+                diesel::update(recipes::table.find(recipe_id))
+                    .set(recipes::deleted_at.eq(diesel::dsl::now))
+                    .execute(c)
+            }
+        })
+        .await;
+
+    match num_deleted {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from("Recipe not found."))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(err) => Err(RecipeResponse::InternalServerError(format!(
+            "Database error while deleting the recipe: {}",
+            err
+        ))),
+    }
+}
+
+/// Restore any recipe
+///
+/// Take a recipe of any user out of the trash.
+#[utoipa::path(
+    post,
+    path = "/admin/recipes/{recipe_id}/restore",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Recipe restored succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = NOT_FOUND, description = "Recipe was not found in the trash"),
+        (status = 500, description = "Error restoring the recipe"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/admin/recipes/<recipe_id>/restore")]
+pub async fn admin_restore_recipe(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> Result<Status, RecipeResponse<RecipeResultDTO>> {
+    if let Err(err) = admin {
+        return Err(RecipeResponse::from(err));
+    }
+
+    let num_restored = conn
+        .run(move |c| {
+            diesel::update(
+                recipes::table
+                    .find(recipe_id)
+                    .filter(recipes::deleted_at.is_not_null()),
+            )
+            .set(recipes::deleted_at.eq(None::<chrono::NaiveDateTime>))
+            .execute(c)
+        })
+        .await;
+
+    match num_restored {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "The recipe is not in the trash.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot restore the recipe in the database.",
+        ))),
+    }
+}
+
+/// Recipes whose search document mentions a tag
+fn tagged_recipe_ids(c: &mut PgConnection, tag_ids: &[i32]) -> QueryResult<Vec<i32>> {
+    recipes_tags::table
+        .filter(recipes_tags::tag_id.eq_any(tag_ids))
+        .select(recipes_tags::recipe_id)
+        .distinct()
+        .load::<i32>(c)
+}
+
+/// Rename tag
+///
+/// Change the label of a tag on every recipe. The slug follows the new label.
+#[utoipa::path(
+    put,
+    path = "/admin/tags/{tag_slug}",
+    tag = "admin",
+    request_body = TagPostDTO,
+    responses(
+        (status = 200, description = "Tag renamed succesfully", body = TagDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = NOT_FOUND, description = "Tag was not found"),
+        (status = 422, description = "Validation error or the new name is taken"),
+        (status = 500, description = "Error saving the tag"),
+    ),
+    params(
+        ("tag_slug" = String, description = "Tag slug", example = "gluten-free"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/admin/tags/<tag_slug>", data = "<tag>")]
+pub async fn rename_tag(
+    conn: LogsDbConn,
+    tag_slug: String,
+    tag: Json<TagPostDTO>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<TagDTO> {
+    if let Err(err) = admin {
+        return RecipeResponse::from(err);
+    }
+
+    match tag.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let label = tag.into_inner().label;
+    let new_slug = slugify(&label);
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let found = match tags::table
+                    .filter(tags::slug.eq(&tag_slug))
+                    .first::<Tag>(c)
+                    .optional()?
+                {
+                    Some(t) => t,
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "Tag not found.",
+                        ))))
+                    }
+                };
+                let taken = tags::table
+                    .filter(tags::slug.eq(&new_slug))
+                    .filter(tags::id.ne(found.id))
+                    .count()
+                    .get_result::<i64>(c)?;
+                if taken > 0 {
+                    return Ok(Err(RecipeResponse::BadRequest(String::from(
+                        "A tag with this name already exists.",
+                    ))));
+                }
+                let renamed = diesel::update(tags::table.find(found.id))
+                    .set((tags::label.eq(&label), tags::slug.eq(&new_slug)))
+                    .get_result::<Tag>(c)?;
+                for recipe_id in tagged_recipe_ids(c, &[found.id])? {
+                    refresh_search_document(c, recipe_id)?;
+                }
+                Ok::<_, diesel::result::Error>(Ok(TagDTO::from(renamed)))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the tag in the database.",
+        )),
+    }
+}
+
+/// Delete tag
+///
+/// Remove a tag from every recipe and from the tag list.
+#[utoipa::path(
+    delete,
+    path = "/admin/tags/{tag_slug}",
+    tag = "admin",
+    responses(
+        (status = 204, description = "Tag deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = NOT_FOUND, description = "Tag was not found"),
+        (status = 500, description = "Error deleting the tag"),
+    ),
+    params(
+        ("tag_slug" = String, description = "Tag slug", example = "gluten-free"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/admin/tags/<tag_slug>")]
+pub async fn delete_tag(
+    conn: LogsDbConn,
+    tag_slug: String,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> Result<Status, RecipeResponse<TagDTO>> {
+    if let Err(err) = admin {
+        return Err(RecipeResponse::from(err));
+    }
+
+    let num_deleted = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let tag_ids = tags::table
+                    .filter(tags::slug.eq(&tag_slug))
+                    .select(tags::id)
+                    .load::<i32>(c)?;
+                let recipe_ids = tagged_recipe_ids(c, &tag_ids)?;
+                diesel::delete(recipes_tags::table.filter(recipes_tags::tag_id.eq_any(&tag_ids)))
+                    .execute(c)?;
+                let num =
+                    diesel::delete(tags::table.filter(tags::id.eq_any(&tag_ids))).execute(c)?;
+                for recipe_id in recipe_ids {
+                    refresh_search_document(c, recipe_id)?;
+                }
+                Ok::<_, diesel::result::Error>(num)
+            })
+        })
+        .await;
+
+    match num_deleted {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from("Tag not found."))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the tag from the database.",
+        ))),
+    }
+}
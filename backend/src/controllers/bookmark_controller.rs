@@ -3,92 +3,156 @@ use rocket::serde::json::Json;
 
 use crate::models::*;
 use crate::schema::*;
+use crate::units::UnitSystem;
 use crate::LogsDbConn;
 
+use super::convert_units;
 use super::get_recipe_elements;
-use super::pagination;
+use super::{add_to_collection, default_collection, remove_orphan_bookmarks, user_collection};
+use super::{load_recipe_page, pagination, Listing, RecipePage};
+use super::{readable_recipe, visible_recipe_ids, visible_recipes};
 
 /// List of bookmarked recipes
 ///
 /// Get all recipes that are bookmarked by the logged in user from the database.
+/// With `collection` only the recipes of that collection are listed, in the order of the
+/// collection unless `sort` or `cursor` is given.
 #[utoipa::path(
     get,
-    path = "/bookmarks?{page}&{per_page}",
+    path = "/bookmarks?{collection}&{page}&{per_page}&{sort}&{direction}&{cursor}&{with_total}&{units}",
     tag = "recipes",
     responses(
         (status = 200, description = "Bookmarked recipes found succesfully", body = [PaginatedResult<RecipeResultDTO>]),
+        (status = 404, description = "Collection was not found"),
+        (status = 422, description = "Invalid cursor or sort key"),
         (status = 500, description = "Error loading recipes"),
     ),
     params(
+        ("collection" = Option<i32>, Query, description = "Only list the recipes of this collection"),
         ("page" = Option<i64>, Query, description = "Pagination: page number"),
         ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+        ("sort" = Option<RecipeSort>, Query, description = "Sort key: title, created_at, updated_at, timer, kcal, ingredients (number of ingredients), bookmarks (number of bookmarks), rating (average rating). Defaults to updated_at"),
+        ("direction" = Option<SortDirection>, Query, description = "Sort direction: asc or desc. Defaults to asc for title, timer and kcal, desc for the rest"),
+        ("cursor" = Option<String>, Query, description = "Cursor pagination: next_cursor or prev_cursor of a previous response, overrides page, sort and direction"),
+        ("with_total" = Option<bool>, Query, description = "Cursor pagination: count the total as well, defaults to false"),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
     ),
 )]
-#[get("/bookmarks?<page>&<per_page>")]
+#[get("/bookmarks?<collection>&<page>&<per_page>&<sort>&<direction>&<cursor>&<with_total>&<units>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn bookmarked_list(
     conn: LogsDbConn,
+    collection: Option<i32>,
     page: Option<i64>,
     per_page: Option<i64>,
+    sort: Option<RecipeSort>,
+    direction: Option<SortDirection>,
+    cursor: Option<String>,
+    with_total: Option<bool>,
+    units: Option<UnitSystem>,
     key: Result<Jwt, NetworkResponse>,
 ) -> RecipeResponse<PaginatedResult<RecipeResultDTO>> {
-    let user_id: Option<i32> = match key {
-        Ok(k) => Some(k.claims.subject_id),
-        Err(_) => None,
+    let user_id: i32 = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your bookmarked recipes.",
+            ))
+        }
     };
 
-    if user_id.is_none() {
-        return RecipeResponse::Unauthorized(String::from(
-            "Please log in to see your bookmarked recipes.",
-        ));
-    }
+    let by_position = collection.is_some() && sort.is_none() && cursor.is_none();
+    let listing = match Listing::new(page, per_page, sort, direction, cursor, with_total) {
+        Ok(l) => l,
+        Err(err) => return RecipeResponse::BadRequest(err),
+    };
 
-    let total: i64 = match conn
+    let mut recipe_page = match conn
         .run(move |c| {
-            recipes::table
-                .inner_join(bookmarks::table)
-                .filter(bookmarks::user_id.eq(user_id.unwrap()))
-                .count()
-                .get_result(c)
+            let collection_id = match collection {
+                Some(id) => match user_collection(c, id, user_id)? {
+                    Some(found) => Some(found.id),
+                    None => return Ok(None),
+                },
+                None => None,
+            };
+
+            if let (Some(id), true) = (collection_id, by_position) {
+                let ordered = || {
+                    collection_recipes::table
+                        .filter(collection_recipes::collection_id.eq(id))
+                        .filter(
+                            collection_recipes::recipe_id.eq_any(visible_recipe_ids(Some(user_id))),
+                        )
+                };
+                let total = ordered().count().get_result::<i64>(c)?;
+                let (current_page, per_page, offset) =
+                    pagination(listing.page, listing.per_page, total);
+                let ids = ordered()
+                    .order((
+                        collection_recipes::position.asc(),
+                        collection_recipes::recipe_id.asc(),
+                    ))
+                    .limit(per_page)
+                    .offset(offset)
+                    .select(collection_recipes::recipe_id)
+                    .load::<i32>(c)?;
+                let mut records = recipes::table
+                    .filter(recipes::id.eq_any(&ids))
+                    .load::<Recipe>(c)?;
+                records.sort_by_key(|r| ids.iter().position(|id| *id == r.id));
+                return Ok(Some(RecipePage {
+                    records,
+                    total: Some(total),
+                    current_page: Some(current_page),
+                    per_page,
+                    next_cursor: None,
+                    prev_cursor: None,
+                }));
+            }
+
+            load_recipe_page(
+                c,
+                || {
+                    let query = recipes::table
+                        .filter(recipes::deleted_at.is_null())
+                        .filter(visible_recipes(Some(user_id)))
+                        .into_boxed();
+                    match collection_id {
+                        Some(id) => query.filter(
+                            recipes::id.eq_any(
+                                collection_recipes::table
+                                    .filter(collection_recipes::collection_id.eq(id))
+                                    .select(collection_recipes::recipe_id),
+                            ),
+                        ),
+                        None => query.filter(
+                            recipes::id.eq_any(
+                                bookmarks::table
+                                    .filter(bookmarks::user_id.eq(user_id))
+                                    .select(bookmarks::recipe_id),
+                            ),
+                        ),
+                    }
+                },
+                &listing,
+            )
+            .map(Some)
         })
         .await
     {
-        Ok(c) => c,
+        Ok(Some(res)) => res,
+        Ok(None) => return RecipeResponse::NotFound(String::from("The collection was not found.")),
         Err(_) => {
             return RecipeResponse::InternalServerError(String::from(
-                "Database error while counting records.",
+                "Cannot read recipes from the database.",
             ))
         }
     };
 
-    let (current_page, per_page, offset) = pagination(page, per_page, total);
-
-    let recipes_list = match conn
-        .run(move |c| {
-            recipes::table
-                .inner_join(bookmarks::table)
-                .filter(bookmarks::user_id.eq(user_id.unwrap()))
-                .select(Recipe::as_select())
-                .order(recipes::updated_at.desc())
-                .offset(offset)
-                .limit(per_page)
-                .load::<Recipe>(c)
-        })
-        .await
-    {
-        Ok(res) => res,
-        Err(err) => return RecipeResponse::InternalServerError(err.to_string()),
-    };
-
-    match get_recipe_elements(recipes_list, conn, user_id).await {
-        Ok(res) => {
-            let paginated = PaginatedResult {
-                records: res,
-                total,
-                current_page,
-                per_page,
-            };
-            RecipeResponse::Ok(Json(paginated))
-        }
+    let recipes_list = std::mem::take(&mut recipe_page.records);
+    match get_recipe_elements(recipes_list, &conn, Some(user_id)).await {
+        Ok(res) => RecipeResponse::Ok(Json(recipe_page.into_result(convert_units(res, units)))),
         Err(_) => RecipeResponse::InternalServerError(String::from(
             "Cannot read recipes from the database.",
         )),
@@ -97,7 +161,8 @@ pub async fn bookmarked_list(
 
 /// Toggle bookmark on a recipe
 ///
-/// Set or unset bookmark relationship with a recipe.
+/// Set or unset bookmark relationship with a recipe. A new bookmark goes to the default
+/// collection, removing a bookmark takes the recipe out of every collection.
 /// Returns bookmarked state after update (true if bookmarked, false if not).
 #[utoipa::path(
     put,
@@ -105,6 +170,7 @@ pub async fn bookmarked_list(
     tag = "recipes",
     responses(
         (status = 200, description = "Bookmark relationship handled succesfully", body = bool),
+        (status = 404, description = "Recipe was not found"),
         (status = 500, description = "Internal Server Error"),
     ),
     params(
@@ -130,42 +196,45 @@ pub async fn toggle_bookmark(
     }
     let user_id = user_id.unwrap();
 
-    match conn
+    let result = conn
         .run(move |c| {
-            bookmarks::table
-                .find((recipe_id, user_id))
-                .first::<Bookmark>(c)
-        })
-        .await
-    {
-        Ok(res) => {
-            // remove
-            match conn.run(move |c| diesel::delete(&res).execute(c)).await {
-                Ok(_) => RecipeResponse::Ok(Json(false)),
-                Err(_) => {
-                    RecipeResponse::InternalServerError(String::from("Error removing bookmark."))
-                }
-            }
-        }
-        Err(diesel::NotFound) => {
-            // add
-            match conn
-                .run(move |c| {
-                    diesel::insert_into(bookmarks::table)
-                        .values((
-                            bookmarks::recipe_id.eq(recipe_id),
-                            bookmarks::user_id.eq(user_id),
-                        ))
-                        .execute(c)
-                })
-                .await
-            {
-                Ok(_) => RecipeResponse::Ok(Json(true)),
-                Err(_) => {
-                    RecipeResponse::InternalServerError(String::from("Error adding bookmark."))
+            c.transaction(|c| {
+                let bookmarked = bookmarks::table
+                    .find((recipe_id, user_id))
+                    .first::<Bookmark>(c)
+                    .optional()?
+                    .is_some();
+                if bookmarked {
+                    // remove from every collection
+                    diesel::delete(
+                        collection_recipes::table
+                            .filter(collection_recipes::recipe_id.eq(recipe_id))
+                            .filter(
+                                collection_recipes::collection_id.eq_any(
+                                    collections::table
+                                        .filter(collections::user_id.eq(user_id))
+                                        .select(collections::id),
+                                ),
+                            ),
+                    )
+                    .execute(c)?;
+                    remove_orphan_bookmarks(c, user_id, &[recipe_id])?;
+                } else {
+                    if !readable_recipe(c, recipe_id, Some(user_id))? {
+                        return Ok(None);
+                    }
+                    // add to the default collection
+                    let collection = default_collection(c, user_id)?;
+                    add_to_collection(c, &collection, recipe_id)?;
                 }
-            }
-        }
-        Err(_) => RecipeResponse::InternalServerError(String::from("Error finding bookmark.")),
+                Ok::<_, diesel::result::Error>(Some(!bookmarked))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from("Error saving bookmark.")),
     }
 }
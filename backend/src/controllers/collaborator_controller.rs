@@ -0,0 +1,433 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{readable_recipe, recipe_role};
+
+/// Everyone attached to the recipe, the owner first
+fn recipe_collaborators(c: &mut PgConnection, recipe_id: i32) -> QueryResult<Vec<CollaboratorDTO>> {
+    let mut list = recipes_users::table
+        .inner_join(users::table)
+        .filter(recipes_users::recipe_id.eq(recipe_id))
+        .select((users::username, recipes_users::role))
+        .load::<(String, String)>(c)?
+        .into_iter()
+        .filter_map(|(username, role)| {
+            Some(CollaboratorDTO {
+                username,
+                role: RecipeRole::parse(&role)?,
+            })
+        })
+        .collect::<Vec<CollaboratorDTO>>();
+    list.sort_by(|a, b| {
+        (a.role != RecipeRole::Owner)
+            .cmp(&(b.role != RecipeRole::Owner))
+            .then(a.username.cmp(&b.username))
+    });
+    Ok(list)
+}
+
+fn user_by_name(c: &mut PgConnection, username: &str) -> QueryResult<Option<i32>> {
+    users::table
+        .filter(users::username.eq(username))
+        .select(users::id)
+        .first::<i32>(c)
+        .optional()
+}
+
+/// Recipe collaborators
+///
+/// Get the owner, editors and viewers of a recipe. Only they can see the list.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/collaborators",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Collaborators found succesfully", body = Vec<CollaboratorDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not a collaborator of the recipe"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Error loading collaborators"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/collaborators", rank = 2)]
+pub async fn collaborators(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<CollaboratorDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see the collaborators of a recipe.",
+            ))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, Some(user_id))? {
+                return Ok(Err(RecipeResponse::NotFound(String::from(
+                    "The recipe was not found.",
+                ))));
+            }
+            if recipe_role(c, recipe_id, user_id)?.is_none() {
+                return Ok(Err(RecipeResponse::Forbidden(String::from(
+                    "Only collaborators of the recipe can see who works on it.",
+                ))));
+            }
+            Ok::<_, diesel::result::Error>(Ok(recipe_collaborators(c, recipe_id)?))
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read collaborators from the database.",
+        )),
+    }
+}
+
+/// Invite collaborator
+///
+/// Add a user to a recipe by username, as editor or viewer. Editors can update the recipe,
+/// viewers can read it even when it is private. Only the owner can invite.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/collaborators",
+    tag = "recipes",
+    request_body = CollaboratorPostDTO,
+    responses(
+        (status = 201, description = "Collaborator added succesfully", body = CollaboratorDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
+        (status = 404, description = "Recipe or user was not found"),
+        (status = 422, description = "Owner role, or the user already collaborates"),
+        (status = 500, description = "Error saving the collaborator"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/collaborators", data = "<invite>")]
+pub async fn invite_collaborator(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    invite: Json<CollaboratorPostDTO>,
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<CollaboratorDTO> {
+    if let Err(err) = owned.and_then(|o| o.owner_only("share")) {
+        return RecipeResponse::from(err);
+    }
+
+    match invite.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let invite = invite.into_inner();
+    if invite.role == RecipeRole::Owner {
+        return RecipeResponse::BadRequest(String::from(
+            "Transfer the ownership to make someone else the owner.",
+        ));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let user_id = match user_by_name(c, &invite.username)? {
+                    Some(id) => id,
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "The user was not found.",
+                        ))))
+                    }
+                };
+                if recipe_role(c, recipe_id, user_id)?.is_some() {
+                    return Ok(Err(RecipeResponse::BadRequest(String::from(
+                        "The user already collaborates on the recipe.",
+                    ))));
+                }
+                diesel::insert_into(recipes_users::table)
+                    .values((
+                        recipes_users::recipe_id.eq(recipe_id),
+                        recipes_users::user_id.eq(user_id),
+                        recipes_users::role.eq(invite.role.as_str()),
+                    ))
+                    .execute(c)?;
+                Ok::<_, diesel::result::Error>(Ok(CollaboratorDTO {
+                    username: invite.username,
+                    role: invite.role,
+                }))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Created(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collaborator in the database.",
+        )),
+    }
+}
+
+/// Change collaborator role
+///
+/// Make a collaborator an editor or a viewer. Only the owner can change roles.
+#[utoipa::path(
+    put,
+    path = "/recipes/{recipe_id}/collaborators/{username}",
+    tag = "recipes",
+    request_body = CollaboratorPutDTO,
+    responses(
+        (status = 200, description = "Role changed succesfully", body = CollaboratorDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
+        (status = 404, description = "Recipe or collaborator was not found"),
+        (status = 422, description = "Owner role, or the collaborator is the owner"),
+        (status = 500, description = "Error saving the collaborator"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("username" = String, description = "Username of the collaborator", example = "jane"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/recipes/<recipe_id>/collaborators/<username>", data = "<update>")]
+pub async fn update_collaborator(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    username: String,
+    update: Json<CollaboratorPutDTO>,
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<CollaboratorDTO> {
+    if let Err(err) = owned.and_then(|o| o.owner_only("share")) {
+        return RecipeResponse::from(err);
+    }
+    let role = update.role;
+    if role == RecipeRole::Owner {
+        return RecipeResponse::BadRequest(String::from(
+            "Transfer the ownership to make someone else the owner.",
+        ));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let user_id = user_by_name(c, &username)?;
+                let current = match user_id {
+                    Some(id) => recipe_role(c, recipe_id, id)?,
+                    None => None,
+                };
+                match (user_id, current) {
+                    (Some(_), Some(RecipeRole::Owner)) => Ok(Err(RecipeResponse::BadRequest(
+                        String::from("Transfer the ownership to change the role of the owner."),
+                    ))),
+                    (Some(id), Some(_)) => {
+                        diesel::update(recipes_users::table.find((recipe_id, id)))
+                            .set(recipes_users::role.eq(role.as_str()))
+                            .execute(c)?;
+                        Ok(Ok(CollaboratorDTO { username, role }))
+                    }
+                    _ => Ok::<_, diesel::result::Error>(Err(RecipeResponse::NotFound(
+                        String::from("The collaborator was not found."),
+                    ))),
+                }
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collaborator in the database.",
+        )),
+    }
+}
+
+/// Remove collaborator
+///
+/// Take a collaborator off a recipe. The owner can remove anyone else,
+/// editors and viewers can remove themselves.
+#[utoipa::path(
+    delete,
+    path = "/recipes/{recipe_id}/collaborators/{username}",
+    tag = "recipes",
+    responses(
+        (status = 204, description = "Collaborator removed succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is neither the owner nor the collaborator"),
+        (status = 404, description = "Recipe or collaborator was not found"),
+        (status = 422, description = "The owner cannot be removed"),
+        (status = 500, description = "Error removing the collaborator"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("username" = String, description = "Username of the collaborator", example = "jane"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/recipes/<recipe_id>/collaborators/<username>")]
+pub async fn remove_collaborator(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    username: String,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<CollaboratorDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit the collaborators of a recipe.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let own_role = match recipe_role(c, recipe_id, user_id)? {
+                    Some(role) => role,
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "The recipe was not found.",
+                        ))))
+                    }
+                };
+                let target = match user_by_name(c, &username)? {
+                    Some(id) => recipe_role(c, recipe_id, id)?.map(|role| (id, role)),
+                    None => None,
+                };
+                match target {
+                    None => Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The collaborator was not found.",
+                    )))),
+                    Some((_, RecipeRole::Owner)) => Ok(Err(RecipeResponse::BadRequest(
+                        String::from("The owner cannot be removed, transfer the ownership first."),
+                    ))),
+                    Some((id, _)) if id == user_id || own_role == RecipeRole::Owner => {
+                        diesel::delete(recipes_users::table.find((recipe_id, id))).execute(c)?;
+                        Ok(Ok(()))
+                    }
+                    Some(_) => Ok::<_, diesel::result::Error>(Err(RecipeResponse::Forbidden(
+                        String::from("Only the owner of the recipe can remove collaborators."),
+                    ))),
+                }
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(_)) => Ok(Status::NoContent),
+        Ok(Err(response)) => Err(response),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot remove the collaborator from the database.",
+        ))),
+    }
+}
+
+/// Transfer ownership
+///
+/// Make another user the owner of a recipe. The current owner stays on as an editor.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/transfer",
+    tag = "recipes",
+    request_body = OwnershipTransferDTO,
+    responses(
+        (status = 200, description = "Ownership transferred succesfully", body = Vec<CollaboratorDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
+        (status = 404, description = "Recipe or user was not found"),
+        (status = 422, description = "The user is already the owner"),
+        (status = 500, description = "Error saving the owner"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/transfer", data = "<transfer>")]
+pub async fn transfer_ownership(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    transfer: Json<OwnershipTransferDTO>,
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<Vec<CollaboratorDTO>> {
+    let owner_id = match owned.and_then(|o| o.owner_only("transfer")) {
+        Ok(o) => o.user_id,
+        Err(err) => return RecipeResponse::from(err),
+    };
+
+    match transfer.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let username = transfer.into_inner().username;
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let user_id = match user_by_name(c, &username)? {
+                    Some(id) => id,
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "The user was not found.",
+                        ))))
+                    }
+                };
+                if user_id == owner_id {
+                    return Ok(Err(RecipeResponse::BadRequest(String::from(
+                        "You already own the recipe.",
+                    ))));
+                }
+                // one owner per recipe, so step down first
+                diesel::update(recipes_users::table.find((recipe_id, owner_id)))
+                    .set(recipes_users::role.eq(RecipeRole::Editor.as_str()))
+                    .execute(c)?;
+                diesel::insert_into(recipes_users::table)
+                    .values((
+                        recipes_users::recipe_id.eq(recipe_id),
+                        recipes_users::user_id.eq(user_id),
+                        recipes_users::role.eq(RecipeRole::Owner.as_str()),
+                    ))
+                    .on_conflict((recipes_users::recipe_id, recipes_users::user_id))
+                    .do_update()
+                    .set(recipes_users::role.eq(RecipeRole::Owner.as_str()))
+                    .execute(c)?;
+                Ok::<_, diesel::result::Error>(Ok(recipe_collaborators(c, recipe_id)?))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the owner in the database.",
+        )),
+    }
+}
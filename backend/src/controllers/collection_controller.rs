@@ -0,0 +1,624 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{readable_recipe, visible_recipe_ids};
+
+/// Name of the collection that toggled bookmarks go to
+const DEFAULT_COLLECTION: &str = "Bookmarks";
+
+/// Collection of the user, `None` if it does not exist or belongs to someone else
+pub fn user_collection(
+    c: &mut PgConnection,
+    collection_id: i32,
+    user_id: i32,
+) -> QueryResult<Option<Collection>> {
+    collections::table
+        .find(collection_id)
+        .filter(collections::user_id.eq(user_id))
+        .first::<Collection>(c)
+        .optional()
+}
+
+/// Default collection of the user, created on first use
+pub fn default_collection(c: &mut PgConnection, user_id: i32) -> QueryResult<Collection> {
+    let found = collections::table
+        .filter(collections::user_id.eq(user_id))
+        .filter(collections::is_default.eq(true))
+        .first::<Collection>(c)
+        .optional()?;
+    match found {
+        Some(collection) => Ok(collection),
+        None => diesel::insert_into(collections::table)
+            .values((
+                collections::user_id.eq(user_id),
+                collections::name.eq(DEFAULT_COLLECTION),
+                collections::is_default.eq(true),
+            ))
+            .on_conflict((collections::user_id, collections::name))
+            .do_update()
+            .set(collections::is_default.eq(true))
+            .get_result::<Collection>(c),
+    }
+}
+
+/// Put a recipe at the end of a collection and bookmark it
+pub fn add_to_collection(
+    c: &mut PgConnection,
+    collection: &Collection,
+    recipe_id: i32,
+) -> QueryResult<()> {
+    let last = collection_recipes::table
+        .filter(collection_recipes::collection_id.eq(collection.id))
+        .select(diesel::dsl::max(collection_recipes::position))
+        .first::<Option<i32>>(c)?;
+    diesel::insert_into(collection_recipes::table)
+        .values((
+            collection_recipes::collection_id.eq(collection.id),
+            collection_recipes::recipe_id.eq(recipe_id),
+            collection_recipes::position.eq(last.unwrap_or(0) + 1),
+        ))
+        .on_conflict_do_nothing()
+        .execute(c)?;
+    diesel::insert_into(bookmarks::table)
+        .values((
+            bookmarks::recipe_id.eq(recipe_id),
+            bookmarks::user_id.eq(collection.user_id),
+        ))
+        .on_conflict_do_nothing()
+        .execute(c)?;
+    Ok(())
+}
+
+/// Remove the bookmarks of recipes that are no longer in any collection of the user
+pub fn remove_orphan_bookmarks(
+    c: &mut PgConnection,
+    user_id: i32,
+    recipe_ids: &[i32],
+) -> QueryResult<usize> {
+    let collected = collection_recipes::table
+        .inner_join(collections::table)
+        .filter(collections::user_id.eq(user_id))
+        .select(collection_recipes::recipe_id);
+    diesel::delete(
+        bookmarks::table
+            .filter(bookmarks::user_id.eq(user_id))
+            .filter(bookmarks::recipe_id.eq_any(recipe_ids))
+            .filter(diesel::dsl::not(bookmarks::recipe_id.eq_any(collected))),
+    )
+    .execute(c)
+}
+
+/// Collections with the number of their recipes the user can still see
+fn load_collections(
+    c: &mut PgConnection,
+    user_id: i32,
+    collection_id: Option<i32>,
+) -> QueryResult<Vec<CollectionDTO>> {
+    let mut query = collections::table
+        .filter(collections::user_id.eq(user_id))
+        .into_boxed();
+    if let Some(id) = collection_id {
+        query = query.filter(collections::id.eq(id));
+    }
+    let list = query
+        .order((collections::is_default.desc(), collections::name.asc()))
+        .load::<Collection>(c)?;
+
+    let counts = collection_recipes::table
+        .filter(collection_recipes::collection_id.eq_any(list.iter().map(|l| l.id)))
+        .filter(collection_recipes::recipe_id.eq_any(visible_recipe_ids(Some(user_id))))
+        .group_by(collection_recipes::collection_id)
+        .select((collection_recipes::collection_id, diesel::dsl::count_star()))
+        .load::<(i32, i64)>(c)?;
+
+    Ok(list
+        .into_iter()
+        .map(|l| {
+            let count = counts
+                .iter()
+                .find(|(id, _)| *id == l.id)
+                .map_or(0, |(_, count)| *count);
+            CollectionDTO::from((l, count))
+        })
+        .collect())
+}
+
+fn name_taken(
+    c: &mut PgConnection,
+    user_id: i32,
+    name: &str,
+    except: Option<i32>,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        collections::table
+            .filter(collections::user_id.eq(user_id))
+            .filter(collections::name.eq(name))
+            .filter(collections::id.ne(except.unwrap_or(0))),
+    ))
+    .get_result::<bool>(c)
+}
+
+/// Bookmark collections
+///
+/// Get the bookmark collections of the logged in user, the default collection first.
+#[utoipa::path(
+    get,
+    path = "/collections",
+    tag = "collections",
+    responses(
+        (status = 200, description = "Collections found succesfully", body = Vec<CollectionDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error loading collections"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/collections")]
+pub async fn user_collections(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<CollectionDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your collections.",
+            ))
+        }
+    };
+
+    match conn.run(move |c| load_collections(c, user_id, None)).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read collections from the database.",
+        )),
+    }
+}
+
+/// Add collection
+///
+/// Create a named bookmark collection, like "Weeknight" or "Christmas".
+#[utoipa::path(
+    post,
+    path = "/collections",
+    tag = "collections",
+    request_body = CollectionPostDTO,
+    responses(
+        (status = 201, description = "Collection created succesfully", body = CollectionDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 422, description = "Invalid name, or a collection with the name exists"),
+        (status = 500, description = "Error saving the collection"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/collections", data = "<collection>")]
+pub async fn add_collection(
+    conn: LogsDbConn,
+    collection: Json<CollectionPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<CollectionDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            ))
+        }
+    };
+
+    match collection.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let name = String::from(collection.name.trim());
+    if name.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The collection needs a name."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                if name_taken(c, user_id, &name, None)? {
+                    return Ok(None);
+                }
+                let collection = diesel::insert_into(collections::table)
+                    .values((collections::user_id.eq(user_id), collections::name.eq(name)))
+                    .get_result::<Collection>(c)?;
+                Ok::<_, diesel::result::Error>(Some(CollectionDTO::from((collection, 0))))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Created(Json(res)),
+        Ok(None) => RecipeResponse::BadRequest(String::from(
+            "You already have a collection with this name.",
+        )),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collection in the database.",
+        )),
+    }
+}
+
+/// Rename collection
+///
+/// Give one of the logged in user's collections a new name.
+#[utoipa::path(
+    put,
+    path = "/collections/{collection_id}",
+    tag = "collections",
+    request_body = CollectionPutDTO,
+    responses(
+        (status = 200, description = "Collection renamed succesfully", body = CollectionDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Collection was not found"),
+        (status = 422, description = "Invalid name, or a collection with the name exists"),
+        (status = 500, description = "Error saving the collection"),
+    ),
+    params(
+        ("collection_id" = i32, description = "Collection id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/collections/<collection_id>", data = "<collection>")]
+pub async fn rename_collection(
+    conn: LogsDbConn,
+    collection_id: i32,
+    collection: Json<CollectionPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<CollectionDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            ))
+        }
+    };
+
+    match collection.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let name = String::from(collection.name.trim());
+    if name.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The collection needs a name."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                if user_collection(c, collection_id, user_id)?.is_none() {
+                    return Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The collection was not found.",
+                    ))));
+                }
+                if name_taken(c, user_id, &name, Some(collection_id))? {
+                    return Ok(Err(RecipeResponse::BadRequest(String::from(
+                        "You already have a collection with this name.",
+                    ))));
+                }
+                diesel::update(collections::table.find(collection_id))
+                    .set(collections::name.eq(name))
+                    .execute(c)?;
+                let mut list = load_collections(c, user_id, Some(collection_id))?;
+                Ok::<_, diesel::result::Error>(Ok(list.remove(0)))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collection in the database.",
+        )),
+    }
+}
+
+/// Delete collection
+///
+/// Delete one of the logged in user's collections. Recipes that are in no other collection
+/// are no longer bookmarked. The default collection cannot be deleted.
+#[utoipa::path(
+    delete,
+    path = "/collections/{collection_id}",
+    tag = "collections",
+    responses(
+        (status = 204, description = "Collection deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Collection was not found"),
+        (status = 422, description = "The default collection cannot be deleted"),
+        (status = 500, description = "Error deleting the collection"),
+    ),
+    params(
+        ("collection_id" = i32, description = "Collection id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/collections/<collection_id>")]
+pub async fn delete_collection(
+    conn: LogsDbConn,
+    collection_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<CollectionDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                match user_collection(c, collection_id, user_id)? {
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "The collection was not found.",
+                        ))))
+                    }
+                    Some(collection) if collection.is_default => {
+                        return Ok(Err(RecipeResponse::BadRequest(String::from(
+                            "The default collection cannot be deleted.",
+                        ))))
+                    }
+                    Some(_) => (),
+                }
+                let recipe_ids = collection_recipes::table
+                    .filter(collection_recipes::collection_id.eq(collection_id))
+                    .select(collection_recipes::recipe_id)
+                    .load::<i32>(c)?;
+                diesel::delete(collections::table.find(collection_id)).execute(c)?;
+                remove_orphan_bookmarks(c, user_id, &recipe_ids)?;
+                Ok::<_, diesel::result::Error>(Ok(()))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(_)) => Ok(Status::NoContent),
+        Ok(Err(response)) => Err(response),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the collection from the database.",
+        ))),
+    }
+}
+
+/// Add recipe to collection
+///
+/// Put a recipe at the end of one of the logged in user's collections, and bookmark it.
+/// A recipe can be in several collections. Adding it again leaves it where it is.
+#[utoipa::path(
+    put,
+    path = "/collections/{collection_id}/recipes/{recipe_id}",
+    tag = "collections",
+    responses(
+        (status = 200, description = "Recipe added succesfully", body = CollectionDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Collection or recipe was not found"),
+        (status = 500, description = "Error saving the collection"),
+    ),
+    params(
+        ("collection_id" = i32, description = "Collection id"),
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/collections/<collection_id>/recipes/<recipe_id>")]
+pub async fn add_collection_recipe(
+    conn: LogsDbConn,
+    collection_id: i32,
+    recipe_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<CollectionDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            ))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let collection = match user_collection(c, collection_id, user_id)? {
+                    Some(collection) => collection,
+                    None => {
+                        return Ok(Err(RecipeResponse::NotFound(String::from(
+                            "The collection was not found.",
+                        ))))
+                    }
+                };
+                if !readable_recipe(c, recipe_id, Some(user_id))? {
+                    return Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The recipe was not found.",
+                    ))));
+                }
+                add_to_collection(c, &collection, recipe_id)?;
+                let mut list = load_collections(c, user_id, Some(collection_id))?;
+                Ok::<_, diesel::result::Error>(Ok(list.remove(0)))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collection in the database.",
+        )),
+    }
+}
+
+/// Remove recipe from collection
+///
+/// Take a recipe out of one of the logged in user's collections. The recipe stays bookmarked
+/// as long as it is in another collection.
+#[utoipa::path(
+    delete,
+    path = "/collections/{collection_id}/recipes/{recipe_id}",
+    tag = "collections",
+    responses(
+        (status = 204, description = "Recipe removed succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not found in the collection"),
+        (status = 500, description = "Error saving the collection"),
+    ),
+    params(
+        ("collection_id" = i32, description = "Collection id"),
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/collections/<collection_id>/recipes/<recipe_id>")]
+pub async fn remove_collection_recipe(
+    conn: LogsDbConn,
+    collection_id: i32,
+    recipe_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<CollectionDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                if user_collection(c, collection_id, user_id)?.is_none() {
+                    return Ok(0);
+                }
+                let removed =
+                    diesel::delete(collection_recipes::table.find((collection_id, recipe_id)))
+                        .execute(c)?;
+                remove_orphan_bookmarks(c, user_id, &[recipe_id])?;
+                Ok::<_, diesel::result::Error>(removed)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "The recipe was not found in the collection.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collection in the database.",
+        ))),
+    }
+}
+
+/// Reorder collection
+///
+/// Put the recipes of one of the logged in user's collections in a new order.
+/// `recipe_ids` has to list every recipe of the collection the user can see once.
+/// Recipes in the trash or no longer shared with the user move behind them.
+#[utoipa::path(
+    put,
+    path = "/collections/{collection_id}/order",
+    tag = "collections",
+    request_body = CollectionOrderDTO,
+    responses(
+        (status = 204, description = "Collection reordered succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Collection was not found"),
+        (status = 422, description = "The ids are not the recipes of the collection"),
+        (status = 500, description = "Error saving the collection"),
+    ),
+    params(
+        ("collection_id" = i32, description = "Collection id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/collections/<collection_id>/order", data = "<order>")]
+pub async fn reorder_collection(
+    conn: LogsDbConn,
+    collection_id: i32,
+    order: Json<CollectionOrderDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<CollectionDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your collections.",
+            )))
+        }
+    };
+    let order = order.into_inner();
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                if user_collection(c, collection_id, user_id)?.is_none() {
+                    return Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The collection was not found.",
+                    ))));
+                }
+                let visible = collection_recipes::table
+                    .filter(collection_recipes::collection_id.eq(collection_id))
+                    .filter(collection_recipes::recipe_id.eq_any(visible_recipe_ids(Some(user_id))))
+                    .select(collection_recipes::recipe_id)
+                    .load::<i32>(c)?;
+                let current = collection_recipes::table
+                    .filter(collection_recipes::collection_id.eq(collection_id))
+                    .order(collection_recipes::position.asc())
+                    .select(collection_recipes::recipe_id)
+                    .load::<i32>(c)?
+                    .into_iter()
+                    .map(|id| (id, visible.contains(&id)))
+                    .collect::<Vec<_>>();
+                let recipe_ids = match order.arranged(&current) {
+                    Some(ids) => ids,
+                    None => {
+                        return Ok(Err(RecipeResponse::BadRequest(String::from(
+                            "List every recipe of the collection once.",
+                        ))))
+                    }
+                };
+                for (position, recipe_id) in recipe_ids.into_iter().enumerate() {
+                    diesel::update(collection_recipes::table.find((collection_id, recipe_id)))
+                        .set(collection_recipes::position.eq(position as i32 + 1))
+                        .execute(c)?;
+                }
+                Ok::<_, diesel::result::Error>(Ok(()))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok(_)) => Ok(Status::NoContent),
+        Ok(Err(response)) => Err(response),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot save the collection in the database.",
+        ))),
+    }
+}
@@ -0,0 +1,363 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{pagination, readable_recipe, recipe_role};
+
+fn comment(c: &mut PgConnection, comment_id: i32) -> QueryResult<CommentDTO> {
+    comments::table
+        .find(comment_id)
+        .inner_join(users::table)
+        .first::<(Comment, User)>(c)
+        .map(CommentDTO::from)
+}
+
+/// Comment of a recipe that can be read, with whether the user owns that recipe
+fn find_comment(
+    c: &mut PgConnection,
+    comment_id: i32,
+    user_id: i32,
+) -> QueryResult<Option<(Comment, bool)>> {
+    let found = comments::table
+        .find(comment_id)
+        .first::<Comment>(c)
+        .optional()?;
+    let found = match found {
+        Some(f) if readable_recipe(c, f.recipe_id, Some(user_id))? => f,
+        _ => return Ok(None),
+    };
+    let owner = recipe_role(c, found.recipe_id, user_id)? == Some(RecipeRole::Owner);
+    Ok(Some((found, owner)))
+}
+
+/// Recipe comments
+///
+/// Get the comments of a recipe, newest first, each with its replies oldest first.
+/// Pagination counts top-level comments only.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/comments?{page}&{per_page}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Comments found succesfully", body = PaginatedResult<CommentDTO>),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Error loading comments"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/comments?<page>&<per_page>", rank = 2)]
+pub async fn recipe_comments(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<CommentDTO>> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+
+            let threads = comments::table
+                .filter(comments::recipe_id.eq(recipe_id))
+                .filter(comments::parent_id.is_null());
+            let total: i64 = threads.count().get_result(c)?;
+            let (current_page, per_page, offset) = pagination(page, per_page, total);
+            let mut records = threads
+                .inner_join(users::table)
+                .order((comments::created_at.desc(), comments::id.desc()))
+                .offset(offset)
+                .limit(per_page)
+                .load::<(Comment, User)>(c)?
+                .into_iter()
+                .map(CommentDTO::from)
+                .collect::<Vec<CommentDTO>>();
+
+            let thread_ids = records.iter().map(|r| r.id).collect::<Vec<i32>>();
+            let replies = comments::table
+                .inner_join(users::table)
+                .filter(comments::parent_id.eq_any(thread_ids))
+                .order((comments::created_at.asc(), comments::id.asc()))
+                .load::<(Comment, User)>(c)?;
+            for reply in replies {
+                if let Some(thread) = records.iter_mut().find(|r| Some(r.id) == reply.0.parent_id) {
+                    thread.replies.push(CommentDTO::from(reply));
+                }
+            }
+
+            Ok::<_, diesel::result::Error>(Some(PaginatedResult {
+                records,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor: None,
+                prev_cursor: None,
+            }))
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read comments from the database.",
+        )),
+    }
+}
+
+/// Add comment
+///
+/// Comment on a recipe, or reply to one of its comments with `parent_id`.
+/// Replies are only one level deep, a reply cannot be replied to.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/comments",
+    tag = "recipes",
+    request_body = CommentPostDTO,
+    responses(
+        (status = 201, description = "Comment added succesfully", body = CommentDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 422, description = "Invalid comment, or parent is not a top-level comment of the recipe"),
+        (status = 500, description = "Error saving the comment"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/comments", data = "<new_comment>")]
+pub async fn add_comment(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    new_comment: Json<CommentPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<CommentDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => return RecipeResponse::Unauthorized(String::from("Please log in to comment.")),
+    };
+
+    match new_comment.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let new_comment = new_comment.into_inner();
+    let body = String::from(new_comment.body.trim());
+    if body.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The comment is empty."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, Some(user_id))? {
+                return Ok(Err(RecipeResponse::NotFound(String::from(
+                    "The recipe was not found.",
+                ))));
+            }
+            if let Some(parent_id) = new_comment.parent_id {
+                let parent = comments::table
+                    .find(parent_id)
+                    .filter(comments::recipe_id.eq(recipe_id))
+                    .filter(comments::parent_id.is_null())
+                    .select(comments::id)
+                    .first::<i32>(c)
+                    .optional()?;
+                if parent.is_none() {
+                    return Ok(Err(RecipeResponse::BadRequest(String::from(
+                        "Replies must answer a top-level comment of the same recipe.",
+                    ))));
+                }
+            }
+
+            let id = diesel::insert_into(comments::table)
+                .values(CommentInsert {
+                    recipe_id,
+                    user_id,
+                    parent_id: new_comment.parent_id,
+                    body,
+                })
+                .returning(comments::id)
+                .get_result::<i32>(c)?;
+            comment(c, id).map(Ok)
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Created(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the comment in the database.",
+        )),
+    }
+}
+
+/// Edit comment
+///
+/// Change the text of a comment. Only its author can edit it.
+#[utoipa::path(
+    put,
+    path = "/comments/{comment_id}",
+    tag = "recipes",
+    request_body = CommentPutDTO,
+    responses(
+        (status = 200, description = "Comment updated succesfully", body = CommentDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the author of the comment"),
+        (status = 404, description = "Comment was not found"),
+        (status = 422, description = "Invalid comment"),
+        (status = 500, description = "Error saving the comment"),
+    ),
+    params(
+        ("comment_id" = i32, description = "Comment id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/comments/<comment_id>", data = "<edit>")]
+pub async fn update_comment(
+    conn: LogsDbConn,
+    comment_id: i32,
+    edit: Json<CommentPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<CommentDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to edit your comments.",
+            ))
+        }
+    };
+
+    match edit.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let body = String::from(edit.body.trim());
+    if body.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The comment is empty."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            match find_comment(c, comment_id, user_id)? {
+                None => {
+                    return Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The comment was not found.",
+                    ))))
+                }
+                Some((found, _)) if found.user_id != user_id => {
+                    return Ok(Err(RecipeResponse::Forbidden(String::from(
+                        "You can only edit your own comments.",
+                    ))))
+                }
+                Some(_) => (),
+            }
+            diesel::update(comments::table.find(comment_id))
+                .set((
+                    comments::body.eq(body),
+                    comments::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(c)?;
+            comment(c, comment_id).map(Ok)
+        })
+        .await;
+
+    match result {
+        Ok(Ok(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(Err(response)) => response,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the comment in the database.",
+        )),
+    }
+}
+
+/// Delete comment
+///
+/// Remove a comment together with its replies.
+/// Comments can be deleted by their author and by the owners of the recipe.
+#[utoipa::path(
+    delete,
+    path = "/comments/{comment_id}",
+    tag = "recipes",
+    responses(
+        (status = 204, description = "Comment deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is neither the author of the comment nor the owner of the recipe"),
+        (status = 404, description = "Comment was not found"),
+        (status = 500, description = "Error deleting the comment"),
+    ),
+    params(
+        ("comment_id" = i32, description = "Comment id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/comments/<comment_id>")]
+pub async fn delete_comment(
+    conn: LogsDbConn,
+    comment_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<CommentDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to delete comments.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            match find_comment(c, comment_id, user_id)? {
+                None => {
+                    return Ok(Err(RecipeResponse::NotFound(String::from(
+                        "The comment was not found.",
+                    ))))
+                }
+                Some((found, owned)) if found.user_id != user_id && !owned => {
+                    return Ok(Err(RecipeResponse::Forbidden(String::from(
+                        "You can only delete your own comments and comments on your recipes.",
+                    ))))
+                }
+                Some(_) => (),
+            }
+            diesel::delete(comments::table.find(comment_id))
+                .execute(c)
+                .map(Ok)
+        })
+        .await;
+
+    match result {
+        Ok(Ok(_)) => Ok(Status::NoContent),
+        Ok(Err(response)) => Err(response),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the comment from the database.",
+        ))),
+    }
+}
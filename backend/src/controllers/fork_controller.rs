@@ -0,0 +1,259 @@
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use rocket::serde::json::Json;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{
+    get_recipe_elements, listed_recipes, load_recipe_elements, pagination, readable_recipe,
+    record_revision, refresh_nutrition, refresh_search_document, visible_recipes, TransactionError,
+};
+
+/// Copy a recipe with its instructions, ingredients and tags into a new recipe owned by the user
+fn copy_recipe(
+    c: &mut PgConnection,
+    source: Recipe,
+    user_id: i32,
+) -> Result<RecipeResultDTO, TransactionError> {
+    let recipe = diesel::insert_into(recipes::table)
+        .values((
+            recipes::title.eq(&source.title),
+            recipes::servings.eq(source.servings),
+            recipes::servings_unit.eq(&source.servings_unit),
+            recipes::timer.eq(source.timer),
+            recipes::kcal.eq(source.kcal),
+            recipes::carbs.eq(source.carbs),
+            recipes::proteins.eq(source.proteins),
+            recipes::fats.eq(source.fats),
+            recipes::image.eq(&source.image),
+            recipes::forked_from.eq(source.id),
+            recipes::visibility.eq(&source.visibility),
+        ))
+        .get_result::<Recipe>(c)
+        .map_err(|_| TransactionError::from("Cannot insert recipe into the database."))?;
+    let new_id = recipe.id.into_sql::<Integer>();
+
+    diesel::insert_into(recipes_users::table)
+        .values((
+            recipes_users::recipe_id.eq(recipe.id),
+            recipes_users::user_id.eq(user_id),
+            recipes_users::role.eq(RecipeRole::Owner.as_str()),
+        ))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot insert owner into the database."))?;
+
+    diesel::insert_into(instructions::table)
+        .values(
+            instructions::table
+                .filter(instructions::recipe_id.eq(source.id))
+                .select((
+                    new_id,
+                    instructions::instruction,
+                    instructions::display_order,
+                )),
+        )
+        .into_columns((
+            instructions::recipe_id,
+            instructions::instruction,
+            instructions::display_order,
+        ))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot insert instructions into the database."))?;
+
+    diesel::insert_into(recipe_ingredients::table)
+        .values(
+            recipe_ingredients::table
+                .filter(recipe_ingredients::recipe_id.eq(source.id))
+                .order(recipe_ingredients::id.asc())
+                .select((
+                    new_id,
+                    recipe_ingredients::ingredient_id,
+                    recipe_ingredients::amount,
+                    recipe_ingredients::unit,
+                )),
+        )
+        .into_columns((
+            recipe_ingredients::recipe_id,
+            recipe_ingredients::ingredient_id,
+            recipe_ingredients::amount,
+            recipe_ingredients::unit,
+        ))
+        .execute(c)
+        .map_err(|_| {
+            TransactionError::from("Cannot insert recipe ingredients into the database.")
+        })?;
+
+    diesel::insert_into(recipes_tags::table)
+        .values(
+            recipes_tags::table
+                .filter(recipes_tags::recipe_id.eq(source.id))
+                .select((new_id, recipes_tags::tag_id)),
+        )
+        .into_columns((recipes_tags::recipe_id, recipes_tags::tag_id))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot insert recipe tags into the database."))?;
+
+    refresh_search_document(c, recipe.id)
+        .map_err(|_| TransactionError::from("Cannot update the search index."))?;
+    refresh_nutrition(c, &[recipe.id])
+        .map_err(|_| TransactionError::from("Cannot update the recipe nutrition."))?;
+
+    let recipe = load_recipe_elements(c, vec![recipe], Some(user_id))
+        .map_err(TransactionError)?
+        .pop()
+        .ok_or(TransactionError::from(
+            "Cannot read recipe from the database.",
+        ))?;
+    record_revision(c, &recipe, Some(user_id))
+        .map_err(|_| TransactionError::from("Cannot save the revision."))?;
+
+    Ok(recipe)
+}
+
+/// Fork recipe
+///
+/// Copy a recipe with its instructions, ingredients and tags into a new recipe owned by the
+/// logged in user, to change it without touching the original. The copy links back to the
+/// original in `forked_from`.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/fork",
+    tag = "recipes",
+    responses(
+        (status = 201, description = "Recipe forked succesfully", body = RecipeResultDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/fork")]
+pub async fn fork_recipe(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<RecipeResultDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from("Please log in to fork recipes."))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                let source = recipes::table
+                    .find(recipe_id)
+                    .filter(recipes::deleted_at.is_null())
+                    .filter(visible_recipes(Some(user_id)))
+                    .first::<Recipe>(c)
+                    .optional()
+                    .map_err(|_| TransactionError::from("Cannot read recipe from the database."))?;
+                match source {
+                    Some(s) => copy_recipe(c, s, user_id).map(Some),
+                    None => Ok(None),
+                }
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Some(recipe)) => RecipeResponse::Created(Json(recipe)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(err) => {
+            RecipeResponse::InternalServerError(format!("{} The recipe was not forked.", err))
+        }
+    }
+}
+
+/// Recipe forks
+///
+/// Get the recipes that were forked from a recipe, newest first.
+/// Unlisted and private forks only show up for their owners.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/forks?{page}&{per_page}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Forks found succesfully", body = PaginatedResult<RecipeResultDTO>),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Error loading recipes"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/forks?<page>&<per_page>", rank = 2)]
+pub async fn recipe_forks(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<RecipeResultDTO>> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+
+            let forks = || {
+                recipes::table
+                    .filter(recipes::forked_from.eq(recipe_id))
+                    .filter(recipes::deleted_at.is_null())
+                    .filter(listed_recipes(user_id))
+            };
+            let total: i64 = forks().count().get_result(c)?;
+            let (current_page, per_page, offset) = pagination(page, per_page, total);
+            let records = forks()
+                .order((recipes::created_at.desc(), recipes::id.desc()))
+                .offset(offset)
+                .limit(per_page)
+                .load::<Recipe>(c)?;
+            Ok::<_, diesel::result::Error>(Some((records, total, current_page, per_page)))
+        })
+        .await;
+
+    let (recipes_list, total, current_page, per_page) = match result {
+        Ok(Some(res)) => res,
+        Ok(None) => return RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read recipes from the database.",
+            ))
+        }
+    };
+
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => RecipeResponse::Ok(Json(PaginatedResult {
+            records: res,
+            total: Some(total),
+            current_page: Some(current_page),
+            per_page,
+            next_cursor: None,
+            prev_cursor: None,
+        })),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read recipes from the database.",
+        )),
+    }
+}
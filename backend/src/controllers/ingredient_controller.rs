@@ -0,0 +1,261 @@
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use validator::Validate;
+
+use crate::ingredient_parser::parse_ingredient_lines;
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{
+    catalogue_entry, ingredient_name_taken, merge_ingredients, search_ingredients, TransactionError,
+};
+
+/// Ingredient catalogue
+///
+/// Autocomplete ingredient names. Names and synonyms starting with `q` come first, then
+/// names containing it, then similar spellings; ties go to the ingredient used in most recipes.
+/// Plural and singular forms match each other. Without `q` the most used ingredients are listed.
+#[utoipa::path(
+    get,
+    path = "/ingredients",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Ingredients found succesfully", body = Vec<IngredientCatalogDTO>),
+        (status = 500, description = "Error loading ingredients"),
+    ),
+    params(
+        ("q" = Option<String>, Query, description = "Typed name, matched against names and synonyms"),
+        ("limit" = Option<i64>, Query, description = "Number of suggestions, 1 to 50, defaults to 10"),
+    ),
+)]
+#[get("/ingredients?<q>&<limit>")]
+pub async fn ingredient_list(
+    conn: LogsDbConn,
+    q: Option<String>,
+    limit: Option<i64>,
+) -> RecipeResponse<Vec<IngredientCatalogDTO>> {
+    let q = q.unwrap_or_default();
+    let limit = limit.unwrap_or(10).clamp(1, 50);
+
+    match conn.run(move |c| search_ingredients(c, &q, limit)).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read ingredients from the database.",
+        )),
+    }
+}
+
+/// Merge ingredients
+///
+/// Merge duplicate ingredients into the one of the path. Recipes using a duplicate
+/// use the kept ingredient from then on, the duplicate names become its synonyms.
+/// Admins only.
+#[utoipa::path(
+    post,
+    path = "/ingredients/{ingredient_id}/merge",
+    request_body = IngredientMergeDTO,
+    tag = "admin",
+    responses(
+        (status = 200, description = "Ingredients merged succesfully", body = IngredientCatalogDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "Ingredient was not found"),
+        (status = 422, description = "Validation error"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    params(
+        ("ingredient_id" = i32, description = "Ingredient to keep"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/ingredients/<ingredient_id>/merge", data = "<merge>")]
+pub async fn merge_ingredient(
+    conn: LogsDbConn,
+    ingredient_id: i32,
+    merge: Json<IngredientMergeDTO>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<IngredientCatalogDTO> {
+    if let Err(err) = admin {
+        return RecipeResponse::from(err);
+    }
+
+    match merge.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let mut ids = merge.into_inner().ids;
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.contains(&ingredient_id) {
+        return RecipeResponse::BadRequest(String::from(
+            "An ingredient cannot be merged into itself.",
+        ));
+    }
+
+    let merge_ids = ids.clone();
+    let found = match conn
+        .run(move |c| {
+            ingredients::table
+                .filter(
+                    ingredients::id
+                        .eq(ingredient_id)
+                        .or(ingredients::id.eq_any(merge_ids)),
+                )
+                .load::<Ingredient>(c)
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read ingredients from the database.",
+            ))
+        }
+    };
+    if let Some(missing) = std::iter::once(&ingredient_id)
+        .chain(&ids)
+        .find(|id| !found.iter().any(|i| i.id == **id))
+    {
+        return RecipeResponse::NotFound(format!("Ingredient {} was not found.", missing));
+    }
+    let merged = found
+        .into_iter()
+        .filter(|i| i.id != ingredient_id)
+        .collect::<Vec<Ingredient>>();
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                merge_ingredients(c, ingredient_id, &merged)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(err) => {
+            RecipeResponse::InternalServerError(format!("{} The ingredients were not merged.", err))
+        }
+    }
+}
+
+/// Add synonym
+///
+/// Add another name for an ingredient, e.g. "scallion" for "green onion".
+/// Recipes using the synonym are stored with the ingredient. Admins only.
+#[utoipa::path(
+    post,
+    path = "/ingredients/{ingredient_id}/synonyms",
+    request_body = IngredientSynonymDTO,
+    tag = "admin",
+    responses(
+        (status = 201, description = "Synonym added succesfully", body = IngredientCatalogDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not an admin"),
+        (status = 404, description = "Ingredient was not found"),
+        (status = 422, description = "Validation error or name already taken"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    params(
+        ("ingredient_id" = i32, description = "Ingredient id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/ingredients/<ingredient_id>/synonyms", data = "<synonym>")]
+pub async fn add_ingredient_synonym(
+    conn: LogsDbConn,
+    ingredient_id: i32,
+    synonym: Json<IngredientSynonymDTO>,
+    admin: Result<AdminUser, NetworkResponse>,
+) -> RecipeResponse<IngredientCatalogDTO> {
+    if let Err(err) = admin {
+        return RecipeResponse::from(err);
+    }
+
+    match synonym.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let label = synonym
+        .label
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if label.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The synonym cannot be empty."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, diesel::result::Error, _>(|c| {
+                let exists = ingredients::table
+                    .find(ingredient_id)
+                    .first::<Ingredient>(c)
+                    .optional()?
+                    .is_some();
+                if !exists {
+                    return Ok(RecipeResponse::NotFound(String::from(
+                        "Ingredient was not found.",
+                    )));
+                }
+                if ingredient_name_taken(c, &label)? {
+                    return Ok(RecipeResponse::BadRequest(format!(
+                        "\"{}\" is already in the ingredient catalogue.",
+                        label
+                    )));
+                }
+                diesel::insert_into(ingredient_synonyms::table)
+                    .values((
+                        ingredient_synonyms::ingredient_id.eq(ingredient_id),
+                        ingredient_synonyms::label.eq(&label),
+                    ))
+                    .execute(c)?;
+                Ok(RecipeResponse::Created(Json(catalogue_entry(
+                    c,
+                    ingredient_id,
+                )?)))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => res,
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the synonym in the database.",
+        )),
+    }
+}
+
+/// Parse ingredient lines
+///
+/// Split free-text ingredient lines like "1 1/2 cups all-purpose flour, sifted"
+/// into amount, unit, label and a preparation note. Empty lines are skipped.
+#[utoipa::path(
+    post,
+    path = "/ingredients/parse",
+    request_body = IngredientParseDTO,
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Lines parsed succesfully", body = Vec<ParsedIngredientDTO>),
+        (status = 422, description = "Validation error or a line without ingredient"),
+    ),
+)]
+#[post("/ingredients/parse", data = "<parse>")]
+pub async fn parse_ingredients(
+    parse: Json<IngredientParseDTO>,
+) -> RecipeResponse<Vec<ParsedIngredientDTO>> {
+    match parse.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+
+    match parse_ingredient_lines(&parse.lines) {
+        Ok(parsed) => RecipeResponse::Ok(Json(parsed)),
+        Err(err) => RecipeResponse::BadRequest(err),
+    }
+}
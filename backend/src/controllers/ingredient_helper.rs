@@ -0,0 +1,244 @@
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+use crate::nutrition::merged_nutrient_source;
+use crate::{models::*, schema::*};
+
+use super::{refresh_nutrition, refresh_search_document, TransactionError};
+
+sql_function! {
+    /// Matching key of an ingredient name: lowercase, single spaces, last word in singular.
+    /// Defined by the ingredient catalogue migration, unique over ingredients and synonyms.
+    fn ingredient_key(label: Text) -> Text;
+}
+
+/// Catalogue entry for an ingredient name, matched by key against labels and synonyms
+pub fn find_ingredient(c: &mut PgConnection, name: &str) -> QueryResult<Option<Ingredient>> {
+    let found = ingredients::table
+        .filter(ingredient_key(ingredients::label).eq(ingredient_key(name)))
+        .first::<Ingredient>(c)
+        .optional()?;
+    if found.is_some() {
+        return Ok(found);
+    }
+
+    ingredient_synonyms::table
+        .inner_join(ingredients::table)
+        .filter(ingredient_key(ingredient_synonyms::label).eq(ingredient_key(name)))
+        .select((ingredients::id, ingredients::label))
+        .first::<Ingredient>(c)
+        .optional()
+}
+
+/// Catalogue entry for an ingredient name, names that are not in the catalogue yet are added
+pub fn resolve_ingredient(c: &mut PgConnection, name: &str) -> QueryResult<Ingredient> {
+    let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if let Some(ingredient) = find_ingredient(c, &name)? {
+        return Ok(ingredient);
+    }
+
+    diesel::insert_into(ingredients::table)
+        .values(IngredientInsert { label: name })
+        .get_result::<Ingredient>(c)
+}
+
+/// Catalogue entries for a list of ingredient names, in the same order
+pub fn resolve_ingredients(
+    c: &mut PgConnection,
+    names: &[String],
+) -> Result<Vec<Ingredient>, TransactionError> {
+    names
+        .iter()
+        .map(|name| {
+            resolve_ingredient(c, name)
+                .map_err(|_| TransactionError::from("Cannot save ingredients in the database."))
+        })
+        .collect()
+}
+
+/// Autocomplete over the catalogue. Names or synonyms starting with `q` come first,
+/// then those containing it, then similar spellings. Ties go to the most used ingredient.
+/// An empty `q` lists the most used ingredients.
+pub fn search_ingredients(
+    c: &mut PgConnection,
+    q: &str,
+    limit: i64,
+) -> QueryResult<Vec<IngredientCatalogDTO>> {
+    let q = q.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let pattern = q
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    diesel::sql_query(
+        "SELECT i.id, i.label, syn.labels AS synonyms, COALESCE(u.count, 0) AS usage_count
+        FROM ingredients i
+        CROSS JOIN LATERAL (
+            SELECT
+                COALESCE(array_agg(s.label ORDER BY s.label), '{}') AS labels,
+                bool_or(ingredient_key(s.label) LIKE ingredient_key($2) || '%') AS prefix,
+                bool_or(s.label ILIKE '%' || $2 || '%') AS contains,
+                max(word_similarity($1, s.label)) AS similarity
+            FROM ingredient_synonyms s
+            WHERE s.ingredient_id = i.id
+        ) syn
+        LEFT JOIN (
+            SELECT ri.ingredient_id, COUNT(DISTINCT ri.recipe_id) AS count
+            FROM recipe_ingredients ri
+            JOIN recipes r ON r.id = ri.recipe_id AND r.deleted_at IS NULL
+            GROUP BY ri.ingredient_id
+        ) u ON u.ingredient_id = i.id
+        CROSS JOIN LATERAL (
+            SELECT CASE
+                WHEN $1 = '' THEN 0
+                WHEN ingredient_key(i.label) LIKE ingredient_key($2) || '%' OR syn.prefix THEN 0
+                WHEN i.label ILIKE '%' || $2 || '%' OR syn.contains THEN 1
+                WHEN GREATEST(word_similarity($1, i.label), syn.similarity) >= 0.5 THEN 2
+            END AS rank
+        ) m
+        WHERE m.rank IS NOT NULL
+        ORDER BY m.rank, usage_count DESC, i.label
+        LIMIT $3",
+    )
+    .bind::<Text, _>(q)
+    .bind::<Text, _>(pattern)
+    .bind::<diesel::sql_types::BigInt, _>(limit)
+    .load::<IngredientCatalogDTO>(c)
+}
+
+/// A single catalogue entry with its synonyms and usage count
+pub fn catalogue_entry(
+    c: &mut PgConnection,
+    ingredient_id: i32,
+) -> QueryResult<IngredientCatalogDTO> {
+    let ingredient = ingredients::table
+        .find(ingredient_id)
+        .first::<Ingredient>(c)?;
+    let synonyms = ingredient_synonyms::table
+        .filter(ingredient_synonyms::ingredient_id.eq(ingredient_id))
+        .select(ingredient_synonyms::label)
+        .order(ingredient_synonyms::label.asc())
+        .load::<String>(c)?;
+    let usage_count = recipe_ingredients::table
+        .inner_join(recipes::table)
+        .filter(recipe_ingredients::ingredient_id.eq(ingredient_id))
+        .filter(recipes::deleted_at.is_null())
+        .select(diesel::dsl::count_distinct(recipe_ingredients::recipe_id))
+        .get_result::<i64>(c)?;
+
+    Ok(IngredientCatalogDTO {
+        id: ingredient.id,
+        label: ingredient.label,
+        synonyms,
+        usage_count,
+    })
+}
+
+/// Whether a name is taken in the catalogue, as an ingredient or as a synonym
+pub fn ingredient_name_taken(c: &mut PgConnection, name: &str) -> QueryResult<bool> {
+    let ingredient = diesel::select(diesel::dsl::exists(
+        ingredients::table.filter(ingredient_key(ingredients::label).eq(ingredient_key(name))),
+    ))
+    .get_result::<bool>(c)?;
+    let synonym = diesel::select(diesel::dsl::exists(
+        ingredient_synonyms::table
+            .filter(ingredient_key(ingredient_synonyms::label).eq(ingredient_key(name))),
+    ))
+    .get_result::<bool>(c)?;
+    Ok(ingredient || synonym)
+}
+
+/// Merge duplicates into one ingredient: recipe lines, shopping list and pantry items and
+/// synonyms move over, the merged names become synonyms and the duplicates are deleted.
+/// Nutrient data of a duplicate moves over too when the ingredient has none.
+pub fn merge_ingredients(
+    c: &mut PgConnection,
+    ingredient_id: i32,
+    merged: &[Ingredient],
+) -> Result<IngredientCatalogDTO, TransactionError> {
+    let merged_ids = merged.iter().map(|i| i.id).collect::<Vec<i32>>();
+
+    let affected_recipes = recipe_ingredients::table
+        .filter(recipe_ingredients::ingredient_id.eq_any(&merged_ids))
+        .select(recipe_ingredients::recipe_id)
+        .distinct()
+        .load::<i32>(c)
+        .map_err(|_| TransactionError::from("Cannot read recipe ingredients from the database."))?;
+
+    diesel::update(recipe_ingredients::table)
+        .filter(recipe_ingredients::ingredient_id.eq_any(&merged_ids))
+        .set(recipe_ingredients::ingredient_id.eq(ingredient_id))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot update recipe ingredients in the database."))?;
+
+    diesel::update(shopping_list_items::table)
+        .filter(shopping_list_items::ingredient_id.eq_any(&merged_ids))
+        .set(shopping_list_items::ingredient_id.eq(ingredient_id))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot update shopping lists in the database."))?;
+
+    diesel::update(pantry_items::table)
+        .filter(pantry_items::ingredient_id.eq_any(&merged_ids))
+        .set(pantry_items::ingredient_id.eq(ingredient_id))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot update pantries in the database."))?;
+
+    diesel::update(ingredient_synonyms::table)
+        .filter(ingredient_synonyms::ingredient_id.eq_any(&merged_ids))
+        .set(ingredient_synonyms::ingredient_id.eq(ingredient_id))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot move synonyms in the database."))?;
+
+    let with_nutrients = ingredient_nutrients::table
+        .filter(
+            ingredient_nutrients::ingredient_id
+                .eq_any(&merged_ids)
+                .or(ingredient_nutrients::ingredient_id.eq(ingredient_id)),
+        )
+        .select(ingredient_nutrients::ingredient_id)
+        .load::<i32>(c)
+        .map_err(|_| TransactionError::from("Cannot read nutrients from the database."))?;
+    if let Some(source) = merged_nutrient_source(ingredient_id, &merged_ids, &with_nutrients) {
+        diesel::update(ingredient_nutrients::table.find(source))
+            .set(ingredient_nutrients::ingredient_id.eq(ingredient_id))
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot move nutrients in the database."))?;
+    }
+
+    diesel::delete(ingredients::table)
+        .filter(ingredients::id.eq_any(&merged_ids))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot delete ingredients from the database."))?;
+
+    diesel::insert_into(ingredient_synonyms::table)
+        .values(
+            merged
+                .iter()
+                .map(|i| {
+                    (
+                        ingredient_synonyms::ingredient_id.eq(ingredient_id),
+                        ingredient_synonyms::label.eq(i.label.clone()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot insert synonyms into the database."))?;
+
+    for recipe_id in &affected_recipes {
+        refresh_search_document(c, *recipe_id)
+            .map_err(|_| TransactionError::from("Cannot update the search index."))?;
+    }
+    // recipes of the ingredient itself change too when it got nutrient data
+    let nutrition_recipes = recipe_ingredients::table
+        .filter(recipe_ingredients::ingredient_id.eq(ingredient_id))
+        .select(recipe_ingredients::recipe_id)
+        .distinct()
+        .load::<i32>(c)
+        .map_err(|_| TransactionError::from("Cannot read recipe ingredients from the database."))?;
+    refresh_nutrition(c, &nutrition_recipes)
+        .map_err(|_| TransactionError::from("Cannot update the recipe nutrition."))?;
+
+    catalogue_entry(c, ingredient_id)
+        .map_err(|_| TransactionError::from("Cannot read the ingredient from the database."))
+}
@@ -0,0 +1,694 @@
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use rand::distributions::{Alphanumeric, DistString};
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use std::collections::HashMap;
+use validator::Validate;
+
+use crate::ical::{calendar, CalendarEvent};
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::get_recipe_elements;
+use super::visible_recipes;
+
+/// Longest range that can be listed or exported at once
+const MAX_RANGE_DAYS: i64 = 366;
+
+/// Date range of a query, `from` and `to` are inclusive dates like 2023-07-26.
+/// Missing ends default to `default`.
+fn date_range(
+    from: Option<String>,
+    to: Option<String>,
+    default: (NaiveDate, NaiveDate),
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |value: Option<String>, name: &str, fallback: NaiveDate| match value {
+        Some(v) => NaiveDate::parse_from_str(&v, "%Y-%m-%d")
+            .map_err(|_| format!("{} must be a date like 2023-07-26.", name)),
+        None => Ok(fallback),
+    };
+    let from = parse(from, "from", default.0)?;
+    let to = parse(to, "to", default.1)?;
+
+    if to < from {
+        return Err(String::from("to must not be before from."));
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(format!(
+            "The range can be at most {} days long.",
+            MAX_RANGE_DAYS
+        ));
+    }
+    Ok((from, to))
+}
+
+/// Monday to Sunday of the current week
+fn this_week() -> (NaiveDate, NaiveDate) {
+    let today = Utc::now().date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    (monday, monday + Duration::days(6))
+}
+
+/// Entries with their recipes, scaled to the planned servings, ordered by date and meal.
+/// Entries of recipes in the trash are left out.
+async fn entry_details(
+    conn: &LogsDbConn,
+    user_id: i32,
+    entries: Vec<MealPlanEntry>,
+) -> Result<Vec<MealPlanEntryDTO>, String> {
+    let ids = entries.iter().map(|e| e.recipe_id).collect::<Vec<i32>>();
+    let recipes_list = conn
+        .run(move |c| {
+            recipes::table
+                .filter(recipes::id.eq_any(ids))
+                .filter(recipes::deleted_at.is_null())
+                .load::<Recipe>(c)
+        })
+        .await
+        .map_err(|_| String::from("Cannot read recipes from the database."))?;
+    let recipes_found = get_recipe_elements(recipes_list, conn, Some(user_id))
+        .await?
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect::<HashMap<i32, RecipeResultDTO>>();
+
+    let mut result = entries
+        .into_iter()
+        .filter_map(|e| {
+            let recipe = recipes_found.get(&e.recipe_id)?.clone();
+            let factor = e.servings as f32 / recipe.servings as f32;
+            Some(MealPlanEntryDTO {
+                id: e.id,
+                date: e.date,
+                slot: MealSlot::parse(&e.slot)?,
+                servings: e.servings,
+                recipe: recipe.scaled(factor, e.servings),
+            })
+        })
+        .collect::<Vec<MealPlanEntryDTO>>();
+    result.sort_by_key(|e| (e.date, e.slot.time(), e.id));
+    Ok(result)
+}
+
+async fn load_range(
+    conn: &LogsDbConn,
+    user_id: i32,
+    (from, to): (NaiveDate, NaiveDate),
+) -> Result<Vec<MealPlanEntryDTO>, String> {
+    let entries = conn
+        .run(move |c| {
+            meal_plan_entries::table
+                .filter(meal_plan_entries::user_id.eq(user_id))
+                .filter(meal_plan_entries::date.between(from, to))
+                .load::<MealPlanEntry>(c)
+        })
+        .await
+        .map_err(|_| String::from("Cannot read the meal plan from the database."))?;
+    entry_details(conn, user_id, entries).await
+}
+
+async fn load_entry(
+    conn: &LogsDbConn,
+    user_id: i32,
+    entry_id: i32,
+) -> Result<Option<MealPlanEntryDTO>, String> {
+    let entry = conn
+        .run(move |c| {
+            meal_plan_entries::table
+                .find(entry_id)
+                .filter(meal_plan_entries::user_id.eq(user_id))
+                .first::<MealPlanEntry>(c)
+                .optional()
+        })
+        .await
+        .map_err(|_| String::from("Cannot read the meal plan from the database."))?;
+    match entry {
+        Some(e) => Ok(entry_details(conn, user_id, vec![e]).await?.pop()),
+        None => Ok(None),
+    }
+}
+
+/// Servings of a recipe that can be planned, `None` when it does not exist, is in the trash
+/// or is not visible to the user
+async fn plannable_recipe(
+    conn: &LogsDbConn,
+    user_id: i32,
+    recipe_id: i32,
+) -> Result<Option<i16>, String> {
+    conn.run(move |c| {
+        recipes::table
+            .find(recipe_id)
+            .filter(recipes::deleted_at.is_null())
+            .filter(visible_recipes(Some(user_id)))
+            .select(recipes::servings)
+            .first::<i16>(c)
+            .optional()
+    })
+    .await
+    .map_err(|_| String::from("Cannot read recipes from the database."))
+}
+
+/// Meal plan
+///
+/// Get the meal plan of the logged in user from `from` to `to`, both included,
+/// defaults to the current week. Each recipe is scaled to the planned servings.
+#[utoipa::path(
+    get,
+    path = "/mealplan?{from}&{to}",
+    tag = "meal plan",
+    responses(
+        (status = 200, description = "Meal plan found succesfully", body = Vec<MealPlanEntryDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 422, description = "Invalid date range"),
+        (status = 500, description = "Error loading the meal plan"),
+    ),
+    params(
+        ("from" = Option<String>, Query, description = "First day, e.g. 2023-07-24. Defaults to Monday of this week"),
+        ("to" = Option<String>, Query, description = "Last day, at most a year after from. Defaults to Sunday of this week"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/mealplan?<from>&<to>")]
+pub async fn meal_plan(
+    conn: LogsDbConn,
+    from: Option<String>,
+    to: Option<String>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<MealPlanEntryDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your meal plan.",
+            ))
+        }
+    };
+    let range = match date_range(from, to, this_week()) {
+        Ok(r) => r,
+        Err(err) => return RecipeResponse::BadRequest(err),
+    };
+
+    match load_range(&conn, user_id, range).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(err) => RecipeResponse::InternalServerError(err),
+    }
+}
+
+/// Find meal plan entry
+///
+/// Get a planned meal of the logged in user.
+#[utoipa::path(
+    get,
+    path = "/mealplan/{entry_id}",
+    tag = "meal plan",
+    responses(
+        (status = 200, description = "Meal found succesfully", body = MealPlanEntryDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Meal was not found"),
+        (status = 500, description = "Error loading the meal plan"),
+    ),
+    params(
+        ("entry_id" = i32, description = "Meal plan entry id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/mealplan/<entry_id>")]
+pub async fn single_meal(
+    conn: LogsDbConn,
+    entry_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<MealPlanEntryDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your meal plan.",
+            ))
+        }
+    };
+
+    match load_entry(&conn, user_id, entry_id).await {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The meal was not found.")),
+        Err(err) => RecipeResponse::InternalServerError(err),
+    }
+}
+
+/// Plan a meal
+///
+/// Put a recipe into the meal plan of the logged in user.
+#[utoipa::path(
+    post,
+    path = "/mealplan",
+    tag = "meal plan",
+    request_body = MealPlanPostDTO,
+    responses(
+        (status = 201, description = "Meal planned succesfully", body = MealPlanEntryDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 422, description = "Invalid meal"),
+        (status = 500, description = "Error saving the meal plan"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/mealplan", data = "<meal>")]
+pub async fn plan_meal(
+    conn: LogsDbConn,
+    meal: Json<MealPlanPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<MealPlanEntryDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to plan meals.",
+            ))
+        }
+    };
+
+    match meal.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let meal = meal.into_inner();
+
+    let recipe_servings = match plannable_recipe(&conn, user_id, meal.recipe_id).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(err) => return RecipeResponse::InternalServerError(err),
+    };
+
+    let inserted = conn
+        .run(move |c| {
+            diesel::insert_into(meal_plan_entries::table)
+                .values(MealPlanEntryInsert {
+                    user_id,
+                    recipe_id: meal.recipe_id,
+                    date: meal.date,
+                    slot: String::from(meal.slot.as_str()),
+                    servings: meal.servings.unwrap_or(recipe_servings),
+                })
+                .get_result::<MealPlanEntry>(c)
+        })
+        .await;
+    let entry = match inserted {
+        Ok(e) => e,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot save the meal plan in the database.",
+            ))
+        }
+    };
+
+    match entry_details(&conn, user_id, vec![entry]).await {
+        Ok(mut res) => match res.pop() {
+            Some(e) => RecipeResponse::Created(Json(e)),
+            None => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        },
+        Err(err) => RecipeResponse::InternalServerError(err),
+    }
+}
+
+/// Update meal plan entry
+///
+/// Move a planned meal to another day or meal, change its servings or swap the recipe.
+/// Swapping the recipe without `servings` keeps the planned servings.
+#[utoipa::path(
+    put,
+    path = "/mealplan/{entry_id}",
+    tag = "meal plan",
+    request_body = MealPlanPutDTO,
+    responses(
+        (status = 200, description = "Meal updated succesfully", body = MealPlanEntryDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Meal or recipe was not found"),
+        (status = 422, description = "Invalid meal"),
+        (status = 500, description = "Error saving the meal plan"),
+    ),
+    params(
+        ("entry_id" = i32, description = "Meal plan entry id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/mealplan/<entry_id>", data = "<meal>")]
+pub async fn update_meal(
+    conn: LogsDbConn,
+    entry_id: i32,
+    meal: Json<MealPlanPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<MealPlanEntryDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to plan meals.",
+            ))
+        }
+    };
+
+    match meal.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let meal = meal.into_inner();
+
+    if let Some(recipe_id) = meal.recipe_id {
+        match plannable_recipe(&conn, user_id, recipe_id).await {
+            Ok(Some(_)) => (),
+            Ok(None) => return RecipeResponse::NotFound(String::from("The recipe was not found.")),
+            Err(err) => return RecipeResponse::InternalServerError(err),
+        }
+    }
+
+    let updated = conn
+        .run(move |c| {
+            let current = meal_plan_entries::table
+                .find(entry_id)
+                .filter(meal_plan_entries::user_id.eq(user_id))
+                .first::<MealPlanEntry>(c)
+                .optional()?;
+            let current = match current {
+                Some(e) => e,
+                None => return Ok(None),
+            };
+            diesel::update(meal_plan_entries::table.find(entry_id))
+                .set((
+                    meal_plan_entries::recipe_id.eq(meal.recipe_id.unwrap_or(current.recipe_id)),
+                    meal_plan_entries::date.eq(meal.date.unwrap_or(current.date)),
+                    meal_plan_entries::slot.eq(meal
+                        .slot
+                        .map(|s| String::from(s.as_str()))
+                        .unwrap_or(current.slot)),
+                    meal_plan_entries::servings.eq(meal.servings.unwrap_or(current.servings)),
+                ))
+                .get_result::<MealPlanEntry>(c)
+                .map(Some)
+        })
+        .await;
+    let entry = match updated {
+        Ok(Some(e)) => e,
+        Ok(None) => return RecipeResponse::NotFound(String::from("The meal was not found.")),
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot save the meal plan in the database.",
+            ))
+        }
+    };
+
+    match entry_details(&conn, user_id, vec![entry]).await {
+        Ok(mut res) => match res.pop() {
+            Some(e) => RecipeResponse::Ok(Json(e)),
+            None => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        },
+        Err(err) => RecipeResponse::InternalServerError(err),
+    }
+}
+
+/// Delete meal plan entry
+///
+/// Remove a planned meal from the logged in user's meal plan.
+#[utoipa::path(
+    delete,
+    path = "/mealplan/{entry_id}",
+    tag = "meal plan",
+    responses(
+        (status = 204, description = "Meal deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Meal was not found"),
+        (status = 500, description = "Error deleting the meal"),
+    ),
+    params(
+        ("entry_id" = i32, description = "Meal plan entry id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/mealplan/<entry_id>")]
+pub async fn delete_meal(
+    conn: LogsDbConn,
+    entry_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<MealPlanEntryDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to plan meals.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            diesel::delete(meal_plan_entries::table.find(entry_id))
+                .filter(meal_plan_entries::user_id.eq(user_id))
+                .execute(c)
+        })
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "The meal was not found.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the meal from the database.",
+        ))),
+    }
+}
+
+/// Export meal plan
+///
+/// Get the meal plan as an iCalendar file. Calendar apps can subscribe to it with the
+/// `token` of `POST /mealplan/feed` instead of logging in.
+/// Without `from` and `to` the last four weeks and the next twelve are exported.
+#[utoipa::path(
+    get,
+    path = "/mealplan.ics?{from}&{to}&{token}",
+    tag = "meal plan",
+    responses(
+        (status = 200, description = "Meal plan exported succesfully", body = String, content_type = "text/calendar"),
+        (status = 401, description = "Not logged in and no valid token"),
+        (status = 422, description = "Invalid date range"),
+        (status = 500, description = "Error loading the meal plan"),
+    ),
+    params(
+        ("from" = Option<String>, Query, description = "First day, e.g. 2023-07-24"),
+        ("to" = Option<String>, Query, description = "Last day, at most a year after from"),
+        ("token" = Option<String>, Query, description = "Calendar subscription token"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/mealplan.ics?<from>&<to>&<token>")]
+pub async fn export_meal_plan(
+    conn: LogsDbConn,
+    from: Option<String>,
+    to: Option<String>,
+    token: Option<String>,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<(ContentType, String), RecipeResponse<MealPlanEntryDTO>> {
+    let user_id = match (token, key) {
+        (Some(token), _) => {
+            let found = conn
+                .run(move |c| {
+                    meal_plan_feeds::table
+                        .inner_join(users::table)
+                        .filter(meal_plan_feeds::token.eq(token))
+                        .filter(users::disabled.eq(false))
+                        .select(meal_plan_feeds::user_id)
+                        .first::<i32>(c)
+                        .optional()
+                })
+                .await;
+            match found {
+                Ok(Some(id)) => id,
+                Ok(None) => {
+                    return Err(RecipeResponse::Unauthorized(String::from(
+                        "The calendar token is not valid.",
+                    )))
+                }
+                Err(_) => {
+                    return Err(RecipeResponse::InternalServerError(String::from(
+                        "Cannot read the meal plan from the database.",
+                    )))
+                }
+            }
+        }
+        (None, Ok(k)) => k.claims.subject_id,
+        (None, Err(_)) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to export your meal plan.",
+            )))
+        }
+    };
+
+    let (monday, _) = this_week();
+    let default = (monday - Duration::weeks(4), monday + Duration::weeks(12));
+    let range = match date_range(from, to, default) {
+        Ok(r) => r,
+        Err(err) => return Err(RecipeResponse::BadRequest(err)),
+    };
+    let entries = match load_range(&conn, user_id, range).await {
+        Ok(res) => res,
+        Err(err) => return Err(RecipeResponse::InternalServerError(err)),
+    };
+
+    let events = entries
+        .iter()
+        .map(|e| {
+            let start = e.date.and_time(e.slot.time());
+            let minutes = e.recipe.timer.filter(|t| *t > 0).unwrap_or(30);
+            let ingredients = e
+                .recipe
+                .ingredients
+                .iter()
+                .map(|i| {
+                    let amount = i.amount.map(|a| format!("{} ", a)).unwrap_or_default();
+                    let unit = i
+                        .unit
+                        .as_ref()
+                        .map(|u| format!("{} ", u))
+                        .unwrap_or_default();
+                    format!("{}{}{}", amount, unit, i.label)
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            CalendarEvent {
+                uid: format!("meal-plan-{}@crimson-eagle", e.id),
+                start,
+                end: start + Duration::minutes(minutes as i64),
+                summary: e.recipe.title.clone(),
+                description: match e.servings {
+                    1 => format!("1 serving\n\n{}", ingredients),
+                    s => format!("{} servings\n\n{}", s, ingredients),
+                },
+            }
+        })
+        .collect::<Vec<CalendarEvent>>();
+
+    Ok((
+        ContentType::Calendar,
+        calendar("Meal plan", &events, Utc::now().naive_utc()),
+    ))
+}
+
+/// Create calendar subscription
+///
+/// Create the secret token calendar apps subscribe to `GET /mealplan.ics` with.
+/// An existing token is replaced, calendars subscribed with it stop updating.
+#[utoipa::path(
+    post,
+    path = "/mealplan/feed",
+    tag = "meal plan",
+    responses(
+        (status = 201, description = "Token created succesfully", body = MealPlanFeedDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error saving the token"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/mealplan/feed")]
+pub async fn create_meal_plan_feed(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<MealPlanFeedDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to subscribe to your meal plan.",
+            ))
+        }
+    };
+
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let stored = token.clone();
+    let result = conn
+        .run(move |c| {
+            diesel::insert_into(meal_plan_feeds::table)
+                .values((
+                    meal_plan_feeds::user_id.eq(user_id),
+                    meal_plan_feeds::token.eq(&stored),
+                ))
+                .on_conflict(meal_plan_feeds::user_id)
+                .do_update()
+                .set((
+                    meal_plan_feeds::token.eq(&stored),
+                    meal_plan_feeds::created_at.eq(diesel::dsl::now),
+                ))
+                .execute(c)
+        })
+        .await;
+
+    match result {
+        Ok(_) => RecipeResponse::Created(Json(MealPlanFeedDTO {
+            path: format!("/mealplan.ics?token={}", token),
+            token,
+        })),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the calendar token in the database.",
+        )),
+    }
+}
+
+/// Delete calendar subscription
+///
+/// Revoke the calendar token of the logged in user.
+#[utoipa::path(
+    delete,
+    path = "/mealplan/feed",
+    tag = "meal plan",
+    responses(
+        (status = 204, description = "Token deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "There is no token"),
+        (status = 500, description = "Error deleting the token"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/mealplan/feed")]
+pub async fn delete_meal_plan_feed(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<MealPlanFeedDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to unsubscribe from your meal plan.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| diesel::delete(meal_plan_feeds::table.find(user_id)).execute(c))
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "There is no calendar token.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the calendar token from the database.",
+        ))),
+    }
+}
@@ -1,12 +1,31 @@
+pub mod admin_controller;
 pub mod bookmark_controller;
+pub mod collaborator_controller;
+pub mod collection_controller;
+pub mod comment_controller;
+pub mod fork_controller;
+pub mod ingredient_controller;
+pub mod ingredient_helper;
+pub mod meal_plan_controller;
+pub mod pantry_controller;
+pub mod rating_controller;
 pub mod recipe_controller;
 pub mod recipe_create_controller;
+pub mod recipe_filter_controller;
 pub mod recipe_helper;
 pub mod recipe_update_controller;
+pub mod revision_controller;
+pub mod session_controller;
+pub mod shopping_list_controller;
 pub mod tag_controller;
+pub mod trash_controller;
 pub mod user_controller;
 
 pub use self::{
-    bookmark_controller::*, recipe_controller::*, recipe_create_controller::*, recipe_helper::*,
-    recipe_update_controller::*, tag_controller::*, user_controller::*,
+    admin_controller::*, bookmark_controller::*, collaborator_controller::*,
+    collection_controller::*, comment_controller::*, fork_controller::*, ingredient_controller::*,
+    ingredient_helper::*, meal_plan_controller::*, pantry_controller::*, rating_controller::*,
+    recipe_controller::*, recipe_create_controller::*, recipe_filter_controller::*,
+    recipe_helper::*, recipe_update_controller::*, revision_controller::*, session_controller::*,
+    shopping_list_controller::*, tag_controller::*, trash_controller::*, user_controller::*,
 };
@@ -0,0 +1,511 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use std::collections::HashMap;
+use validator::Validate;
+
+use crate::models::*;
+use crate::pantry::{coverage, Coverage, Need, Stock};
+use crate::schema::*;
+use crate::units::{normalize_unit, UnitSystem};
+use crate::LogsDbConn;
+
+use super::{convert_units, find_ingredient, get_recipe_elements, listed_recipes};
+
+fn pantry_item(c: &mut PgConnection, item_id: i32) -> QueryResult<PantryItemDTO> {
+    pantry_items::table
+        .find(item_id)
+        .first::<PantryItem>(c)
+        .map(PantryItemDTO::from)
+}
+
+/// Item name with single spaces, empty when there is no name
+fn item_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Pantry
+///
+/// Get the pantry of the logged in user, the items that expire soonest first.
+#[utoipa::path(
+    get,
+    path = "/pantry",
+    tag = "pantry",
+    responses(
+        (status = 200, description = "Pantry found succesfully", body = Vec<PantryItemDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error loading the pantry"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/pantry")]
+pub async fn pantry(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<PantryItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from("Please log in to see your pantry."))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            pantry_items::table
+                .filter(pantry_items::user_id.eq(user_id))
+                .order((
+                    pantry_items::expires_on.asc().nulls_last(),
+                    pantry_items::label.asc(),
+                    pantry_items::id.asc(),
+                ))
+                .load::<PantryItem>(c)
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Ok(Json(res.into_iter().map(PantryItemDTO::from).collect())),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read the pantry from the database.",
+        )),
+    }
+}
+
+/// Add pantry item
+///
+/// Add an ingredient to the pantry of the logged in user. The name is matched against the
+/// catalogue, but never added to it.
+#[utoipa::path(
+    post,
+    path = "/pantry",
+    tag = "pantry",
+    request_body = PantryItemPostDTO,
+    responses(
+        (status = 201, description = "Item added succesfully", body = PantryItemDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 422, description = "Invalid item"),
+        (status = 500, description = "Error saving the pantry"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/pantry", data = "<item>")]
+pub async fn add_pantry_item(
+    conn: LogsDbConn,
+    item: Json<PantryItemPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PantryItemDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your pantry.",
+            ))
+        }
+    };
+
+    match item.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let item = item.into_inner();
+    let label = item_label(&item.label);
+    if label.is_empty() {
+        return RecipeResponse::BadRequest(String::from("The item needs a name."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let ingredient = find_ingredient(c, &label)?;
+                let id = diesel::insert_into(pantry_items::table)
+                    .values(PantryItemInsert {
+                        user_id,
+                        ingredient_id: ingredient.map(|i| i.id),
+                        label,
+                        amount: item.amount,
+                        unit: item.unit.as_deref().and_then(normalize_unit),
+                        expires_on: item.expires_on,
+                    })
+                    .returning(pantry_items::id)
+                    .get_result::<i32>(c)?;
+                pantry_item(c, id)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Created(Json(res)),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the pantry in the database.",
+        )),
+    }
+}
+
+/// Update pantry item
+///
+/// Change the ingredient, amount or expiry date of an item in the logged in user's pantry.
+#[utoipa::path(
+    put,
+    path = "/pantry/{item_id}",
+    tag = "pantry",
+    request_body = PantryItemPutDTO,
+    responses(
+        (status = 200, description = "Item updated succesfully", body = PantryItemDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Item was not found"),
+        (status = 422, description = "Invalid item"),
+        (status = 500, description = "Error saving the item"),
+    ),
+    params(
+        ("item_id" = i32, description = "Pantry item id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/pantry/<item_id>", data = "<item>")]
+pub async fn update_pantry_item(
+    conn: LogsDbConn,
+    item_id: i32,
+    item: Json<PantryItemPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PantryItemDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your pantry.",
+            ))
+        }
+    };
+
+    match item.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let item = item.into_inner();
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let current = pantry_items::table
+                    .find(item_id)
+                    .filter(pantry_items::user_id.eq(user_id))
+                    .first::<PantryItem>(c)
+                    .optional()?;
+                let current = match current {
+                    Some(i) => i,
+                    None => return Ok(None),
+                };
+
+                let label = item
+                    .label
+                    .as_deref()
+                    .map(item_label)
+                    .filter(|l| !l.is_empty());
+                let ingredient_id = match &label {
+                    Some(l) => find_ingredient(c, l)?.map(|i| i.id),
+                    None => current.ingredient_id,
+                };
+                let unit = match item.unit {
+                    Some(u) => u.as_deref().and_then(normalize_unit),
+                    None => current.unit,
+                };
+                diesel::update(pantry_items::table.find(item_id))
+                    .set((
+                        pantry_items::ingredient_id.eq(ingredient_id),
+                        pantry_items::label.eq(label.unwrap_or(current.label)),
+                        pantry_items::amount.eq(item.amount.unwrap_or(current.amount)),
+                        pantry_items::unit.eq(unit),
+                        pantry_items::expires_on.eq(item.expires_on.unwrap_or(current.expires_on)),
+                    ))
+                    .execute(c)?;
+                pantry_item(c, item_id).map(Some)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The item was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the item in the database.",
+        )),
+    }
+}
+
+/// Delete pantry item
+///
+/// Remove an item from the logged in user's pantry.
+#[utoipa::path(
+    delete,
+    path = "/pantry/{item_id}",
+    tag = "pantry",
+    responses(
+        (status = 204, description = "Item deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Item was not found"),
+        (status = 500, description = "Error deleting the item"),
+    ),
+    params(
+        ("item_id" = i32, description = "Pantry item id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/pantry/<item_id>")]
+pub async fn delete_pantry_item(
+    conn: LogsDbConn,
+    item_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<PantryItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your pantry.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            diesel::delete(pantry_items::table.find(item_id))
+                .filter(pantry_items::user_id.eq(user_id))
+                .execute(c)
+        })
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "The item was not found.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the item from the database.",
+        ))),
+    }
+}
+
+/// What can I cook
+///
+/// Rank recipes by how much of their ingredient list is covered, by the given `ingredients`
+/// or else by the pantry of the logged in user. Pantry amounts are compared with the recipe
+/// amounts when their units convert. Every recipe lists the ingredients that are missing.
+/// With `sort=expiring` recipes using the pantry items that expire soonest come first.
+#[utoipa::path(
+    get,
+    path = "/recipes/cookable",
+    tag = "pantry",
+    responses(
+        (status = 200, description = "Recipes ranked succesfully", body = Vec<CookableRecipeDTO>),
+        (status = 401, description = "Neither ingredients given nor logged in"),
+        (status = 422, description = "Invalid min_coverage"),
+        (status = 500, description = "Error loading recipes"),
+    ),
+    params(
+        ("ingredients" = Option<Vec<String>>, Query, description = "Ingredients at hand, instead of the pantry", example = json!(["eggs", "flour"])),
+        ("sort" = Option<CookableSort>, Query, description = "coverage or expiring, defaults to coverage"),
+        ("min_coverage" = Option<f32>, Query, description = "Only recipes with at least this share of their ingredients covered, 0 to 1", example = 0.5),
+        ("limit" = Option<i64>, Query, description = "Number of recipes, 1 to 100, defaults to 20"),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/cookable?<ingredients>&<sort>&<min_coverage>&<limit>&<units>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn cookable_recipes(
+    conn: LogsDbConn,
+    ingredients: Vec<String>,
+    sort: Option<CookableSort>,
+    min_coverage: Option<f32>,
+    limit: Option<i64>,
+    units: Option<UnitSystem>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<CookableRecipeDTO>> {
+    let user_id = key.ok().map(|k| k.claims.subject_id);
+    let min_coverage = min_coverage.unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&min_coverage) {
+        return RecipeResponse::BadRequest(String::from("min_coverage must be between 0 and 1."));
+    }
+    let limit = limit.unwrap_or(20).clamp(1, 100) as usize;
+
+    let stock = match (ingredients.is_empty(), user_id) {
+        // ad-hoc lists only name ingredients, names that are not in the catalogue match nothing
+        (false, _) => {
+            conn.run(move |c| {
+                ingredients
+                    .iter()
+                    .filter_map(|name| find_ingredient(c, name.trim()).transpose())
+                    .map(|found| {
+                        found.map(|i| Stock {
+                            ingredient_id: i.id,
+                            amount: None,
+                            unit: None,
+                            expires_on: None,
+                        })
+                    })
+                    .collect::<QueryResult<Vec<Stock>>>()
+            })
+            .await
+        }
+        (true, Some(user_id)) => conn
+            .run(move |c| {
+                pantry_items::table
+                    .filter(pantry_items::user_id.eq(user_id))
+                    .load::<PantryItem>(c)
+            })
+            .await
+            .map(|items| {
+                // items that are not in the catalogue match no recipe
+                items
+                    .into_iter()
+                    .filter_map(|i| {
+                        Some(Stock {
+                            ingredient_id: i.ingredient_id?,
+                            amount: i.amount,
+                            unit: i.unit,
+                            expires_on: i.expires_on,
+                        })
+                    })
+                    .collect()
+            }),
+        (true, None) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to use your pantry, or list the ingredients you have.",
+            ))
+        }
+    };
+    let stock = match stock {
+        Ok(s) => s,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read the pantry from the database.",
+            ))
+        }
+    };
+    if stock.is_empty() {
+        return RecipeResponse::Ok(Json(vec![]));
+    }
+
+    // ingredient lines of every recipe that uses at least one of the ingredients
+    let stock_ids = stock.iter().map(|s| s.ingredient_id).collect::<Vec<i32>>();
+    let lines = conn
+        .run(move |c| {
+            let candidates = recipes::table
+                .filter(recipes::deleted_at.is_null())
+                .filter(listed_recipes(user_id))
+                .filter(
+                    recipes::id.eq_any(
+                        recipe_ingredients::table
+                            .filter(recipe_ingredients::ingredient_id.eq_any(stock_ids))
+                            .select(recipe_ingredients::recipe_id),
+                    ),
+                )
+                .select(recipes::id)
+                .load::<i32>(c)?;
+            recipe_ingredients::table
+                .inner_join(recipes::table)
+                .inner_join(ingredients::table)
+                .filter(recipe_ingredients::recipe_id.eq_any(candidates))
+                .filter(recipes::deleted_at.is_null())
+                .order((recipe_ingredients::recipe_id, recipe_ingredients::id))
+                .select((
+                    recipe_ingredients::recipe_id,
+                    recipe_ingredients::ingredient_id,
+                    ingredients::label,
+                    recipe_ingredients::amount,
+                    recipe_ingredients::unit,
+                ))
+                .load::<(i32, i32, String, Option<f32>, Option<String>)>(c)
+        })
+        .await;
+    let lines = match lines {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read ingredients from the database.",
+            ))
+        }
+    };
+
+    let mut needs = HashMap::<i32, Vec<Need>>::new();
+    for (recipe_id, ingredient_id, label, amount, unit) in &lines {
+        needs.entry(*recipe_id).or_default().push(Need {
+            ingredient_id: *ingredient_id,
+            label,
+            amount: *amount,
+            unit: unit.as_deref(),
+        });
+    }
+    let mut ranked = needs
+        .iter()
+        .map(|(recipe_id, needs)| (*recipe_id, coverage(needs, &stock)))
+        .filter(|(_, c)| c.ratio() >= min_coverage)
+        .collect::<Vec<(i32, Coverage)>>();
+    ranked.sort_by(|(a_id, a), (b_id, b)| {
+        let by_coverage = b
+            .ratio()
+            .total_cmp(&a.ratio())
+            .then(a.missing.len().cmp(&b.missing.len()));
+        match sort.unwrap_or(CookableSort::Coverage) {
+            CookableSort::Coverage => by_coverage,
+            // recipes without expiring items last
+            CookableSort::Expiring => a
+                .expires_on
+                .is_none()
+                .cmp(&b.expires_on.is_none())
+                .then(a.expires_on.cmp(&b.expires_on))
+                .then(by_coverage),
+        }
+        .then(b_id.cmp(a_id))
+    });
+    ranked.truncate(limit);
+
+    let ids = ranked.iter().map(|(id, _)| *id).collect::<Vec<i32>>();
+    let recipes_list = match conn
+        .run(move |c| {
+            recipes::table
+                .filter(recipes::id.eq_any(ids))
+                .load::<Recipe>(c)
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read recipes from the database.",
+            ))
+        }
+    };
+    let mut recipes_found = match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => convert_units(res, units)
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect::<HashMap<i32, RecipeResultDTO>>(),
+        Err(err) => return RecipeResponse::InternalServerError(err),
+    };
+
+    let result = ranked
+        .into_iter()
+        .filter_map(|(id, c)| {
+            Some(CookableRecipeDTO {
+                recipe: recipes_found.remove(&id)?,
+                coverage: (c.ratio() * 100.0).round() / 100.0,
+                covered: c.covered as i64,
+                total: c.total as i64,
+                missing: c.missing,
+                expires_on: c.expires_on,
+            })
+        })
+        .collect();
+    RecipeResponse::Ok(Json(result))
+}
@@ -0,0 +1,243 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{pagination, readable_recipe, recipe_role};
+
+/// Rate recipe
+///
+/// Give a recipe 1 to 5 stars, optionally with a written review.
+/// Rating a recipe again replaces the previous rating. Owners cannot rate their own recipes.
+#[utoipa::path(
+    put,
+    path = "/recipes/{recipe_id}/rating",
+    tag = "recipes",
+    request_body = RatingPutDTO,
+    responses(
+        (status = 200, description = "Rating saved succesfully", body = RatingDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is the owner or an editor of the recipe"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 422, description = "Invalid rating"),
+        (status = 500, description = "Error saving the rating"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/recipes/<recipe_id>/rating", data = "<rating>")]
+pub async fn rate_recipe(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    rating: Json<RatingPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<RatingDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from("Please log in to rate recipes."))
+        }
+    };
+
+    match rating.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let rating = rating.into_inner();
+    let review = rating
+        .review
+        .map(|r| String::from(r.trim()))
+        .filter(|r| !r.is_empty());
+
+    let recipe = conn
+        .run(move |c| {
+            let found = readable_recipe(c, recipe_id, Some(user_id))?;
+            let editable = recipe_role(c, recipe_id, user_id)?.is_some_and(|r| r.can_edit());
+            Ok::<_, diesel::result::Error>((found, editable))
+        })
+        .await;
+    match recipe {
+        Ok((false, _)) => {
+            return RecipeResponse::NotFound(String::from("The recipe was not found."))
+        }
+        Ok((true, true)) => {
+            return RecipeResponse::Forbidden(String::from(
+                "You cannot rate a recipe you can edit.",
+            ))
+        }
+        Ok((true, false)) => (),
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read the recipe from the database.",
+            ))
+        }
+    }
+
+    let result = conn
+        .run(move |c| {
+            diesel::insert_into(ratings::table)
+                .values((
+                    ratings::recipe_id.eq(recipe_id),
+                    ratings::user_id.eq(user_id),
+                    ratings::stars.eq(rating.stars),
+                    ratings::review.eq(&review),
+                ))
+                .on_conflict((ratings::recipe_id, ratings::user_id))
+                .do_update()
+                .set((
+                    ratings::stars.eq(rating.stars),
+                    ratings::review.eq(&review),
+                    ratings::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<Rating>(c)
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Ok(Json(RatingDTO::from(res))),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the rating in the database.",
+        )),
+    }
+}
+
+/// Delete rating
+///
+/// Take back the logged in user's rating and review of a recipe.
+#[utoipa::path(
+    delete,
+    path = "/recipes/{recipe_id}/rating",
+    tag = "recipes",
+    responses(
+        (status = 204, description = "Rating deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not rated by the logged in user"),
+        (status = 500, description = "Error deleting the rating"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/recipes/<recipe_id>/rating")]
+pub async fn delete_rating(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<RatingDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to rate recipes.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| diesel::delete(ratings::table.find((recipe_id, user_id))).execute(c))
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "You have not rated this recipe.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the rating from the database.",
+        ))),
+    }
+}
+
+/// Recipe reviews
+///
+/// Get the written reviews of a recipe, newest first.
+/// Ratings without review text only count towards the average.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/reviews?{page}&{per_page}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Reviews found succesfully", body = PaginatedResult<ReviewDTO>),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Error loading reviews"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/reviews?<page>&<per_page>", rank = 2)]
+pub async fn recipe_reviews(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<ReviewDTO>> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+
+            let reviews = ratings::table
+                .filter(ratings::recipe_id.eq(recipe_id))
+                .filter(ratings::review.is_not_null());
+            let total: i64 = reviews.count().get_result(c)?;
+            let (current_page, per_page, offset) = pagination(page, per_page, total);
+            let records = reviews
+                .inner_join(users::table)
+                .order((ratings::created_at.desc(), ratings::user_id.desc()))
+                .offset(offset)
+                .limit(per_page)
+                .load::<(Rating, User)>(c)?
+                .into_iter()
+                .map(|(r, u)| ReviewDTO {
+                    username: u.username,
+                    stars: r.stars,
+                    review: r.review.unwrap_or_default(),
+                    created_at: r.created_at,
+                    updated_at: r.updated_at,
+                })
+                .collect::<Vec<ReviewDTO>>();
+
+            Ok::<_, diesel::result::Error>(Some(PaginatedResult {
+                records,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor: None,
+                prev_cursor: None,
+            }))
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read reviews from the database.",
+        )),
+    }
+}
@@ -3,53 +3,73 @@ use rocket::http::Status;
 use rocket::serde::json::Json;
 
 use crate::models::*;
-use crate::schema::recipes::dsl::*;
+use crate::schema::recipes::dsl::{deleted_at, recipes};
+use crate::units::UnitSystem;
 use crate::LogsDbConn;
 
+use super::convert_units;
 use super::get_recipe_elements;
-use super::pagination;
+use super::{listed_recipes, visible_recipes};
+use super::{load_recipe_page, load_search_page, Listing};
 
 /// List of recipes
 ///
-/// Get all recipes from the database
+/// Get all recipes from the database: the public ones, and the logged in user's own.
+/// Paged by `page` or, for stable paging on large lists, by the returned cursors.
 #[utoipa::path(
     get,
-    path = "/recipes?{page}&{per_page}",
+    path = "/recipes?{page}&{per_page}&{sort}&{direction}&{cursor}&{with_total}&{units}",
     tag = "recipes",
     responses(
         (status = 200, description = "Recipes found succesfully", body = [PaginatedResult<RecipeResultDTO>]),
+        (status = 422, description = "Invalid cursor or sort key"),
         (status = 500, description = "Error loading recipes"),
     ),
     params(
         ("page" = Option<i64>, Query, description = "Pagination: page number"),
         ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+        ("sort" = Option<RecipeSort>, Query, description = "Sort key: title, created_at, updated_at, timer, kcal, ingredients (number of ingredients), bookmarks (number of bookmarks), rating (average rating). Defaults to updated_at"),
+        ("direction" = Option<SortDirection>, Query, description = "Sort direction: asc or desc. Defaults to asc for title, timer and kcal, desc for the rest"),
+        ("cursor" = Option<String>, Query, description = "Cursor pagination: next_cursor or prev_cursor of a previous response, overrides page, sort and direction"),
+        ("with_total" = Option<bool>, Query, description = "Cursor pagination: count the total as well, defaults to false"),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
     ),
 )]
-#[get("/recipes?<page>&<per_page>")]
+#[get("/recipes?<page>&<per_page>&<sort>&<direction>&<cursor>&<with_total>&<units>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn recipe(
     conn: LogsDbConn,
     page: Option<i64>,
     per_page: Option<i64>,
+    sort: Option<RecipeSort>,
+    direction: Option<SortDirection>,
+    cursor: Option<String>,
+    with_total: Option<bool>,
+    units: Option<UnitSystem>,
     key: Result<Jwt, NetworkResponse>,
 ) -> RecipeResponse<PaginatedResult<RecipeResultDTO>> {
-    let total: i64 = match conn.run(|c| recipes.count().get_result(c)).await {
-        Ok(c) => c,
-        Err(_) => {
-            return RecipeResponse::InternalServerError(String::from(
-                "Database error while counting records.",
-            ))
-        }
+    let listing = match Listing::new(page, per_page, sort, direction, cursor, with_total) {
+        Ok(l) => l,
+        Err(err) => return RecipeResponse::BadRequest(err),
     };
 
-    let (current_page, per_page, offset) = pagination(page, per_page, total);
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
 
-    let recipes_list = match conn
+    let mut recipe_page = match conn
         .run(move |c| {
-            recipes
-                .order(updated_at.desc())
-                .offset(offset)
-                .limit(per_page)
-                .load::<Recipe>(c)
+            load_recipe_page(
+                c,
+                || {
+                    recipes
+                        .filter(deleted_at.is_null())
+                        .filter(listed_recipes(user_id))
+                        .into_boxed()
+                },
+                &listing,
+            )
         })
         .await
     {
@@ -61,21 +81,9 @@ pub async fn recipe(
         }
     };
 
-    let user_id: Option<i32> = match key {
-        Ok(k) => Some(k.claims.subject_id),
-        Err(_) => None,
-    };
-
-    match get_recipe_elements(recipes_list, conn, user_id).await {
-        Ok(res) => {
-            let paginated = PaginatedResult {
-                records: res,
-                total,
-                current_page,
-                per_page,
-            };
-            RecipeResponse::Ok(Json(paginated))
-        }
+    let recipes_list = std::mem::take(&mut recipe_page.records);
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => RecipeResponse::Ok(Json(recipe_page.into_result(convert_units(res, units)))),
         Err(_) => RecipeResponse::InternalServerError(String::from(
             "Cannot read recipes from the database.",
         )),
@@ -84,49 +92,55 @@ pub async fn recipe(
 
 /// List of recipes, filtered
 ///
-/// Get filtered and paginated list of recipes from the database
+/// Full-text search over title, tags, ingredients and instructions of the public recipes
+/// and the logged in user's own. Results are ranked by relevance and carry a highlighted snippet.
+/// Paged by `page` or by the returned cursors, like the list of recipes.
 #[utoipa::path(
     get,
-    path = "/recipes/search/{query}?{page}&{per_page}",
+    path = "/recipes/search/{query}?{page}&{per_page}&{sort}&{direction}&{cursor}&{with_total}&{units}",
     tag = "recipes",
     responses(
         (status = 200, description = "Recipes found succesfully", body = [PaginatedResult<RecipeResultDTO>]),
+        (status = 422, description = "Invalid cursor"),
         (status = 500, description = "Internal Server Error")
     ),
     params(
-        ("query" = String, Path, description = "Search term", example = "shrimp"),
+        ("query" = String, Path, description = "Search term, supports quoted phrases, `or` and `-` exclusion", example = "shrimp"),
         ("page" = Option<i64>, Query, description = "Pagination: page number"),
         ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+        ("sort" = Option<RecipeSort>, Query, description = "Sort key: title, created_at, updated_at, timer, kcal, ingredients (number of ingredients), bookmarks (number of bookmarks), rating (average rating), relevance. Defaults to relevance"),
+        ("direction" = Option<SortDirection>, Query, description = "Sort direction: asc or desc. Defaults to asc for title, timer and kcal, desc for the rest"),
+        ("cursor" = Option<String>, Query, description = "Cursor pagination: next_cursor or prev_cursor of a previous response, overrides page, sort and direction"),
+        ("with_total" = Option<bool>, Query, description = "Cursor pagination: count the total as well, defaults to false"),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
     )
 )]
-#[get("/recipes/search/<query>?<page>&<per_page>")]
+#[get("/recipes/search/<query>?<page>&<per_page>&<sort>&<direction>&<cursor>&<with_total>&<units>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn search(
     conn: LogsDbConn,
     query: String,
     page: Option<i64>,
     per_page: Option<i64>,
+    sort: Option<RecipeSort>,
+    direction: Option<SortDirection>,
+    cursor: Option<String>,
+    with_total: Option<bool>,
+    units: Option<UnitSystem>,
     key: Result<Jwt, NetworkResponse>,
 ) -> RecipeResponse<PaginatedResult<RecipeResultDTO>> {
-    let total: i64 = match conn.run(|c| recipes.count().get_result(c)).await {
-        Ok(c) => c,
-        Err(_) => {
-            return RecipeResponse::InternalServerError(String::from(
-                "Database error while counting records.",
-            ))
-        }
+    let listing = match Listing::search(page, per_page, sort, direction, cursor, with_total) {
+        Ok(l) => l,
+        Err(err) => return RecipeResponse::BadRequest(err),
     };
 
-    let (current_page, per_page, offset) = pagination(page, per_page, total);
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
 
-    let recipes_list = match conn
-        .run(move |c| {
-            recipes
-                .filter(title.ilike(format!("%{}%", query)))
-                .order(updated_at.desc())
-                .offset(offset)
-                .limit(per_page)
-                .load::<Recipe>(c)
-        })
+    let (mut recipe_page, hits) = match conn
+        .run(move |c| load_search_page(c, &query, user_id, &listing))
         .await
     {
         Ok(res) => res,
@@ -137,20 +151,20 @@ pub async fn search(
         }
     };
 
-    let user_id: Option<i32> = match key {
-        Ok(k) => Some(k.claims.subject_id),
-        Err(_) => None,
-    };
-
-    match get_recipe_elements(recipes_list, conn, user_id).await {
+    let recipes_list = std::mem::take(&mut recipe_page.records);
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
         Ok(res) => {
-            let paginated = PaginatedResult {
-                records: res,
-                total,
-                current_page,
-                per_page,
-            };
-            RecipeResponse::Ok(Json(paginated))
+            let records = res
+                .into_iter()
+                .map(|mut r| {
+                    r.snippet = hits
+                        .iter()
+                        .find(|h| h.id == r.id)
+                        .map(|h| h.snippet.clone());
+                    r
+                })
+                .collect::<Vec<RecipeResultDTO>>();
+            RecipeResponse::Ok(Json(recipe_page.into_result(convert_units(records, units))))
         }
         Err(_) => RecipeResponse::InternalServerError(String::from(
             "Cannot read recipes from the database.",
@@ -160,27 +174,61 @@ pub async fn search(
 
 /// Find recipe
 ///
-/// Get recipe by id from the database
+/// Get recipe by id from the database. Private recipes are only found by their owners.
+/// With `servings` or `factor` the ingredient amounts and nutrition are scaled,
+/// amounts are rounded to kitchen precision.
 #[utoipa::path(
     get,
-    path = "/recipes/{recipe_id}",
+    path = "/recipes/{recipe_id}?{servings}&{factor}&{units}",
     tag = "recipes",
     responses(
         (status = 200, description = "Recipe found succesfully", body = RecipeResultDTO),
+        (status = 422, description = "Invalid servings or factor"),
         (status = NOT_FOUND, description = "Recipe was not found"),
     ),
     params(
         ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("servings" = Option<i16>, Query, description = "Scale the recipe to this many servings", example = 6),
+        ("factor" = Option<f32>, Query, description = "Scale the recipe by this factor", example = 1.5),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
     )
 )]
-#[get("/recipes/<recipe_id>")]
+#[get("/recipes/<recipe_id>?<servings>&<factor>&<units>")]
 pub async fn single_recipe(
     conn: LogsDbConn,
     recipe_id: i32,
+    servings: Option<i16>,
+    factor: Option<f32>,
+    units: Option<UnitSystem>,
     key: Result<Jwt, NetworkResponse>,
 ) -> RecipeResponse<RecipeResultDTO> {
+    if servings.is_some() && factor.is_some() {
+        return RecipeResponse::BadRequest(String::from(
+            "Scale the recipe either by servings or by factor.",
+        ));
+    }
+    if servings.is_some_and(|s| !(1..=1000).contains(&s)) {
+        return RecipeResponse::BadRequest(String::from("Servings must be between 1 and 1000."));
+    }
+    if factor.is_some_and(|f| !(f > 0.0 && f <= 100.0)) {
+        return RecipeResponse::BadRequest(String::from(
+            "Factor must be greater than 0 and at most 100.",
+        ));
+    }
+
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
     let recipes_list = match conn
-        .run(move |c| recipes.find(recipe_id).load::<Recipe>(c))
+        .run(move |c| {
+            recipes
+                .find(recipe_id)
+                .filter(deleted_at.is_null())
+                .filter(visible_recipes(user_id))
+                .load::<Recipe>(c)
+        })
         .await
     {
         Ok(res) => res,
@@ -191,14 +239,26 @@ pub async fn single_recipe(
         }
     };
 
-    let user_id: Option<i32> = match key {
-        Ok(k) => Some(k.claims.subject_id),
-        Err(_) => None,
-    };
-
-    match get_recipe_elements(recipes_list, conn, user_id).await {
-        Ok(res) => match res.first() {
-            Some(r) => RecipeResponse::Ok(Json(r.clone())),
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => match res.into_iter().next() {
+            Some(r) => {
+                let recipe = match (servings, factor) {
+                    (Some(s), _) => {
+                        let f = s as f32 / r.servings as f32;
+                        r.scaled(f, s)
+                    }
+                    (None, Some(f)) => {
+                        let s = ((r.servings as f32 * f).round() as i16).max(1);
+                        r.scaled(f, s)
+                    }
+                    (None, None) => r,
+                };
+                let recipe = match units {
+                    Some(system) => recipe.converted(system),
+                    None => recipe,
+                };
+                RecipeResponse::Ok(Json(recipe))
+            }
             None => RecipeResponse::NotFound(String::from("The recipe was not found.")),
         },
         Err(_) => RecipeResponse::InternalServerError(String::from(
@@ -209,55 +269,60 @@ pub async fn single_recipe(
 
 /// Delete recipe
 ///
-/// Find recipe by id and remove it from the database
+/// Move recipe to the trash, or remove it permanently together with its instructions,
+/// ingredients, tags, bookmarks and collaborators. Only the owner can delete a recipe.
+/// A recipe that is already in the trash is always removed permanently.
 #[utoipa::path(
     delete,
-    path = "/recipes/{id}",
+    path = "/recipes/{recipe_id}?{permanent}",
     tag = "recipes",
     responses(
-        (status = 200, description = "Recipe deleted succesfully"),
+        (status = 204, description = "Recipe deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
         (status = NOT_FOUND, description = "Recipe was not found"),
     ),
     params(
-        ("del_id" = i32, description = "Recipe id"),
+        ("recipe_id" = i32, description = "Recipe id"),
+        ("permanent" = Option<bool>, Query, description = "Skip the trash and remove the recipe permanently"),
     ),
     security(
         ("name" = ["Bearer"])
     ),
 )]
-#[delete("/recipes/<del_id>")]
+#[delete("/recipes/<recipe_id>?<permanent>")]
 pub async fn delete(
     conn: LogsDbConn,
-    del_id: i32,
-    key: Result<Jwt, NetworkResponse>,
+    recipe_id: i32,
+    permanent: Option<bool>,
+    owned: Result<OwnedRecipe, NetworkResponse>,
 ) -> Result<Status, RecipeResponse<RecipeResultDTO>> {
     use crate::schema::recipes;
 
-    match key {
-        Ok(k) => k.claims.subject_id,
-        Err(_) => {
-            return Err(RecipeResponse::Unauthorized(String::from(
-                "Please log in to be able to delete recipes.",
-            )))
-        }
+    let recipe = match owned.and_then(|o| o.owner_only("delete")) {
+        Ok(o) => o.recipe,
+        Err(err) => return Err(RecipeResponse::from(err)),
     };
-    // TODO: Fix deletion error:
-    // "update or delete on table "recipes" violates foreign key constraint "recipes_users_recipe_id_fkey" on table "recipes_users""
-    let num_deleted = match conn
-        .run(move |c| diesel::delete(recipes::table.find(del_id)).execute(c))
+
+    // dependent rows are removed by ON DELETE CASCADE
+    let num_deleted = if permanent.unwrap_or(false) || recipe.deleted_at.is_some() {
+        conn.run(move |c| diesel::delete(recipes::table.find(recipe_id)).execute(c))
+            .await
+    } else {
+        conn.run(move |c| {
+            diesel::update(recipes::table.find(recipe_id))
+                .set(recipes::deleted_at.eq(diesel::dsl::now))
+                .execute(c)
+        })
         .await
-    {
-        Ok(num) => num,
-        Err(err) => {
-            return Err(RecipeResponse::InternalServerError(format!(
-                "Database error while deleting the recipe: {}",
-                err.to_string()
-            )))
-        }
     };
 
     match num_deleted {
-        0 => Err(RecipeResponse::NotFound(String::from("Recipe not found."))),
-        _ => Ok(Status::NoContent),
+        Ok(0) => Err(RecipeResponse::NotFound(String::from("Recipe not found."))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(err) => Err(RecipeResponse::InternalServerError(format!(
+            "Database error while deleting the recipe: {}",
+            err
+        ))),
     }
 }
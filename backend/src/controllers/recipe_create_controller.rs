@@ -2,11 +2,17 @@ use diesel::prelude::*;
 use rocket::serde::json::Json;
 use validator::Validate;
 
+use crate::ingredient_parser::parse_ingredient_lines;
 use crate::models::*;
 use crate::schema::recipes::dsl::*;
 use crate::schema::*;
 use crate::LogsDbConn;
 
+use super::{
+    add_nutrition, record_revision, refresh_nutrition, refresh_search_document,
+    resolve_ingredients, TransactionError,
+};
+
 /// Add recipe
 ///
 /// Create new recipe in the database
@@ -39,210 +45,133 @@ pub async fn create_recipe(
         }
     };
 
+    let mut addrecipe = addrecipe.into_inner();
+
+    // raw lines are parsed and added to the structured ingredients
+    if let Some(lines) = addrecipe.ingredient_lines.take() {
+        match parse_ingredient_lines(&lines) {
+            Ok(parsed) => addrecipe
+                .ingredients
+                .get_or_insert_with(Vec::new)
+                .extend(parsed.into_iter().map(|p| p.ingredient)),
+            Err(err) => return RecipeResponse::BadRequest(err),
+        }
+    }
+
     match addrecipe.validate() {
         Ok(_) => (),
         Err(err) => return RecipeResponse::BadRequest(err.to_string()),
     };
 
-    let addrecipe = addrecipe.into_inner();
-    let addrecipe_clone = addrecipe.clone();
-
-    let mut recipe = match conn
+    // every insert runs in one transaction, a failing step rolls back the whole recipe
+    let result = conn
         .run(move |c| {
-            diesel::insert_into(recipes)
-                .values(RecipesInput::from(&addrecipe_clone))
-                .get_result::<Recipe>(c)
+            c.transaction::<_, TransactionError, _>(|c| insert_recipe(c, addrecipe, user_id))
         })
-        .await
-    {
-        Ok(res) => RecipeResultDTO::from(res),
-        Err(_) => {
-            return RecipeResponse::InternalServerError(String::from(
-                "Cannot insert recipe into the database.",
-            ))
+        .await;
+
+    match result {
+        Ok(recipe) => RecipeResponse::Created(Json(recipe)),
+        Err(err) => {
+            RecipeResponse::InternalServerError(format!("{} The recipe was not created.", err))
         }
-    };
+    }
+}
+
+fn insert_recipe(
+    c: &mut PgConnection,
+    addrecipe: RecipePostDTO,
+    user_id: i32,
+) -> Result<RecipeResultDTO, TransactionError> {
+    let mut recipe = diesel::insert_into(recipes)
+        .values(RecipesInput::from(&addrecipe))
+        .get_result::<Recipe>(c)
+        .map(RecipeResultDTO::from)
+        .map_err(|_| TransactionError::from("Cannot insert recipe into the database."))?;
 
     // add logged in user as owner
-    match conn
-        .run(move |c| {
-            diesel::insert_into(recipes_users::table)
-                .values((
-                    recipes_users::recipe_id.eq(recipe.id),
-                    recipes_users::user_id.eq(user_id),
-                ))
-                .execute(c)
-        })
-        .await
-    {
-        Ok(_) => (),
-        Err(_) => {
-            return RecipeResponse::InternalServerError(String::from(
-                "Cannot insert owner into the database.",
-            ))
-        }
-    };
-    recipe.owned = Some(true);
+    diesel::insert_into(recipes_users::table)
+        .values((
+            recipes_users::recipe_id.eq(recipe.id),
+            recipes_users::user_id.eq(user_id),
+            recipes_users::role.eq(RecipeRole::Owner.as_str()),
+        ))
+        .execute(c)
+        .map_err(|_| TransactionError::from("Cannot insert owner into the database."))?;
+    recipe.role = Some(RecipeRole::Owner);
 
     // add instructions
-    if addrecipe.instructions.is_some() && !addrecipe.instructions.clone().unwrap().is_empty() {
-        let instructions = addrecipe
-            .instructions
-            .clone()
-            .unwrap()
-            .into_iter()
+    if let Some(add_instructions) = addrecipe.instructions.filter(|i| !i.is_empty()) {
+        let instructions = add_instructions
+            .iter()
             .enumerate()
             .map(|(k, i)| InstructionInsert {
-                instruction: i,
+                instruction: i.clone(),
                 display_order: k as i32,
                 recipe_id: recipe.id,
             })
             .collect::<Vec<InstructionInsert>>();
-        match conn
-            .run(|c| {
-                diesel::insert_into(instructions::table)
-                    .values(instructions)
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                return RecipeResponse::InternalServerError(String::from(
-                    "Cannot insert instructions into the database.",
-                ))
-            }
-        };
-        recipe.instructions = addrecipe.instructions.unwrap();
+        diesel::insert_into(instructions::table)
+            .values(instructions)
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot insert instructions into the database."))?;
+        recipe.instructions = add_instructions;
     }
 
-    // add instructions
-    if addrecipe.ingredients.is_some() && !addrecipe.ingredients.clone().unwrap().is_empty() {
-        let available_ingredents =
-            match conn.run(|c| ingredients::table.load::<Ingredient>(c)).await {
-                Ok(res) => res,
-                Err(_) => {
-                    return RecipeResponse::InternalServerError(String::from(
-                        "Cannot load ingredients from the database.",
-                    ))
-                }
-            };
-
-        let mut recipe_ingredients_inserts = Vec::<RecipeIngredientInsert>::new();
-        let mut ingredient_inserts = Vec::<IngredientInsert>::new();
-        let mut delayed_inserts = Vec::<((Option<String>, String), Vec<Option<f32>>)>::new();
-        for addingredient in addrecipe.ingredients.clone().unwrap() {
-            let mut ingredient: Option<&Ingredient> = None;
-            if !available_ingredents.is_empty() {
-                ingredient = available_ingredents
-                    .iter()
-                    .find(|i| i.unit == addingredient.unit && i.label == addingredient.label);
-            }
-            match ingredient {
-                Some(i) => {
-                    recipe_ingredients_inserts.push(RecipeIngredientInsert {
-                        amount: addingredient.amount,
-                        recipe_id: recipe.id,
-                        ingredient_id: i.id,
-                    });
-                }
-                // need to add ingredients and then use those ids in another recipe_ingredients insert
-                None => {
-                    let mut found = false;
-                    for ((u, l), a) in &mut delayed_inserts {
-                        if u.clone() == addingredient.unit && l.clone() == addingredient.label {
-                            a.push(addingredient.amount);
-                            found = true;
-                        }
-                    }
-                    if !found {
-                        ingredient_inserts.push(IngredientInsert {
-                            unit: addingredient.unit.clone(),
-                            label: addingredient.label.clone(),
-                        });
-                        delayed_inserts.push((
-                            (addingredient.unit, addingredient.label),
-                            vec![addingredient.amount],
-                        ));
-                    }
-                }
-            }
-        }
-
-        // add ingredients, get ids
-        if !ingredient_inserts.is_empty() {
-            let new_ingredients = match conn
-                .run(move |c| {
-                    diesel::insert_into(ingredients::table)
-                        .values(&ingredient_inserts)
-                        .get_results::<Ingredient>(c)
-                })
-                .await
-            {
-                Ok(res) => res,
-                Err(_) => {
-                    return RecipeResponse::InternalServerError(String::from(
-                        "Cannot insert ingredients into the database.",
-                    ))
-                }
-            };
+    // add ingredients
+    if let Some(add_ingredients) = addrecipe.ingredients.filter(|i| !i.is_empty()) {
+        let add_ingredients = add_ingredients
+            .into_iter()
+            .map(IngredientDTO::normalized)
+            .collect::<Vec<IngredientDTO>>();
+        let labels = add_ingredients
+            .iter()
+            .map(|i| i.label.clone())
+            .collect::<Vec<String>>();
+        let catalogue = resolve_ingredients(c, &labels)?;
+
+        let recipe_ingredients_inserts = add_ingredients
+            .iter()
+            .zip(&catalogue)
+            .map(|(addingredient, ingredient)| RecipeIngredientInsert {
+                amount: addingredient.amount,
+                recipe_id: recipe.id,
+                ingredient_id: ingredient.id,
+                unit: addingredient.unit.clone(),
+            })
+            .collect::<Vec<RecipeIngredientInsert>>();
 
-            for ingredient in new_ingredients {
-                for ((unit, label), amounts) in &delayed_inserts {
-                    if ingredient.unit == unit.clone() && ingredient.label == label.clone() {
-                        for a in amounts {
-                            recipe_ingredients_inserts.push(RecipeIngredientInsert {
-                                amount: *a,
-                                recipe_id: recipe.id,
-                                ingredient_id: ingredient.id,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-        // add recipe_ingredients
+        // add recipe_ingredients, in posted order
         if !recipe_ingredients_inserts.is_empty() {
-            match conn
-                .run(|c| {
-                    diesel::insert_into(recipe_ingredients::table)
-                        .values(
-                            recipe_ingredients_inserts
-                                .into_iter()
-                                .rev() // reverse, to keep original order as much as possible
-                                .collect::<Vec<RecipeIngredientInsert>>(),
-                        )
-                        .execute(c)
-                })
-                .await
-            {
-                Ok(_) => (),
-                Err(_) => {
-                    return RecipeResponse::InternalServerError(String::from(
-                        "Cannot insert recipe ingredients into the database.",
-                    ))
-                }
-            };
+            diesel::insert_into(recipe_ingredients::table)
+                .values(recipe_ingredients_inserts)
+                .execute(c)
+                .map_err(|_| {
+                    TransactionError::from("Cannot insert recipe ingredients into the database.")
+                })?;
         }
-        recipe.ingredients = addrecipe.ingredients.unwrap();
+        // labels as stored in the catalogue, "Eggs" may become "egg"
+        recipe.ingredients = add_ingredients
+            .into_iter()
+            .zip(catalogue)
+            .map(|(i, ingredient)| IngredientDTO {
+                label: ingredient.label,
+                ..i
+            })
+            .collect::<Vec<IngredientDTO>>();
     }
 
     // add tags
-    if addrecipe.tags.is_some() && !addrecipe.tags.clone().unwrap().is_empty() {
-        let available_tags = match conn.run(|c| tags::table.load::<Tag>(c)).await {
-            Ok(res) => res,
-            Err(_) => {
-                return RecipeResponse::InternalServerError(String::from(
-                    "Cannot load tags from the database.",
-                ))
-            }
-        };
+    if let Some(add_tags) = addrecipe.tags.filter(|t| !t.is_empty()) {
+        let available_tags = tags::table
+            .load::<Tag>(c)
+            .map_err(|_| TransactionError::from("Cannot load tags from the database."))?;
 
         let mut tag_list = Vec::<TagDTO>::new();
         let mut recipes_tags_inserts = Vec::<RecipeTag>::new();
         let mut tag_inserts = Vec::<TagDTO>::new();
         let mut delayed_inserts = Vec::<String>::new();
-        for addtag in addrecipe.tags.clone().unwrap() {
+        for addtag in add_tags {
             let mut tag: Option<&Tag> = None;
             if !available_tags.is_empty() {
                 tag = available_tags.iter().find(|t| t.label == addtag);
@@ -276,21 +205,10 @@ pub async fn create_recipe(
 
         // add tags, get ids
         if !tag_inserts.is_empty() {
-            let new_tags = match conn
-                .run(move |c| {
-                    diesel::insert_into(tags::table)
-                        .values(&tag_inserts)
-                        .get_results::<Tag>(c)
-                })
-                .await
-            {
-                Ok(res) => res,
-                Err(_) => {
-                    return RecipeResponse::InternalServerError(String::from(
-                        "Cannot insert tags into the database.",
-                    ))
-                }
-            };
+            let new_tags = diesel::insert_into(tags::table)
+                .values(&tag_inserts)
+                .get_results::<Tag>(c)
+                .map_err(|_| TransactionError::from("Cannot insert tags into the database."))?;
 
             for t in &delayed_inserts {
                 for tag in &new_tags {
@@ -306,24 +224,26 @@ pub async fn create_recipe(
         }
         // add recipes_tags
         if !recipes_tags_inserts.is_empty() {
-            match conn
-                .run(|c| {
-                    diesel::insert_into(recipes_tags::table)
-                        .values(recipes_tags_inserts)
-                        .execute(c)
-                })
-                .await
-            {
-                Ok(_) => (),
-                Err(_) => {
-                    return RecipeResponse::InternalServerError(String::from(
-                        "Cannot insert recipe tags into the database.",
-                    ))
-                }
-            };
+            diesel::insert_into(recipes_tags::table)
+                .values(recipes_tags_inserts)
+                .execute(c)
+                .map_err(|_| {
+                    TransactionError::from("Cannot insert recipe tags into the database.")
+                })?;
         }
         recipe.tags = tag_list;
     }
 
-    RecipeResponse::Created(Json(recipe))
+    refresh_search_document(c, recipe.id)
+        .map_err(|_| TransactionError::from("Cannot update the search index."))?;
+    refresh_nutrition(c, &[recipe.id])
+        .map_err(|_| TransactionError::from("Cannot update the recipe nutrition."))?;
+
+    add_nutrition(c, std::slice::from_mut(&mut recipe))
+        .map_err(|_| TransactionError::from("Cannot read nutrients from the database."))?;
+
+    record_revision(c, &recipe, Some(user_id))
+        .map_err(|_| TransactionError::from("Cannot save the revision."))?;
+
+    Ok(recipe)
 }
@@ -0,0 +1,295 @@
+use diesel::dsl::{count_star, not, sql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Float};
+use rocket::serde::json::Json;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::units::UnitSystem;
+use crate::LogsDbConn;
+
+use super::convert_units;
+use super::get_recipe_elements;
+use super::ingredient_key;
+use super::{listed_recipes, load_recipe_page, Listing};
+
+/// Preparation time buckets for the timer facet, (min, max) in minutes
+const TIMER_BUCKETS: [(i16, Option<i16>); 5] = [
+    (0, Some(15)),
+    (15, Some(30)),
+    (30, Some(60)),
+    (60, Some(120)),
+    (120, None),
+];
+
+/// List of recipes, faceted
+///
+/// Get recipes matching every given filter, with facet counts for tags and preparation time.
+/// The tag facet counts the matching recipes per tag, the timer facet ignores `max_timer`
+/// so every bucket shows how many recipes it would return.
+#[utoipa::path(
+    get,
+    path = "/recipes/filter",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Recipes found succesfully", body = FilteredResult),
+        (status = 401, description = "Owned or bookmarked filter without being logged in"),
+        (status = 500, description = "Error loading recipes"),
+    ),
+    params(
+        RecipeFilter,
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+        ("sort" = Option<RecipeSort>, Query, description = "Sort key: title, created_at, updated_at, timer, kcal, ingredients (number of ingredients), bookmarks (number of bookmarks), rating (average rating). Defaults to updated_at"),
+        ("direction" = Option<SortDirection>, Query, description = "Sort direction: asc or desc. Defaults to asc for title, timer and kcal, desc for the rest"),
+        ("cursor" = Option<String>, Query, description = "Cursor pagination: next_cursor or prev_cursor of a previous response, overrides page, sort and direction"),
+        ("with_total" = Option<bool>, Query, description = "Cursor pagination: count the total as well, defaults to false"),
+        ("units" = Option<UnitSystem>, Query, description = "Convert ingredient amounts into metric or imperial units"),
+    ),
+)]
+#[get(
+    "/recipes/filter?<page>&<per_page>&<sort>&<direction>&<cursor>&<with_total>&<units>&<filter..>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn filter_recipes(
+    conn: LogsDbConn,
+    filter: RecipeFilter,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<RecipeSort>,
+    direction: Option<SortDirection>,
+    cursor: Option<String>,
+    with_total: Option<bool>,
+    units: Option<UnitSystem>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<FilteredResult> {
+    let listing = match Listing::new(page, per_page, sort, direction, cursor, with_total) {
+        Ok(l) => l,
+        Err(err) => return RecipeResponse::BadRequest(err),
+    };
+
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    if user_id.is_none() && (filter.owned == Some(true) || filter.bookmarked == Some(true)) {
+        return RecipeResponse::Unauthorized(String::from(
+            "Please log in to filter your own or bookmarked recipes.",
+        ));
+    }
+
+    let list_filter = filter.clone();
+    let mut recipe_page = match conn
+        .run(move |c| {
+            load_recipe_page(
+                c,
+                || filtered_recipes(&list_filter, user_id, true),
+                &listing,
+            )
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read recipes from the database.",
+            ))
+        }
+    };
+
+    let facets = match conn.run(move |c| recipe_facets(c, &filter, user_id)).await {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot count facets in the database.",
+            ))
+        }
+    };
+
+    let recipes_list = std::mem::take(&mut recipe_page.records);
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => RecipeResponse::Ok(Json(FilteredResult {
+            result: recipe_page.into_result(convert_units(res, units)),
+            facets,
+        })),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read recipes from the database.",
+        )),
+    }
+}
+
+/// Nutrient of the whole recipe as entered, or else as computed from its ingredients
+fn nutrient_value(column: &str) -> String {
+    format!(
+        "COALESCE(recipes.{column}::real, (SELECT recipe_nutrition.{column} FROM recipe_nutrition WHERE recipe_nutrition.recipe_id = recipes.id))"
+    )
+}
+
+/// Recipes matching the filter, `with_timer` false leaves out the `max_timer` condition
+fn filtered_recipes(
+    filter: &RecipeFilter,
+    user_id: Option<i32>,
+    with_timer: bool,
+) -> recipes::BoxedQuery<'static, Pg> {
+    let mut query = recipes::table
+        .filter(recipes::deleted_at.is_null())
+        .filter(listed_recipes(user_id))
+        .into_boxed();
+
+    // tags
+    if !filter.tags.is_empty() {
+        if filter.tag_mode == Some(TagMatch::Any) {
+            query = query.filter(
+                recipes::id.eq_any(
+                    recipes_tags::table
+                        .inner_join(tags::table)
+                        .filter(tags::slug.eq_any(filter.tags.clone()))
+                        .select(recipes_tags::recipe_id),
+                ),
+            );
+        } else {
+            for slug in &filter.tags {
+                query = query.filter(
+                    recipes::id.eq_any(
+                        recipes_tags::table
+                            .inner_join(tags::table)
+                            .filter(tags::slug.eq(slug.clone()))
+                            .select(recipes_tags::recipe_id),
+                    ),
+                );
+            }
+        }
+    }
+    if !filter.exclude_tags.is_empty() {
+        query = query.filter(not(recipes::id.eq_any(
+            recipes_tags::table
+                .inner_join(tags::table)
+                .filter(tags::slug.eq_any(filter.exclude_tags.clone()))
+                .select(recipes_tags::recipe_id),
+        )));
+    }
+
+    // time and nutrition
+    if with_timer {
+        if let Some(max) = filter.max_timer {
+            query = query.filter(recipes::timer.le(max));
+        }
+    }
+    for (column, min, max) in [
+        ("kcal", filter.min_kcal, filter.max_kcal),
+        ("carbs", filter.min_carbs, filter.max_carbs),
+        ("proteins", filter.min_proteins, filter.max_proteins),
+        ("fats", filter.min_fats, filter.max_fats),
+    ] {
+        if let Some(min) = min {
+            query = query.filter(
+                sql::<Bool>(&format!("{} >= ", nutrient_value(column)))
+                    .bind::<Float, _>(f32::from(min)),
+            );
+        }
+        if let Some(max) = max {
+            query = query.filter(
+                sql::<Bool>(&format!("{} <= ", nutrient_value(column)))
+                    .bind::<Float, _>(f32::from(max)),
+            );
+        }
+    }
+
+    // ingredients, matched like the catalogue does: case, plural and synonyms don't matter
+    for label in &filter.ingredients {
+        query = query.filter(recipes::id.eq_any(recipes_with_ingredient(label)));
+    }
+    for label in &filter.exclude_ingredients {
+        query = query.filter(not(recipes::id.eq_any(recipes_with_ingredient(label))));
+    }
+
+    // logged in user's recipes
+    if let Some(id) = user_id {
+        if filter.owned == Some(true) {
+            query = query.filter(
+                recipes::id.eq_any(
+                    recipes_users::table
+                        .filter(recipes_users::user_id.eq(id))
+                        .filter(recipes_users::role.eq(RecipeRole::Owner.as_str()))
+                        .select(recipes_users::recipe_id),
+                ),
+            );
+        }
+        if filter.bookmarked == Some(true) {
+            query = query.filter(
+                recipes::id.eq_any(
+                    bookmarks::table
+                        .filter(bookmarks::user_id.eq(id))
+                        .select(bookmarks::recipe_id),
+                ),
+            );
+        }
+    }
+
+    query
+}
+
+/// Ids of the recipes using an ingredient, found by its name or one of its synonyms
+fn recipes_with_ingredient(
+    label: &str,
+) -> recipe_ingredients::BoxedQuery<'static, Pg, diesel::sql_types::Integer> {
+    recipe_ingredients::table
+        .filter(
+            recipe_ingredients::ingredient_id
+                .eq_any(
+                    ingredients::table
+                        .filter(
+                            ingredient_key(ingredients::label)
+                                .eq(ingredient_key(label.to_string())),
+                        )
+                        .select(ingredients::id),
+                )
+                .or(recipe_ingredients::ingredient_id.eq_any(
+                    ingredient_synonyms::table
+                        .filter(
+                            ingredient_key(ingredient_synonyms::label)
+                                .eq(ingredient_key(label.to_string())),
+                        )
+                        .select(ingredient_synonyms::ingredient_id),
+                )),
+        )
+        .select(recipe_ingredients::recipe_id)
+        .into_boxed()
+}
+
+fn recipe_facets(
+    c: &mut PgConnection,
+    filter: &RecipeFilter,
+    user_id: Option<i32>,
+) -> QueryResult<RecipeFacets> {
+    let tag_counts = recipes_tags::table
+        .inner_join(tags::table)
+        .filter(
+            recipes_tags::recipe_id
+                .eq_any(filtered_recipes(filter, user_id, true).select(recipes::id)),
+        )
+        .group_by((tags::id, tags::label, tags::slug))
+        .select((tags::label, tags::slug, count_star()))
+        .order((count_star().desc(), tags::label.asc()))
+        .load::<(String, String, i64)>(c)?;
+
+    let mut timers = Vec::<TimerFacet>::new();
+    for (min, max) in TIMER_BUCKETS {
+        let mut query = filtered_recipes(filter, user_id, false).filter(recipes::timer.ge(min));
+        if let Some(max) = max {
+            query = query.filter(recipes::timer.lt(max));
+        }
+        let count = query.count().get_result::<i64>(c)?;
+        timers.push(TimerFacet { min, max, count });
+    }
+
+    Ok(RecipeFacets {
+        tags: tag_counts
+            .into_iter()
+            .map(|(label, slug, count)| TagFacet { label, slug, count })
+            .collect::<Vec<TagFacet>>(),
+        timers,
+    })
+}
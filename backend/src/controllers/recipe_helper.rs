@@ -1,125 +1,480 @@
+use diesel::dsl::{count_star, sql};
+use diesel::expression::SqlLiteral;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Float, Integer, Nullable, Text};
+use std::{env, fmt};
 
-use crate::{models::*, schema::*, LogsDbConn};
+use crate::nutrition::{compute_nutrition, NutritionLine};
+use crate::{models::*, schema::*, units::UnitSystem, LogsDbConn};
+
+/// Error of a step inside a database transaction.
+///
+/// Returning it from a `transaction` closure rolls back every previous step,
+/// the message tells which step failed.
+#[derive(Debug)]
+pub struct TransactionError(pub String);
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for TransactionError {
+    fn from(message: &str) -> Self {
+        Self(String::from(message))
+    }
+}
+
+impl From<diesel::result::Error> for TransactionError {
+    fn from(err: diesel::result::Error) -> Self {
+        Self(format!("Database error: {}", err))
+    }
+}
 
 pub async fn get_recipe_elements(
     recipes_list: Vec<Recipe>,
-    conn: LogsDbConn,
+    conn: &LogsDbConn,
     user_id: Option<i32>,
 ) -> Result<Vec<RecipeResultDTO>, String> {
-    let recipe_results = conn
-        .run(move |c| {
-            // get instructions
-            let instructions_list: Vec<Instruction> = match Instruction::belonging_to(&recipes_list)
-                .order(instructions::display_order.asc())
-                .load::<Instruction>(c)
-            {
-                Ok(res) => res,
-                Err(_) => return Err(String::from("Cannot read instructions from the database.")),
-            };
+    conn.run(move |c| load_recipe_elements(c, recipes_list, user_id))
+        .await
+}
 
-            // get ingredients
-            let ingredients_list: Vec<(RecipeIngredient, Ingredient)> =
-                match RecipeIngredient::belonging_to(&recipes_list)
-                    .inner_join(ingredients::table)
-                    .load::<(RecipeIngredient, Ingredient)>(c)
-                {
-                    Ok(res) => res,
-                    Err(_) => {
-                        return Err(String::from("Cannot read ingredients from the database."))
-                    }
-                };
-            let ingredients_grouped = ingredients_list.grouped_by(&recipes_list);
-
-            // get tags
-            let tags_list: Vec<(RecipeTag, Tag)> = match RecipeTag::belonging_to(&recipes_list)
-                .inner_join(tags::table)
-                .load::<(RecipeTag, Tag)>(c)
-            {
-                Ok(res) => res,
-                Err(_) => return Err(String::from("Cannot read tags from the database.")),
-            };
-            let tags_grouped = tags_list.grouped_by(&recipes_list);
-
-            // if user is logged in, get bookmarks
-            let mut bookmarks_grouped = Vec::<Vec<Bookmark>>::new();
-            let mut owned_grouped = Vec::<Vec<RecipeUser>>::new();
-            if let Some(id) = user_id {
-                // TODO add ownership
-                let bookmarks_list: Vec<Bookmark> = match Bookmark::belonging_to(&recipes_list)
-                    .filter(bookmarks::user_id.eq(id))
-                    .load::<Bookmark>(c)
-                {
-                    Ok(res) => res,
-                    Err(_) => return Err(String::from("Cannot read bookmarks from the database.")),
-                };
-                bookmarks_grouped = bookmarks_list.grouped_by(&recipes_list);
-                let owned_list: Vec<RecipeUser> = match RecipeUser::belonging_to(&recipes_list)
-                    .filter(recipes_users::user_id.eq(id))
-                    .load::<RecipeUser>(c)
-                {
-                    Ok(res) => res,
-                    Err(_) => return Err(String::from("Cannot read ownership from the database.")),
-                };
-                owned_grouped = owned_list.grouped_by(&recipes_list);
-            }
+/// Full recipes with instructions, ingredients, tags, nutrition, ratings and comment counts.
+/// Bookmark and role are only filled in for a logged in user.
+/// Private recipes of other users are left out, whichever query loaded them.
+pub fn load_recipe_elements(
+    c: &mut PgConnection,
+    recipes_list: Vec<Recipe>,
+    user_id: Option<i32>,
+) -> Result<Vec<RecipeResultDTO>, String> {
+    let recipes_list = match visible_only(c, recipes_list, user_id) {
+        Ok(res) => res,
+        Err(_) => return Err(String::from("Cannot read ownership from the database.")),
+    };
 
-            let mut recipe_results = instructions_list
-                .grouped_by(&recipes_list)
+    // get instructions
+    let instructions_list: Vec<Instruction> = match Instruction::belonging_to(&recipes_list)
+        .order(instructions::display_order.asc())
+        .load::<Instruction>(c)
+    {
+        Ok(res) => res,
+        Err(_) => return Err(String::from("Cannot read instructions from the database.")),
+    };
+
+    // get ingredients
+    let ingredients_list: Vec<(RecipeIngredient, Ingredient)> =
+        match RecipeIngredient::belonging_to(&recipes_list)
+            .inner_join(ingredients::table)
+            .load::<(RecipeIngredient, Ingredient)>(c)
+        {
+            Ok(res) => res,
+            Err(_) => return Err(String::from("Cannot read ingredients from the database.")),
+        };
+    let ingredients_grouped = ingredients_list.grouped_by(&recipes_list);
+
+    // get tags
+    let tags_list: Vec<(RecipeTag, Tag)> = match RecipeTag::belonging_to(&recipes_list)
+        .inner_join(tags::table)
+        .load::<(RecipeTag, Tag)>(c)
+    {
+        Ok(res) => res,
+        Err(_) => return Err(String::from("Cannot read tags from the database.")),
+    };
+    let tags_grouped = tags_list.grouped_by(&recipes_list);
+
+    // if user is logged in, get bookmarks
+    let mut bookmarks_grouped = Vec::<Vec<Bookmark>>::new();
+    let mut owned_grouped = Vec::<Vec<RecipeUser>>::new();
+    if let Some(id) = user_id {
+        // TODO add ownership
+        let bookmarks_list: Vec<Bookmark> = match Bookmark::belonging_to(&recipes_list)
+            .filter(bookmarks::user_id.eq(id))
+            .load::<Bookmark>(c)
+        {
+            Ok(res) => res,
+            Err(_) => return Err(String::from("Cannot read bookmarks from the database.")),
+        };
+        bookmarks_grouped = bookmarks_list.grouped_by(&recipes_list);
+        let owned_list: Vec<RecipeUser> = match RecipeUser::belonging_to(&recipes_list)
+            .filter(recipes_users::user_id.eq(id))
+            .load::<RecipeUser>(c)
+        {
+            Ok(res) => res,
+            Err(_) => return Err(String::from("Cannot read ownership from the database.")),
+        };
+        owned_grouped = owned_list.grouped_by(&recipes_list);
+    }
+
+    let mut recipe_results = instructions_list
+        .grouped_by(&recipes_list)
+        .into_iter()
+        .zip(recipes_list)
+        .zip(ingredients_grouped)
+        .zip(tags_grouped)
+        .map(|(((instruction, recipe), ingredient), tag)| {
+            let mut rec = RecipeResultDTO::from(recipe);
+            rec.instructions = instruction
+                .into_iter()
+                .map(|v| v.instruction)
+                .collect::<Vec<String>>();
+            rec.ingredients = ingredient
                 .into_iter()
-                .zip(recipes_list)
-                .zip(ingredients_grouped)
-                .zip(tags_grouped)
-                .map(|(((instruction, recipe), ingredient), tag)| {
-                    let mut rec = RecipeResultDTO::from(recipe);
-                    rec.instructions = instruction
-                        .into_iter()
-                        .map(|v| v.instruction)
-                        .collect::<Vec<String>>();
-                    rec.ingredients = ingredient
-                        .into_iter()
-                        .map(|(ri, i)| IngredientDTO {
-                            unit: i.unit,
-                            label: i.label,
-                            amount: ri.amount,
-                        })
-                        .collect::<Vec<IngredientDTO>>();
-                    rec.tags = tag
-                        .into_iter()
-                        .map(|(_rt, t)| TagDTO::from(t))
-                        .collect::<Vec<TagDTO>>();
-                    rec
+                .map(|(ri, i)| IngredientDTO {
+                    unit: ri.unit,
+                    label: i.label,
+                    amount: ri.amount,
                 })
-                .collect::<Vec<RecipeResultDTO>>();
-
-            if user_id.is_some() {
-                recipe_results = recipe_results
-                    .into_iter()
-                    .zip(bookmarks_grouped)
-                    .zip(owned_grouped)
-                    .map(|((r, b), o)| {
-                        let mut recipe = r;
-                        recipe.bookmarked = if b.is_empty() {
-                            Some(false)
-                        } else {
-                            Some(true)
-                        };
-                        recipe.owned = if o.is_empty() {
-                            Some(false)
-                        } else {
-                            Some(true)
-                        };
-                        recipe
-                    })
-                    .collect::<Vec<RecipeResultDTO>>();
-            }
-            Ok::<_, String>(recipe_results)
+                .collect::<Vec<IngredientDTO>>();
+            rec.tags = tag
+                .into_iter()
+                .map(|(_rt, t)| TagDTO::from(t))
+                .collect::<Vec<TagDTO>>();
+            rec
         })
-        .await?;
+        .collect::<Vec<RecipeResultDTO>>();
 
+    if user_id.is_some() {
+        recipe_results = recipe_results
+            .into_iter()
+            .zip(bookmarks_grouped)
+            .zip(owned_grouped)
+            .map(|((r, b), o)| {
+                let mut recipe = r;
+                recipe.bookmarked = if b.is_empty() {
+                    Some(false)
+                } else {
+                    Some(true)
+                };
+                recipe.role = o.first().and_then(|ru| RecipeRole::parse(&ru.role));
+                recipe
+            })
+            .collect::<Vec<RecipeResultDTO>>();
+    }
+
+    if add_nutrition(c, &mut recipe_results).is_err() {
+        return Err(String::from("Cannot read nutrients from the database."));
+    }
+    if add_ratings(c, &mut recipe_results, user_id).is_err() {
+        return Err(String::from("Cannot read ratings from the database."));
+    }
+    if add_comment_counts(c, &mut recipe_results).is_err() {
+        return Err(String::from("Cannot read comments from the database."));
+    }
+    if add_fork_counts(c, &mut recipe_results, user_id).is_err() {
+        return Err(String::from("Cannot read forks from the database."));
+    }
     Ok(recipe_results)
 }
 
+/// The recipes without the private ones the user does not own
+fn visible_only(
+    c: &mut PgConnection,
+    recipes_list: Vec<Recipe>,
+    user_id: Option<i32>,
+) -> QueryResult<Vec<Recipe>> {
+    let private = Visibility::Private.as_str();
+    let private_ids = recipes_list
+        .iter()
+        .filter(|r| r.visibility == private)
+        .map(|r| r.id)
+        .collect::<Vec<i32>>();
+    if private_ids.is_empty() {
+        return Ok(recipes_list);
+    }
+    let owned = match user_id {
+        Some(id) => recipes_users::table
+            .filter(recipes_users::recipe_id.eq_any(private_ids))
+            .filter(recipes_users::user_id.eq(id))
+            .select(recipes_users::recipe_id)
+            .load::<i32>(c)?,
+        None => Vec::new(),
+    };
+    Ok(recipes_list
+        .into_iter()
+        .filter(|r| r.visibility != private || owned.contains(&r.id))
+        .collect())
+}
+
+/// Ingredient line of a recipe: recipe id, label, amount, unit and nutrient data of the ingredient
+type NutritionRow = (
+    i32,
+    String,
+    Option<f32>,
+    Option<String>,
+    Option<IngredientNutrient>,
+);
+
+fn nutrition_rows(c: &mut PgConnection, recipe_ids: &[i32]) -> QueryResult<Vec<NutritionRow>> {
+    recipe_ingredients::table
+        .inner_join(ingredients::table.left_join(ingredient_nutrients::table))
+        .filter(recipe_ingredients::recipe_id.eq_any(recipe_ids))
+        .select((
+            recipe_ingredients::recipe_id,
+            ingredients::label,
+            recipe_ingredients::amount,
+            recipe_ingredients::unit,
+            ingredient_nutrients::all_columns.nullable(),
+        ))
+        .load::<NutritionRow>(c)
+}
+
+fn nutrition_lines(rows: &[NutritionRow], recipe_id: i32) -> Vec<NutritionLine<'_>> {
+    rows.iter()
+        .filter(|(id, ..)| *id == recipe_id)
+        .map(|(_, label, amount, unit, nutrient)| NutritionLine {
+            label,
+            amount: *amount,
+            unit: unit.as_deref(),
+            nutrient: nutrient.as_ref(),
+        })
+        .collect()
+}
+
+/// Nutrition of the recipes, computed from their ingredients, see `compute_nutrition`
+pub fn add_nutrition(c: &mut PgConnection, recipes: &mut [RecipeResultDTO]) -> QueryResult<()> {
+    let recipe_ids = recipes.iter().map(|r| r.id).collect::<Vec<i32>>();
+    let rows = nutrition_rows(c, &recipe_ids)?;
+
+    for recipe in recipes.iter_mut() {
+        let manual = NutritionValues {
+            kcal: recipe.kcal.map(f32::from),
+            carbs: recipe.carbs.map(f32::from),
+            proteins: recipe.proteins.map(f32::from),
+            fats: recipe.fats.map(f32::from),
+        };
+        recipe.nutrition = Some(compute_nutrition(
+            &nutrition_lines(&rows, recipe.id),
+            recipe.servings,
+            manual,
+        ));
+    }
+    Ok(())
+}
+
+/// Store the nutrition computed from the ingredients of the recipes, listings sort and filter by it.
+/// Needs to run after every write to the recipes' ingredients and after nutrient data changes.
+pub fn refresh_nutrition(c: &mut PgConnection, recipe_ids: &[i32]) -> QueryResult<usize> {
+    let rows = nutrition_rows(c, recipe_ids)?;
+    let values = recipe_ids
+        .iter()
+        .map(|id| {
+            let computed =
+                compute_nutrition(&nutrition_lines(&rows, *id), 1, NutritionValues::default())
+                    .computed;
+            (
+                recipe_nutrition::recipe_id.eq(*id),
+                recipe_nutrition::kcal.eq(computed.kcal),
+                recipe_nutrition::carbs.eq(computed.carbs),
+                recipe_nutrition::proteins.eq(computed.proteins),
+                recipe_nutrition::fats.eq(computed.fats),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    diesel::delete(recipe_nutrition::table.filter(recipe_nutrition::recipe_id.eq_any(recipe_ids)))
+        .execute(c)?;
+    diesel::insert_into(recipe_nutrition::table)
+        .values(values)
+        .execute(c)
+}
+
+/// Average rating and number of ratings of the recipes, and the stars given by the user
+pub fn add_ratings(
+    c: &mut PgConnection,
+    recipes: &mut [RecipeResultDTO],
+    user_id: Option<i32>,
+) -> QueryResult<()> {
+    let recipe_ids = recipes.iter().map(|r| r.id).collect::<Vec<i32>>();
+    let totals = ratings::table
+        .filter(ratings::recipe_id.eq_any(&recipe_ids))
+        .group_by(ratings::recipe_id)
+        .select((
+            ratings::recipe_id,
+            sql::<Nullable<Float>>("AVG(ratings.stars)::real"),
+            count_star(),
+        ))
+        .load::<(i32, Option<f32>, i64)>(c)?;
+    let own = match user_id {
+        Some(id) => ratings::table
+            .filter(ratings::recipe_id.eq_any(&recipe_ids))
+            .filter(ratings::user_id.eq(id))
+            .select((ratings::recipe_id, ratings::stars))
+            .load::<(i32, i16)>(c)?,
+        None => Vec::new(),
+    };
+
+    for recipe in recipes.iter_mut() {
+        if let Some((_, average, count)) = totals.iter().find(|(id, ..)| *id == recipe.id) {
+            recipe.average_rating = *average;
+            recipe.rating_count = *count;
+        }
+        recipe.my_rating = own
+            .iter()
+            .find(|(id, _)| *id == recipe.id)
+            .map(|(_, stars)| *stars);
+    }
+    Ok(())
+}
+
+/// Number of comments of the recipes, replies included
+pub fn add_comment_counts(
+    c: &mut PgConnection,
+    recipes: &mut [RecipeResultDTO],
+) -> QueryResult<()> {
+    let recipe_ids = recipes.iter().map(|r| r.id).collect::<Vec<i32>>();
+    let counts = comments::table
+        .filter(comments::recipe_id.eq_any(recipe_ids))
+        .group_by(comments::recipe_id)
+        .select((comments::recipe_id, count_star()))
+        .load::<(i32, i64)>(c)?;
+
+    for recipe in recipes.iter_mut() {
+        recipe.comment_count = counts
+            .iter()
+            .find(|(id, _)| *id == recipe.id)
+            .map_or(0, |(_, count)| *count);
+    }
+    Ok(())
+}
+
+/// Number of forks of the recipes the user gets listed, forks in the trash are not counted
+pub fn add_fork_counts(
+    c: &mut PgConnection,
+    recipes: &mut [RecipeResultDTO],
+    user_id: Option<i32>,
+) -> QueryResult<()> {
+    let recipe_ids = recipes
+        .iter()
+        .map(|r| Some(r.id))
+        .collect::<Vec<Option<i32>>>();
+    let counts = recipes::table
+        .filter(recipes::forked_from.eq_any(recipe_ids))
+        .filter(recipes::deleted_at.is_null())
+        .filter(listed_recipes(user_id))
+        .group_by(recipes::forked_from)
+        .select((recipes::forked_from, count_star()))
+        .load::<(Option<i32>, i64)>(c)?;
+
+    for recipe in recipes.iter_mut() {
+        recipe.fork_count = counts
+            .iter()
+            .find(|(id, _)| *id == Some(recipe.id))
+            .map_or(0, |(_, count)| *count);
+    }
+    Ok(())
+}
+
+/// Save the recipe as its next revision. Fields that depend on who is asking are left out.
+pub fn record_revision(
+    c: &mut PgConnection,
+    recipe: &RecipeResultDTO,
+    user_id: Option<i32>,
+) -> QueryResult<i32> {
+    let mut snapshot = recipe.clone();
+    snapshot.bookmarked = None;
+    snapshot.role = None;
+    snapshot.my_rating = None;
+    snapshot.snippet = None;
+    let snapshot = serde_json::to_value(snapshot)
+        .map_err(|err| diesel::result::Error::SerializationError(Box::new(err)))?;
+
+    let latest = recipe_revisions::table
+        .filter(recipe_revisions::recipe_id.eq(recipe.id))
+        .select(diesel::dsl::max(recipe_revisions::revision))
+        .first::<Option<i32>>(c)?;
+    diesel::insert_into(recipe_revisions::table)
+        .values(RecipeRevisionInsert {
+            recipe_id: recipe.id,
+            revision: latest.unwrap_or(0) + 1,
+            user_id,
+            snapshot,
+        })
+        .returning(recipe_revisions::revision)
+        .get_result::<i32>(c)
+}
+
+/// Whether the user can read, rate and comment on the recipe: it exists, is not in the trash
+/// and is visible to them
+pub fn readable_recipe(
+    c: &mut PgConnection,
+    recipe_id: i32,
+    user_id: Option<i32>,
+) -> QueryResult<bool> {
+    recipes::table
+        .find(recipe_id)
+        .filter(recipes::deleted_at.is_null())
+        .filter(visible_recipes(user_id))
+        .select(recipes::id)
+        .first::<i32>(c)
+        .optional()
+        .map(|r| r.is_some())
+}
+
+/// Role of the user on the recipe, `None` if they are not one of its collaborators
+pub fn recipe_role(
+    c: &mut PgConnection,
+    recipe_id: i32,
+    user_id: i32,
+) -> QueryResult<Option<RecipeRole>> {
+    recipes_users::table
+        .find((recipe_id, user_id))
+        .select(recipes_users::role)
+        .first::<String>(c)
+        .optional()
+        .map(|r| r.and_then(|r| RecipeRole::parse(&r)))
+}
+
+/// Recipes the user can open: public and unlisted ones, private ones only if they own them
+pub fn visible_recipes(
+    user_id: Option<i32>,
+) -> Box<dyn BoxableExpression<recipes::table, Pg, SqlType = Bool>> {
+    let shared = recipes::visibility.ne(Visibility::Private.as_str());
+    match user_id {
+        Some(id) => Box::new(shared.or(recipes::id.eq_any(owned_recipe_ids(id)))),
+        None => Box::new(shared),
+    }
+}
+
+/// Ids of the recipes out of the trash that the user can open, for subqueries
+pub fn visible_recipe_ids(user_id: Option<i32>) -> recipes::BoxedQuery<'static, Pg, Integer> {
+    recipes::table
+        .select(recipes::id)
+        .filter(recipes::deleted_at.is_null())
+        .into_boxed()
+        .filter(visible_recipes(user_id))
+}
+
+/// Recipes that show up in listings, search and counts for the user: public ones,
+/// and their own whatever the visibility
+pub fn listed_recipes(
+    user_id: Option<i32>,
+) -> Box<dyn BoxableExpression<recipes::table, Pg, SqlType = Bool>> {
+    let public = recipes::visibility.eq(Visibility::Public.as_str());
+    match user_id {
+        Some(id) => Box::new(public.or(recipes::id.eq_any(owned_recipe_ids(id)))),
+        None => Box::new(public),
+    }
+}
+
+/// `listed_recipes` for raw SQL, with the user id bound as the `param`th parameter
+pub fn listed_recipes_sql(param: usize) -> String {
+    format!(
+        "(recipes.visibility = 'public' OR recipes.id IN \
+        (SELECT recipe_id FROM recipes_users WHERE user_id = ${}))",
+        param
+    )
+}
+
+fn owned_recipe_ids(user_id: i32) -> recipes_users::BoxedQuery<'static, Pg, Integer> {
+    recipes_users::table
+        .filter(recipes_users::user_id.eq(user_id))
+        .select(recipes_users::recipe_id)
+        .into_boxed()
+}
+
 pub fn pagination(page: Option<i64>, per_page: Option<i64>, total: i64) -> (i64, i64, i64) {
     let page_number = page.unwrap_or(1);
     let elements_per_page = per_page.unwrap_or(10);
@@ -140,3 +495,485 @@ pub fn pagination(page: Option<i64>, per_page: Option<i64>, total: i64) -> (i64,
 
     (current_page, per_page, offset)
 }
+
+/// Text search configuration used for stemming, e.g. "english" or "simple".
+pub fn search_config() -> String {
+    env::var("SEARCH_LANGUAGE").unwrap_or_else(|_| String::from("english"))
+}
+
+/// Rebuild the full-text search document of a recipe.
+/// Needs to run after every write to the recipe's title, instructions, ingredients or tags.
+pub fn refresh_search_document(c: &mut PgConnection, recipe_id: i32) -> QueryResult<usize> {
+    diesel::sql_query("SELECT recipe_search_refresh($1, $2::regconfig)")
+        .bind::<Integer, _>(recipe_id)
+        .bind::<Text, _>(search_config())
+        .execute(c)
+}
+
+/// ORDER BY expression for recipe listings, see `RecipeSort::order_clause`
+pub fn recipe_order(sort: RecipeSort, direction: Option<SortDirection>) -> SqlLiteral<Bool> {
+    sql::<Bool>(&sort.order_clause(direction))
+}
+
+/// How a recipe listing is paged: by page number, or by cursor when one is given.
+pub struct Listing {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub sort: RecipeSort,
+    pub direction: SortDirection,
+    pub cursor: Option<RecipeCursor>,
+    /// count the total in cursor mode too
+    pub with_total: bool,
+}
+
+impl Listing {
+    /// Checks the query parameters, a cursor overrides `sort` and `direction`.
+    pub fn new(
+        page: Option<i64>,
+        per_page: Option<i64>,
+        sort: Option<RecipeSort>,
+        direction: Option<SortDirection>,
+        cursor: Option<String>,
+        with_total: Option<bool>,
+    ) -> Result<Self, String> {
+        let listing = Self::sorted_by(
+            page,
+            per_page,
+            sort.unwrap_or(RecipeSort::UpdatedAt),
+            direction,
+            cursor,
+            with_total,
+        )?;
+        if listing.sort == RecipeSort::Relevance {
+            return Err(String::from(
+                "Sorting by relevance is only available when searching.",
+            ));
+        }
+        Ok(listing)
+    }
+
+    /// Like `new` for full-text search results, sorted by relevance unless asked otherwise.
+    pub fn search(
+        page: Option<i64>,
+        per_page: Option<i64>,
+        sort: Option<RecipeSort>,
+        direction: Option<SortDirection>,
+        cursor: Option<String>,
+        with_total: Option<bool>,
+    ) -> Result<Self, String> {
+        Self::sorted_by(
+            page,
+            per_page,
+            sort.unwrap_or(RecipeSort::Relevance),
+            direction,
+            cursor,
+            with_total,
+        )
+    }
+
+    fn sorted_by(
+        page: Option<i64>,
+        per_page: Option<i64>,
+        sort: RecipeSort,
+        direction: Option<SortDirection>,
+        cursor: Option<String>,
+        with_total: Option<bool>,
+    ) -> Result<Self, String> {
+        let cursor = match cursor {
+            Some(c) => match RecipeCursor::decode(&c) {
+                Some(c) => Some(c),
+                None => return Err(String::from("Invalid cursor.")),
+            },
+            None => None,
+        };
+        let sort = match &cursor {
+            Some(c) => c.sort,
+            None => sort,
+        };
+        let direction = match &cursor {
+            Some(c) => c.direction,
+            None => direction.unwrap_or(sort.default_direction()),
+        };
+
+        Ok(Listing {
+            page,
+            per_page,
+            sort,
+            direction,
+            cursor,
+            with_total: with_total.unwrap_or(false),
+        })
+    }
+}
+
+/// One page of recipes, `records` still need their elements loaded
+pub struct RecipePage {
+    pub records: Vec<Recipe>,
+    pub total: Option<i64>,
+    pub current_page: Option<i64>,
+    pub per_page: i64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl RecipePage {
+    pub fn into_result(self, records: Vec<RecipeResultDTO>) -> PaginatedResult<RecipeResultDTO> {
+        PaginatedResult {
+            records,
+            total: self.total,
+            current_page: self.current_page,
+            per_page: self.per_page,
+            next_cursor: self.next_cursor,
+            prev_cursor: self.prev_cursor,
+        }
+    }
+}
+
+/// Load one page of the recipes selected by `query`.
+///
+/// Without a cursor the page number is used with OFFSET, with a cursor the records
+/// are selected by comparing the sort key and id, so pages don't drift on inserts.
+/// Both modes hand out cursors to the neighbouring pages.
+pub fn load_recipe_page<F>(
+    c: &mut PgConnection,
+    query: F,
+    listing: &Listing,
+) -> QueryResult<RecipePage>
+where
+    F: Fn() -> recipes::BoxedQuery<'static, Pg>,
+{
+    let sort = listing.sort;
+    let direction = listing.direction;
+
+    let cursor = match &listing.cursor {
+        Some(cursor) => cursor,
+        None => {
+            let total = query().count().get_result::<i64>(c)?;
+            let (current_page, per_page, offset) =
+                pagination(listing.page, listing.per_page, total);
+            let records = query()
+                .order(recipe_order(sort, Some(direction)))
+                .offset(offset)
+                .limit(per_page)
+                .load::<Recipe>(c)?;
+
+            let next_cursor = match records.last() {
+                Some(r) if offset + per_page < total => {
+                    Some(recipe_cursor(c, sort, direction, r.id, false)?)
+                }
+                _ => None,
+            };
+            let prev_cursor = match records.first() {
+                Some(r) if current_page > 1 => Some(recipe_cursor(c, sort, direction, r.id, true)?),
+                _ => None,
+            };
+
+            return Ok(RecipePage {
+                records,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor,
+                prev_cursor,
+            });
+        }
+    };
+
+    let per_page = match listing.per_page {
+        Some(p) if p >= 1 => p,
+        _ => 10,
+    };
+    let order = if cursor.backward {
+        sort.reverse_order_clause(direction)
+    } else {
+        sort.order_clause(Some(direction))
+    };
+    // one more than needed, to know if there is another page
+    let mut records = query()
+        .filter(cursor_condition(cursor))
+        .order(sql::<Bool>(&order))
+        .limit(per_page + 1)
+        .load::<Recipe>(c)?;
+    let has_more = records.len() as i64 > per_page;
+    records.truncate(per_page as usize);
+    if cursor.backward {
+        records.reverse();
+    }
+
+    let (next_cursor, prev_cursor) = match (records.first(), records.last()) {
+        (Some(first), Some(last)) => {
+            let has_next = has_more || cursor.backward;
+            let has_prev = has_more || !cursor.backward;
+            (
+                match has_next {
+                    true => Some(recipe_cursor(c, sort, direction, last.id, false)?),
+                    false => None,
+                },
+                match has_prev {
+                    true => Some(recipe_cursor(c, sort, direction, first.id, true)?),
+                    false => None,
+                },
+            )
+        }
+        _ => (None, None),
+    };
+
+    let total = match listing.with_total {
+        true => Some(query().count().get_result::<i64>(c)?),
+        false => None,
+    };
+
+    Ok(RecipePage {
+        records,
+        total,
+        current_page: None,
+        per_page,
+        next_cursor,
+        prev_cursor,
+    })
+}
+
+/// Load one page of the full-text search results for `text`, paged like `load_recipe_page`.
+/// The hits are returned too, they carry the highlighted snippets.
+pub fn load_search_page(
+    c: &mut PgConnection,
+    text: &str,
+    user_id: Option<i32>,
+    listing: &Listing,
+) -> QueryResult<(RecipePage, Vec<RecipeSearchHit>)> {
+    let config = search_config();
+    let sort = listing.sort;
+    let direction = listing.direction;
+
+    // matching recipes with their rank, named `recipes` so the sort expressions apply to them
+    let matches = format!(
+        "(SELECT recipes.*, ts_rank(d.document, q) AS rank, d.content, q \
+        FROM recipes \
+        JOIN recipe_search_documents d ON d.recipe_id = recipes.id \
+        CROSS JOIN websearch_to_tsquery($1::regconfig, $2) q \
+        WHERE recipes.deleted_at IS NULL AND {} AND d.document @@ q) recipes",
+        listed_recipes_sql(3)
+    );
+    let select_hits = format!(
+        "SELECT recipes.id, ({})::text AS key, \
+        ts_headline($1::regconfig, recipes.content, recipes.q, 'MaxFragments=2, MaxWords=20, MinWords=5') AS snippet \
+        FROM {}",
+        sort.expression(),
+        matches
+    );
+    let count = |c: &mut PgConnection| {
+        diesel::sql_query(format!("SELECT COUNT(*) AS count FROM {}", matches))
+            .bind::<Text, _>(&config)
+            .bind::<Text, _>(text)
+            .bind::<Nullable<Integer>, _>(user_id)
+            .get_result::<RowCount>(c)
+            .map(|r| r.count)
+    };
+
+    let (hits, total, current_page, per_page, has_next, has_prev) = match &listing.cursor {
+        None => {
+            let total = count(c)?;
+            let (current_page, per_page, offset) =
+                pagination(listing.page, listing.per_page, total);
+            let hits = diesel::sql_query(format!(
+                "{} ORDER BY {} OFFSET $4 LIMIT $5",
+                select_hits,
+                sort.order_clause(Some(direction))
+            ))
+            .bind::<Text, _>(&config)
+            .bind::<Text, _>(text)
+            .bind::<Nullable<Integer>, _>(user_id)
+            .bind::<BigInt, _>(offset)
+            .bind::<BigInt, _>(per_page)
+            .load::<RecipeSearchHit>(c)?;
+            (
+                hits,
+                Some(total),
+                Some(current_page),
+                per_page,
+                offset + per_page < total,
+                current_page > 1,
+            )
+        }
+        Some(cursor) => {
+            let per_page = match listing.per_page {
+                Some(p) if p >= 1 => p,
+                _ => 10,
+            };
+            let order = if cursor.backward {
+                sort.reverse_order_clause(direction)
+            } else {
+                sort.order_clause(Some(direction))
+            };
+            let limit_param = if cursor.key.is_some() { 6 } else { 5 };
+            let mut query = diesel::sql_query(format!(
+                "{} WHERE {} ORDER BY {} LIMIT ${}",
+                select_hits,
+                cursor_condition_sql(cursor, 4),
+                order,
+                limit_param
+            ))
+            .into_boxed()
+            .bind::<Text, _>(config.clone())
+            .bind::<Text, _>(String::from(text))
+            .bind::<Nullable<Integer>, _>(user_id);
+            if let Some(key) = &cursor.key {
+                query = query.bind::<Text, _>(key.clone());
+            }
+            // one more than needed, to know if there is another page
+            let mut hits = query
+                .bind::<Integer, _>(cursor.id)
+                .bind::<BigInt, _>(per_page + 1)
+                .load::<RecipeSearchHit>(c)?;
+            let has_more = hits.len() as i64 > per_page;
+            hits.truncate(per_page as usize);
+            if cursor.backward {
+                hits.reverse();
+            }
+            let total = match listing.with_total {
+                true => Some(count(c)?),
+                false => None,
+            };
+            (
+                hits,
+                total,
+                None,
+                per_page,
+                has_more || cursor.backward,
+                has_more || !cursor.backward,
+            )
+        }
+    };
+
+    let mut records = recipes::table
+        .filter(recipes::id.eq_any(hits.iter().map(|h| h.id).collect::<Vec<i32>>()))
+        .load::<Recipe>(c)?;
+    // keep the order of the hits
+    records.sort_by_key(|r| hits.iter().position(|h| h.id == r.id));
+
+    let hit_cursor = |hit: &RecipeSearchHit, backward: bool| {
+        RecipeCursor {
+            sort,
+            direction,
+            key: hit.key.clone(),
+            id: hit.id,
+            backward,
+        }
+        .encode()
+    };
+    let next_cursor = hits
+        .last()
+        .filter(|_| has_next)
+        .map(|h| hit_cursor(h, false));
+    let prev_cursor = hits
+        .first()
+        .filter(|_| has_prev)
+        .map(|h| hit_cursor(h, true));
+
+    Ok((
+        RecipePage {
+            records,
+            total,
+            current_page,
+            per_page,
+            next_cursor,
+            prev_cursor,
+        },
+        hits,
+    ))
+}
+
+/// Encoded cursor pointing at the recipe, the sort key is read back from the database
+fn recipe_cursor(
+    c: &mut PgConnection,
+    sort: RecipeSort,
+    direction: SortDirection,
+    recipe_id: i32,
+    backward: bool,
+) -> QueryResult<String> {
+    let key = diesel::sql_query(format!(
+        "SELECT ({})::text AS key FROM recipes WHERE recipes.id = $1",
+        sort.expression()
+    ))
+    .bind::<Integer, _>(recipe_id)
+    .get_result::<SortKey>(c)?
+    .key;
+
+    Ok(RecipeCursor {
+        sort,
+        direction,
+        key,
+        id: recipe_id,
+        backward,
+    }
+    .encode())
+}
+
+/// Records after the cursor in sort order, or before it when walking backwards.
+/// NULL keys sort last, which the conditions have to follow.
+fn cursor_condition(
+    cursor: &RecipeCursor,
+) -> Box<dyn BoxableExpression<recipes::table, Pg, SqlType = Bool>> {
+    let expr = cursor.sort.expression();
+    let ascending = (cursor.direction == SortDirection::Asc) != cursor.backward;
+    let op = if ascending { ">" } else { "<" };
+
+    match (&cursor.key, cursor.backward) {
+        (Some(key), false) => Box::new(
+            sql::<Bool>(&format!(
+                "({expr} IS NULL OR ({expr}, recipes.id) {op} (CAST("
+            ))
+            .bind::<Text, _>(key.clone())
+            .sql(&format!(" AS {}), ", cursor.sort.key_type()))
+            .bind::<Integer, _>(cursor.id)
+            .sql("))"),
+        ),
+        (Some(key), true) => Box::new(
+            sql::<Bool>(&format!("(({expr}, recipes.id) {op} (CAST("))
+                .bind::<Text, _>(key.clone())
+                .sql(&format!(" AS {}), ", cursor.sort.key_type()))
+                .bind::<Integer, _>(cursor.id)
+                .sql("))"),
+        ),
+        (None, false) => Box::new(
+            sql::<Bool>(&format!("({expr} IS NULL AND recipes.id {op} "))
+                .bind::<Integer, _>(cursor.id)
+                .sql(")"),
+        ),
+        (None, true) => Box::new(
+            sql::<Bool>(&format!("({expr} IS NOT NULL OR recipes.id {op} "))
+                .bind::<Integer, _>(cursor.id)
+                .sql(")"),
+        ),
+    }
+}
+
+/// `cursor_condition` for raw SQL, with the key and the id bound as the `param`th and following
+/// parameters. Without a key only the id is bound.
+pub fn cursor_condition_sql(cursor: &RecipeCursor, param: usize) -> String {
+    let expr = cursor.sort.expression();
+    let ascending = (cursor.direction == SortDirection::Asc) != cursor.backward;
+    let op = if ascending { ">" } else { "<" };
+    let key = format!("CAST(${} AS {})", param, cursor.sort.key_type());
+
+    match (&cursor.key, cursor.backward) {
+        (Some(_), false) => format!(
+            "({expr} IS NULL OR ({expr}, recipes.id) {op} ({key}, ${}))",
+            param + 1
+        ),
+        (Some(_), true) => format!("(({expr}, recipes.id) {op} ({key}, ${}))", param + 1),
+        (None, false) => format!("({expr} IS NULL AND recipes.id {op} ${param})"),
+        (None, true) => format!("({expr} IS NOT NULL OR recipes.id {op} ${param})"),
+    }
+}
+
+/// Convert the ingredient amounts of every recipe, if a measurement system was asked for
+pub fn convert_units(
+    records: Vec<RecipeResultDTO>,
+    units: Option<UnitSystem>,
+) -> Vec<RecipeResultDTO> {
+    match units {
+        Some(system) => records.into_iter().map(|r| r.converted(system)).collect(),
+        None => records,
+    }
+}
@@ -1,23 +1,31 @@
 use diesel::prelude::*;
-use rocket::http::Status;
 use rocket::serde::json::Json;
+use validator::Validate;
 
 use crate::models::*;
 use crate::schema::recipes::dsl::*;
 use crate::schema::*;
 use crate::LogsDbConn;
 
+use super::{
+    load_recipe_elements, record_revision, refresh_nutrition, refresh_search_document,
+    resolve_ingredients, TransactionError,
+};
+
 /// Update recipe
 ///
-/// Update recipe in the database
+/// Update recipe in the database, open to its owner and editors.
 #[utoipa::path(
     put,
-    path = "/recipes",
+    path = "/recipes/{recipe_id}",
     request_body = RecipePutDTO,
     tag = "recipes",
     responses(
         (status = 200, description = "Recipe updated succesfully", body = RecipeResultDTO),
         (status = 400, description = "Validation error"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is neither the owner nor an editor of the recipe"),
+        (status = NOT_FOUND, description = "Recipe was not found"),
         (status = 500, description = "Internal Server Error"),
     ),
     params(
@@ -32,116 +40,157 @@ pub async fn update_recipe(
     conn: LogsDbConn,
     recipe_id: i32,
     updaterecipe: Json<RecipePutDTO>,
-) -> Result<Json<RecipeResultDTO>, Status> {
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<RecipeResultDTO> {
+    let owned = match owned {
+        Ok(o) => o,
+        Err(err) => return RecipeResponse::from(err),
+    };
+    if owned.recipe.deleted_at.is_some() {
+        return RecipeResponse::NotFound(String::from(
+            "The recipe is in the trash, restore it before editing.",
+        ));
+    }
+    match updaterecipe.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
     let updaterecipe = updaterecipe.into_inner();
-    let recipe: Recipe = match conn
-        .run(move |c| recipes.find(recipe_id).first::<Recipe>(c))
-        .await
-    {
-        Ok(result) => {
-            let new_title = match &updaterecipe.title {
-                Some(t) => {
-                    if t.is_empty() {
-                        result.title.clone()
-                    } else {
-                        t.clone()
-                    }
-                }
-                None => result.title.clone(),
-            };
-            let new_servings = match &updaterecipe.servings {
-                Some(s) => {
-                    if s.is_empty() {
-                        result.servings.clone()
-                    } else {
-                        s.clone()
-                    }
-                }
-                None => result.servings.clone(),
-            };
-            match conn
-                .run(move |c| {
-                    diesel::update(&result)
-                        .set((
-                            title.eq(new_title),
-                            servings.eq(new_servings),
-                            updated_at.eq(diesel::dsl::now), // we have to update this even if title or servings were untouched
-                        ))
-                        .get_result::<Recipe>(c)
-                })
-                .await
-            {
-                Ok(res) => res,
-                Err(_) => return Err(Status::InternalServerError),
-            }
+    let user_id = owned.user_id;
+
+    // every step runs in one transaction, a failing step rolls back the whole update
+    let updated = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                apply_recipe_update(c, recipe_id, updaterecipe, user_id)
+            })
+        })
+        .await;
+
+    match updated {
+        Ok(recipe) => RecipeResponse::Ok(Json(recipe)),
+        Err(err) => {
+            RecipeResponse::InternalServerError(format!("{} The recipe was not updated.", err))
         }
-        Err(_) => return Err(Status::NotFound),
-    }; // get updated recipe or return error
-
-    // get updated instructions
-    let recipe_instructions =
-        match update_instructions(recipe_id, &updaterecipe.instructions, &conn).await {
-            Ok(res) => res,
-            Err(_) => return Err(Status::InternalServerError),
-        };
+    }
+}
 
-    // get updated ingredients
-    let recipe_ingredients =
-        match update_ingredients(recipe_id, &updaterecipe.ingredients, &conn).await {
-            Ok(res) => res,
-            Err(_) => return Err(Status::InternalServerError),
-        };
+/// Write an update to a recipe, its instructions, ingredients and tags, and save the result
+/// as a new revision. Recipes from before revisions were kept get their current state saved
+/// first. Has to run inside a transaction.
+pub fn apply_recipe_update(
+    c: &mut PgConnection,
+    recipe_id: i32,
+    updaterecipe: RecipePutDTO,
+    user_id: i32,
+) -> Result<RecipeResultDTO, TransactionError> {
+    // the lock keeps concurrent updates from taking the same revision number
+    let result = recipes
+        .find(recipe_id)
+        .for_update()
+        .first::<Recipe>(c)
+        .map_err(|_| TransactionError::from("Cannot read recipe from the database."))?;
+
+    let has_revisions = diesel::select(diesel::dsl::exists(
+        recipe_revisions::table.filter(recipe_revisions::recipe_id.eq(recipe_id)),
+    ))
+    .get_result::<bool>(c)
+    .map_err(|_| TransactionError::from("Cannot read revisions from the database."))?;
+    if !has_revisions {
+        let current = recipes
+            .find(recipe_id)
+            .load::<Recipe>(c)
+            .map_err(|_| TransactionError::from("Cannot read recipe from the database."))?;
+        for recipe in load_recipe_elements(c, current, Some(user_id)).map_err(TransactionError)? {
+            record_revision(c, &recipe, None)
+                .map_err(|_| TransactionError::from("Cannot save the revision."))?;
+        }
+    }
 
-    // get updated tags
-    let recipe_tags = match update_tags(recipe_id, &updaterecipe.tags, &conn).await {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
+    let new_title = match &updaterecipe.title {
+        Some(t) => {
+            if t.is_empty() {
+                result.title.clone()
+            } else {
+                t.clone()
+            }
+        }
+        None => result.title.clone(),
+    };
+    let new_servings = updaterecipe.servings.unwrap_or(result.servings);
+    // an empty unit clears it
+    let new_servings_unit = match &updaterecipe.servings_unit {
+        Some(u) => {
+            if u.is_empty() {
+                None
+            } else {
+                Some(u.clone())
+            }
+        }
+        None => result.servings_unit.clone(),
+    };
+    // nutrition overrides: a missing field keeps the value, null goes back to the computed one
+    let new_kcal = updaterecipe.kcal.unwrap_or(result.kcal);
+    let new_carbs = updaterecipe.carbs.unwrap_or(result.carbs);
+    let new_proteins = updaterecipe.proteins.unwrap_or(result.proteins);
+    let new_fats = updaterecipe.fats.unwrap_or(result.fats);
+    let new_visibility = match updaterecipe.visibility {
+        Some(v) => String::from(v.as_str()),
+        None => result.visibility.clone(),
     };
 
-    let mut recipe = RecipeResultDTO::from(recipe);
-    recipe.instructions = recipe_instructions
-        .into_iter()
-        .map(|i| i.instruction)
-        .collect::<Vec<String>>();
-    recipe.ingredients = recipe_ingredients
-        .into_iter()
-        .map(IngredientDTO::from)
-        .collect::<Vec<IngredientDTO>>();
-    recipe.tags = recipe_tags
-        .into_iter()
-        .map(TagDTO::from)
-        .collect::<Vec<TagDTO>>();
-
-    Ok(Json(recipe))
+    let recipe = diesel::update(&result)
+        .set((
+            title.eq(new_title),
+            servings.eq(new_servings),
+            servings_unit.eq(new_servings_unit),
+            kcal.eq(new_kcal),
+            carbs.eq(new_carbs),
+            proteins.eq(new_proteins),
+            fats.eq(new_fats),
+            visibility.eq(new_visibility),
+            updated_at.eq(diesel::dsl::now), // we have to update this even if title or servings were untouched
+        ))
+        .get_result::<Recipe>(c)
+        .map_err(|_| TransactionError::from("Cannot update recipe in the database."))?;
+
+    update_instructions(c, recipe_id, &updaterecipe.instructions)?;
+    update_ingredients(c, recipe_id, &updaterecipe.ingredients)?;
+    update_tags(c, recipe_id, &updaterecipe.tags)?;
+
+    refresh_search_document(c, recipe_id)
+        .map_err(|_| TransactionError::from("Cannot update the search index."))?;
+    refresh_nutrition(c, &[recipe_id])
+        .map_err(|_| TransactionError::from("Cannot update the recipe nutrition."))?;
+
+    let recipe = load_recipe_elements(c, vec![recipe], Some(user_id))
+        .map_err(TransactionError)?
+        .pop()
+        .ok_or(TransactionError::from(
+            "Cannot read recipe from the database.",
+        ))?;
+    record_revision(c, &recipe, Some(user_id))
+        .map_err(|_| TransactionError::from("Cannot save the revision."))?;
+
+    Ok(recipe)
 }
 
-async fn update_instructions(
+fn update_instructions(
+    c: &mut PgConnection,
     recipe_id: i32,
     update: &Option<Vec<String>>,
-    conn: &LogsDbConn,
-) -> Result<Vec<Instruction>, Status> {
-    let recipe_instructions = match conn
-        .run(move |c| {
-            instructions::table
-                .filter(instructions::recipe_id.eq(recipe_id))
-                .order(instructions::display_order.asc())
-                .load::<Instruction>(c)
-        })
-        .await
-    {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
-    };
+) -> Result<Vec<Instruction>, TransactionError> {
+    let recipe_instructions = instructions::table
+        .filter(instructions::recipe_id.eq(recipe_id))
+        .order(instructions::display_order.asc())
+        .load::<Instruction>(c)
+        .map_err(|_| TransactionError::from("Cannot read instructions from the database."))?;
 
-    if update.is_none() {
-        return Ok(recipe_instructions);
-    }
-
-    let mut old_instructions: Vec<Instruction> = recipe_instructions;
     let mut new_instructions: Vec<String> = match update.clone() {
         Some(i) => i,
-        None => return Err(Status::InternalServerError),
+        None => return Ok(recipe_instructions),
     };
+    let mut old_instructions: Vec<Instruction> = recipe_instructions;
 
     let diff = old_instructions.len() as i32 - new_instructions.len() as i32;
 
@@ -151,20 +200,10 @@ async fn update_instructions(
         for _ in 0..diff {
             delete_ids.push(old_instructions.pop().unwrap().id);
         }
-        match conn
-            .run(|c| {
-                diesel::delete(instructions::table)
-                    .filter(instructions::id.eq_any(delete_ids))
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on delete.");
-                return Err(Status::InternalServerError);
-            }
-        }
+        diesel::delete(instructions::table)
+            .filter(instructions::id.eq_any(delete_ids))
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot delete instructions from the database."))?;
     }
     // need to add new
     else if diff < 0 {
@@ -179,171 +218,96 @@ async fn update_instructions(
             });
             display_order -= 1;
         }
-        match conn
-            .run(|c| {
-                diesel::insert_into(instructions::table)
-                    .values(
-                        inserts
-                            .into_iter()
-                            .rev()
-                            .collect::<Vec<InstructionInsert>>(),
-                    )
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on insert.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        diesel::insert_into(instructions::table)
+            .values(
+                inserts
+                    .into_iter()
+                    .rev()
+                    .collect::<Vec<InstructionInsert>>(),
+            )
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot insert instructions into the database."))?;
     }
 
     // need to update
-    if !old_instructions.is_empty() {
-        // build sql query for batch update
-        let mut query = String::from("UPDATE instructions SET instruction = CASE");
-
-        let update_ids: Vec<String> = old_instructions
-            .iter()
-            .zip(new_instructions)
-            .filter_map(|(old, new)| {
-                if old.instruction != new {
-                    query.push_str(&format!(" WHEN id={} THEN '{}'", old.id, new));
-                    Some(old.id.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<String>>();
-
-        // don't update if not needed
-        if !update_ids.is_empty() {
-            query.push_str(&format!(
-                " ELSE instruction END WHERE id IN ({})",
-                update_ids.join(",")
-            ));
-            println!("Query: {query}");
-            match conn.run(|c| diesel::sql_query(query).execute(c)).await {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("DB error on batch update.");
-                    return Err(Status::InternalServerError);
-                }
-            };
+    for (old, new) in old_instructions.iter().zip(new_instructions) {
+        if old.instruction != new {
+            diesel::update(instructions::table.find(old.id))
+                .set(instructions::instruction.eq(new))
+                .execute(c)
+                .map_err(|_| {
+                    TransactionError::from("Cannot update instructions in the database.")
+                })?;
         }
     }
 
     // return updated instructions
-    match conn
-        .run(move |c| {
-            instructions::table
-                .filter(instructions::recipe_id.eq(recipe_id))
-                .order(instructions::display_order.asc())
-                .load::<Instruction>(c)
-        })
-        .await
-    {
-        Ok(res) => Ok(res),
-        Err(_) => Err(Status::InternalServerError),
-    }
+    instructions::table
+        .filter(instructions::recipe_id.eq(recipe_id))
+        .order(instructions::display_order.asc())
+        .load::<Instruction>(c)
+        .map_err(|_| TransactionError::from("Cannot read instructions from the database."))
 }
 
-async fn update_ingredients(
+fn update_ingredients(
+    c: &mut PgConnection,
     recipe_id: i32,
     update: &Option<Vec<IngredientDTO>>,
-    conn: &LogsDbConn,
-) -> Result<Vec<(RecipeIngredient, Ingredient)>, Status> {
-    let recipe_ingredients: Vec<(RecipeIngredient, Ingredient)> = match conn
-        .run(move |c| {
-            recipe_ingredients::table
-                .filter(recipe_ingredients::recipe_id.eq(recipe_id))
-                .inner_join(ingredients::table)
-                .load::<(RecipeIngredient, Ingredient)>(c)
-        })
-        .await
-    {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
+) -> Result<Vec<(RecipeIngredient, Ingredient)>, TransactionError> {
+    let recipe_ingredients: Vec<(RecipeIngredient, Ingredient)> = recipe_ingredients::table
+        .filter(recipe_ingredients::recipe_id.eq(recipe_id))
+        .inner_join(ingredients::table)
+        .load::<(RecipeIngredient, Ingredient)>(c)
+        .map_err(|_| TransactionError::from("Cannot read ingredients from the database."))?;
+
+    let update_ingredients: Vec<IngredientDTO> = match update.clone() {
+        Some(i) => i.into_iter().map(IngredientDTO::normalized).collect(),
+        None => return Ok(recipe_ingredients),
     };
-
-    if update.is_none() {
-        return Ok(recipe_ingredients);
-    }
+    let labels = update_ingredients
+        .iter()
+        .map(|i| i.label.clone())
+        .collect::<Vec<String>>();
+    let mut update_ingredients = update_ingredients
+        .into_iter()
+        .zip(resolve_ingredients(c, &labels)?)
+        .collect::<Vec<(IngredientDTO, Ingredient)>>();
 
     let mut recipe_ingredients_inserts = Vec::<RecipeIngredientInsert>::new();
-    let mut ingredient_inserts = Vec::<IngredientInsert>::new();
-    let mut delayed_inserts = Vec::<((Option<String>, String), Vec<Option<f32>>)>::new(); // Ingredient, amount(s)
     let mut updates = Vec::<(RecipeIngredient, IngredientDTO)>::new();
     let mut delete_ids = Vec::<i32>::new();
 
-    let mut update_ingredients: Vec<IngredientDTO> = update.clone().unwrap();
     let mut old_ingredients: Vec<(RecipeIngredient, Ingredient)> = recipe_ingredients;
 
-    // get all ingredients for inserts, easier than filter by what we need
-    // unnecessary query if there won't be any inserts
-    let available_ingredents = match conn.run(|c| ingredients::table.load::<Ingredient>(c)).await {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
-    };
-
     for _ in 0..update_ingredients.len() {
-        let new = update_ingredients.pop().unwrap();
-        let mut addnew = true;
-
-        old_ingredients.retain(|(r, i)| {
-            // discard unaffected rows
-            if r.amount == new.amount && i.unit == new.unit && i.label == new.label {
-                addnew = false;
-                false
-            }
-            // add update and discard
-            else if i.unit == new.unit && i.label == new.label {
-                updates.push((r.clone(), new.clone()));
-                addnew = false;
+        let (new, ingredient) = update_ingredients.pop().unwrap();
+        // consume at most one old row per new line, preferring an unaffected one
+        let same_line =
+            |r: &RecipeIngredient| r.unit == new.unit && r.ingredient_id == ingredient.id;
+        let matched = old_ingredients
+            .iter()
+            .position(|(r, _)| same_line(r) && r.amount == new.amount)
+            .or_else(|| old_ingredients.iter().position(|(r, _)| same_line(r)));
+
+        let addnew = match matched {
+            Some(idx) => {
+                let (old, _) = old_ingredients.remove(idx);
+                // add update unless the row is unaffected
+                if old.amount != new.amount {
+                    updates.push((old, new.clone()));
+                }
                 false
             }
-            // keep
-            else {
-                true
-            }
-        });
+            None => true,
+        };
 
         if addnew {
-            // simple insert if the ingredient is available
-            let mut ingredient: Option<&Ingredient> = None;
-            if !available_ingredents.is_empty() {
-                ingredient = available_ingredents
-                    .iter()
-                    .find(|i| i.unit == new.unit && i.label == new.label);
-            }
-            match ingredient {
-                Some(i) => {
-                    recipe_ingredients_inserts.push(RecipeIngredientInsert {
-                        amount: new.amount,
-                        recipe_id,
-                        ingredient_id: i.id,
-                    });
-                }
-                // need to add ingredients and then use those ids in another recipe_ingredients insert
-                None => {
-                    let mut found = false;
-                    for ((u, l), a) in &mut delayed_inserts {
-                        if u.clone() == new.unit && l.clone() == new.label {
-                            a.push(new.amount);
-                            found = true;
-                        }
-                    }
-                    if !found {
-                        ingredient_inserts.push(IngredientInsert {
-                            unit: new.unit.clone(),
-                            label: new.label.clone(),
-                        });
-                        delayed_inserts.push(((new.unit, new.label), vec![new.amount]));
-                    }
-                }
-            }
+            recipe_ingredients_inserts.push(RecipeIngredientInsert {
+                amount: new.amount,
+                recipe_id,
+                ingredient_id: ingredient.id,
+                unit: new.unit,
+            });
         }
     }
 
@@ -352,153 +316,68 @@ async fn update_ingredients(
         for _ in 0..(old_ingredients.len() as u32) {
             delete_ids.push(old_ingredients.pop().unwrap().0.id);
         }
-        match conn
-            .run(|c| {
-                diesel::delete(recipe_ingredients::table)
-                    .filter(recipe_ingredients::id.eq_any(delete_ids))
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on delete.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        diesel::delete(recipe_ingredients::table)
+            .filter(recipe_ingredients::id.eq_any(delete_ids))
+            .execute(c)
+            .map_err(|_| {
+                TransactionError::from("Cannot delete recipe ingredients from the database.")
+            })?;
     }
 
     // insert
-    // add ingredients, get ids
-    if !ingredient_inserts.is_empty() {
-        let new_ingredients = match conn
-            .run(move |c| {
-                diesel::insert_into(ingredients::table)
-                    .values(&ingredient_inserts)
-                    .get_results::<Ingredient>(c)
-            })
-            .await
-        {
-            Ok(res) => res,
-            Err(_) => return Err(Status::InternalServerError),
-        };
-
-        for ingredient in new_ingredients {
-            for ((unit, label), amounts) in &delayed_inserts {
-                if ingredient.unit == unit.clone() && ingredient.label == label.clone() {
-                    for a in amounts {
-                        recipe_ingredients_inserts.push(RecipeIngredientInsert {
-                            amount: *a,
-                            recipe_id,
-                            ingredient_id: ingredient.id,
-                        });
-                    }
-                }
-            }
-        }
-    }
     // add recipe_ingredients
     if !recipe_ingredients_inserts.is_empty() {
-        match conn
-            .run(|c| {
-                diesel::insert_into(recipe_ingredients::table)
-                    .values(
-                        recipe_ingredients_inserts
-                            .into_iter()
-                            .rev() // reverse, to keep original order as much as possible
-                            .collect::<Vec<RecipeIngredientInsert>>(),
-                    )
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on insert.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        diesel::insert_into(recipe_ingredients::table)
+            .values(
+                recipe_ingredients_inserts
+                    .into_iter()
+                    .rev() // reverse, to keep original order as much as possible
+                    .collect::<Vec<RecipeIngredientInsert>>(),
+            )
+            .execute(c)
+            .map_err(|_| {
+                TransactionError::from("Cannot insert recipe ingredients into the database.")
+            })?;
     }
 
-    // build sql query for batch update
-    // TODO binding
-    let mut query = String::from("UPDATE recipe_ingredients SET amount = CASE");
-
-    let update_ids: Vec<String> = updates
-        .into_iter()
-        .map(|(old, new)| {
-            query.push_str(&format!(
-                " WHEN id={} THEN {}",
-                old.id,
-                match new.amount {
-                    Some(a) => a.to_string(),
-                    None => String::from("NULL"),
-                }
-            ));
-            old.id.to_string()
-        })
-        .collect::<Vec<String>>();
-
-    // don't update if not needed
-    if !update_ids.is_empty() {
-        query.push_str(&format!(
-            " ELSE amount END WHERE id IN ({})",
-            update_ids.join(",")
-        ));
-
-        match conn.run(|c| diesel::sql_query(query).execute(c)).await {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on batch update.");
-                return Err(Status::InternalServerError);
-            }
-        };
+    // update amounts
+    for (old, new) in updates {
+        diesel::update(recipe_ingredients::table.find(old.id))
+            .set(recipe_ingredients::amount.eq(new.amount))
+            .execute(c)
+            .map_err(|_| {
+                TransactionError::from("Cannot update recipe ingredients in the database.")
+            })?;
     }
 
-    match conn
-        .run(move |c| {
-            recipe_ingredients::table
-                .filter(recipe_ingredients::recipe_id.eq(recipe_id))
-                .inner_join(ingredients::table)
-                .load::<(RecipeIngredient, Ingredient)>(c)
-        })
-        .await
-    {
-        Ok(res) => Ok(res),
-        Err(_) => Err(Status::InternalServerError),
-    }
+    recipe_ingredients::table
+        .filter(recipe_ingredients::recipe_id.eq(recipe_id))
+        .inner_join(ingredients::table)
+        .load::<(RecipeIngredient, Ingredient)>(c)
+        .map_err(|_| TransactionError::from("Cannot read ingredients from the database."))
 }
 
-async fn update_tags(
+fn update_tags(
+    c: &mut PgConnection,
     recipe_id: i32,
     update: &Option<Vec<String>>,
-    conn: &LogsDbConn,
-) -> Result<Vec<Tag>, Status> {
-    let mut recipe_tags = match conn
-        .run(move |c| {
-            recipes_tags::table
-                .filter(recipes_tags::recipe_id.eq(recipe_id))
-                .inner_join(tags::table)
-                .select(Tag::as_select())
-                .load::<Tag>(c)
-        })
-        .await
-    {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
+) -> Result<Vec<Tag>, TransactionError> {
+    let mut recipe_tags = recipes_tags::table
+        .filter(recipes_tags::recipe_id.eq(recipe_id))
+        .inner_join(tags::table)
+        .select(Tag::as_select())
+        .load::<Tag>(c)
+        .map_err(|_| TransactionError::from("Cannot read tags from the database."))?;
+
+    let mut update_tags: Vec<String> = match update.clone() {
+        Some(t) => t,
+        None => return Ok(recipe_tags),
     };
 
-    if update.is_none() {
-        return Ok(recipe_tags);
-    }
-
     // add tags
-    let available_tags = match conn.run(|c| tags::table.load::<Tag>(c)).await {
-        Ok(res) => res,
-        Err(_) => return Err(Status::InternalServerError),
-    };
-
-    let mut update_tags: Vec<String> = update.clone().unwrap();
+    let available_tags = tags::table
+        .load::<Tag>(c)
+        .map_err(|_| TransactionError::from("Cannot load tags from the database."))?;
 
     let mut recipes_tags_inserts = Vec::<RecipeTag>::new();
     let mut tag_inserts = Vec::<TagDTO>::new();
@@ -556,40 +435,20 @@ async fn update_tags(
         for _ in 0..(recipe_tags.len() as u32) {
             delete_ids.push(recipe_tags.pop().unwrap().id);
         }
-        match conn
-            .run(move |c| {
-                diesel::delete(recipes_tags::table)
-                    .filter(recipes_tags::tag_id.eq_any(delete_ids))
-                    .filter(recipes_tags::recipe_id.eq(recipe_id))
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on delete.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        diesel::delete(recipes_tags::table)
+            .filter(recipes_tags::tag_id.eq_any(delete_ids))
+            .filter(recipes_tags::recipe_id.eq(recipe_id))
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot delete recipe tags from the database."))?;
     }
 
     // insert
     // add tags, get ids
     if !tag_inserts.is_empty() {
-        let new_tags = match conn
-            .run(move |c| {
-                diesel::insert_into(tags::table)
-                    .values(&tag_inserts)
-                    .get_results::<Tag>(c)
-            })
-            .await
-        {
-            Ok(res) => res,
-            Err(_) => {
-                println!("DB error on tag insert.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        let new_tags = diesel::insert_into(tags::table)
+            .values(&tag_inserts)
+            .get_results::<Tag>(c)
+            .map_err(|_| TransactionError::from("Cannot insert tags into the database."))?;
 
         for tag in new_tags {
             for t in &delayed_inserts {
@@ -604,33 +463,16 @@ async fn update_tags(
     }
     // add recipes_tags
     if !recipes_tags_inserts.is_empty() {
-        match conn
-            .run(|c| {
-                diesel::insert_into(recipes_tags::table)
-                    .values(recipes_tags_inserts)
-                    .execute(c)
-            })
-            .await
-        {
-            Ok(_) => (),
-            Err(_) => {
-                println!("DB error on recipes_tags insert.");
-                return Err(Status::InternalServerError);
-            }
-        };
+        diesel::insert_into(recipes_tags::table)
+            .values(recipes_tags_inserts)
+            .execute(c)
+            .map_err(|_| TransactionError::from("Cannot insert recipe tags into the database."))?;
     }
 
-    match conn
-        .run(move |c| {
-            recipes_tags::table
-                .filter(recipes_tags::recipe_id.eq(recipe_id))
-                .inner_join(tags::table)
-                .select(Tag::as_select())
-                .load::<Tag>(c)
-        })
-        .await
-    {
-        Ok(res) => Ok(res),
-        Err(_) => Err(Status::InternalServerError),
-    }
+    recipes_tags::table
+        .filter(recipes_tags::recipe_id.eq(recipe_id))
+        .inner_join(tags::table)
+        .select(Tag::as_select())
+        .load::<Tag>(c)
+        .map_err(|_| TransactionError::from("Cannot read tags from the database."))
 }
@@ -0,0 +1,299 @@
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+
+use crate::models::*;
+use crate::revisions::recipe_diff;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::{apply_recipe_update, pagination, readable_recipe, TransactionError};
+
+/// Snapshot of a revision, `None` if the recipe has no such revision
+fn revision_snapshot(
+    c: &mut PgConnection,
+    recipe_id: i32,
+    revision: i32,
+) -> QueryResult<Option<RecipeResultDTO>> {
+    let snapshot = recipe_revisions::table
+        .filter(recipe_revisions::recipe_id.eq(recipe_id))
+        .filter(recipe_revisions::revision.eq(revision))
+        .select(recipe_revisions::snapshot)
+        .first::<serde_json::Value>(c)
+        .optional()?;
+    snapshot
+        .map(serde_json::from_value::<RecipeResultDTO>)
+        .transpose()
+        .map_err(|err| diesel::result::Error::DeserializationError(Box::new(err)))
+}
+
+/// Recipe revisions
+///
+/// Get the saved revisions of a recipe, newest first.
+/// Every update saves the full recipe as a new revision.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/revisions?{page}&{per_page}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Revisions found succesfully", body = PaginatedResult<RevisionDTO>),
+        (status = 404, description = "Recipe was not found"),
+        (status = 500, description = "Error loading revisions"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/revisions?<page>&<per_page>", rank = 2)]
+pub async fn list_revisions(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<RevisionDTO>> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+
+            let total: i64 = recipe_revisions::table
+                .filter(recipe_revisions::recipe_id.eq(recipe_id))
+                .count()
+                .get_result(c)?;
+            let (current_page, per_page, offset) = pagination(page, per_page, total);
+            let records = recipe_revisions::table
+                .left_join(users::table)
+                .filter(recipe_revisions::recipe_id.eq(recipe_id))
+                .order(recipe_revisions::revision.desc())
+                .offset(offset)
+                .limit(per_page)
+                .select((
+                    recipe_revisions::revision,
+                    users::username.nullable(),
+                    recipe_revisions::created_at,
+                ))
+                .load::<(i32, Option<String>, chrono::NaiveDateTime)>(c)?
+                .into_iter()
+                .map(|(revision, username, created_at)| RevisionDTO {
+                    revision,
+                    username,
+                    created_at,
+                })
+                .collect::<Vec<RevisionDTO>>();
+
+            Ok::<_, diesel::result::Error>(Some(PaginatedResult {
+                records,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor: None,
+                prev_cursor: None,
+            }))
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read revisions from the database.",
+        )),
+    }
+}
+
+/// Recipe revision
+///
+/// Get a recipe as it was saved in the given revision.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/revisions/{revision}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Revision found succesfully", body = RecipeResultDTO),
+        (status = 404, description = "Recipe or revision was not found"),
+        (status = 500, description = "Error loading the revision"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("revision" = i32, description = "Revision number", example = 1),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/revisions/<revision>")]
+pub async fn single_revision(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    revision: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<RecipeResultDTO> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+            revision_snapshot(c, recipe_id, revision)
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The revision was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read the revision from the database.",
+        )),
+    }
+}
+
+/// Compare revisions
+///
+/// Get the changes between two revisions of a recipe: changed fields, added, removed and
+/// changed instructions and ingredients, and added and removed tags.
+/// Compares with the latest revision unless `to` is given.
+#[utoipa::path(
+    get,
+    path = "/recipes/{recipe_id}/revisions/{revision}/diff?{to}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Revisions compared succesfully", body = RecipeDiffDTO),
+        (status = 404, description = "Recipe or revision was not found"),
+        (status = 500, description = "Error loading the revisions"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("revision" = i32, description = "Revision to compare from", example = 1),
+        ("to" = Option<i32>, Query, description = "Revision to compare to, defaults to the latest one"),
+    ),
+    security(
+        (),
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/recipes/<recipe_id>/revisions/<revision>/diff?<to>")]
+pub async fn revision_diff(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    revision: i32,
+    to: Option<i32>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<RecipeDiffDTO> {
+    let user_id: Option<i32> = match key {
+        Ok(k) => Some(k.claims.subject_id),
+        Err(_) => None,
+    };
+
+    let result = conn
+        .run(move |c| {
+            if !readable_recipe(c, recipe_id, user_id)? {
+                return Ok(None);
+            }
+            let to = match to {
+                Some(t) => t,
+                None => recipe_revisions::table
+                    .filter(recipe_revisions::recipe_id.eq(recipe_id))
+                    .select(diesel::dsl::max(recipe_revisions::revision))
+                    .first::<Option<i32>>(c)?
+                    .unwrap_or(revision),
+            };
+            let from_snapshot = revision_snapshot(c, recipe_id, revision)?;
+            let to_snapshot = revision_snapshot(c, recipe_id, to)?;
+            Ok::<_, diesel::result::Error>(
+                from_snapshot
+                    .zip(to_snapshot)
+                    .map(|(from, to_recipe)| recipe_diff(revision, &from, to, &to_recipe)),
+            )
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(res)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The revision was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read the revisions from the database.",
+        )),
+    }
+}
+
+/// Revert recipe
+///
+/// Bring a recipe back to the state of an earlier revision. The revert is saved as a new
+/// revision, so it can be undone. The image is not part of the revert.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/revisions/{revision}/revert",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Recipe reverted succesfully", body = RecipeResultDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
+        (status = NOT_FOUND, description = "Recipe or revision was not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+        ("revision" = i32, description = "Revision to go back to", example = 1),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/revisions/<revision>/revert")]
+pub async fn revert_recipe(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    revision: i32,
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<RecipeResultDTO> {
+    let owned = match owned.and_then(|o| o.owner_only("revert")) {
+        Ok(o) => o,
+        Err(err) => return RecipeResponse::from(err),
+    };
+    if owned.recipe.deleted_at.is_some() {
+        return RecipeResponse::NotFound(String::from(
+            "The recipe is in the trash, restore it before editing.",
+        ));
+    }
+    let user_id = owned.user_id;
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                let snapshot = revision_snapshot(c, recipe_id, revision)
+                    .map_err(|_| TransactionError::from("Cannot read the revision."))?;
+                match snapshot {
+                    Some(s) => {
+                        apply_recipe_update(c, recipe_id, RecipePutDTO::from(s), user_id).map(Some)
+                    }
+                    None => Ok(None),
+                }
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Some(recipe)) => RecipeResponse::Ok(Json(recipe)),
+        Ok(None) => RecipeResponse::NotFound(String::from("The revision was not found.")),
+        Err(err) => {
+            RecipeResponse::InternalServerError(format!("{} The recipe was not reverted.", err))
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+
+use crate::jwt::*;
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+/// Open a session for a device, expired sessions of the user are cleaned up on the way.
+/// Returns the session id and its first refresh token.
+pub fn create_session(
+    c: &mut PgConnection,
+    user_id: i32,
+    user_agent: Option<String>,
+) -> QueryResult<(i32, String)> {
+    diesel::delete(
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::expires_at.le(now)),
+    )
+    .execute(c)?;
+
+    let refresh_token = create_refresh_token();
+    let session_id = diesel::insert_into(sessions::table)
+        .values((
+            sessions::user_id.eq(user_id),
+            sessions::refresh_token_hash.eq(hash_refresh_token(&refresh_token)),
+            sessions::user_agent.eq(user_agent),
+            sessions::expires_at.eq(now + refresh_token_days().days()),
+        ))
+        .returning(sessions::id)
+        .get_result::<i32>(c)?;
+    Ok((session_id, refresh_token))
+}
+
+/// End every session of a user, their access tokens stop working right away
+pub fn revoke_sessions(c: &mut PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id))).execute(c)
+}
+
+/// Access token for a session, together with the session's current refresh token
+pub fn token_response(
+    user_id: i32,
+    session_id: i32,
+    refresh_token: String,
+) -> Result<TokenResponse, NetworkResponse> {
+    match create_jwt(user_id, session_id) {
+        Ok(access_token) => Ok(TokenResponse {
+            access_token,
+            token_type: String::from("Bearer"),
+            expires_in: access_token_minutes() * 60,
+            refresh_token,
+        }),
+        Err(err) => {
+            eprintln!("JWT token generation error: {:?}", err);
+            Err(NetworkResponse::InternalServerError(
+                "Failed to generate JWT token".to_string(),
+            ))
+        }
+    }
+}
+
+/// Refresh tokens
+///
+/// Trade a refresh token for a new access token and a new refresh token.
+/// Each refresh token works once. Using one a second time ends its session,
+/// since it means someone else got hold of it.
+#[utoipa::path(
+    post,
+    path = "/token/refresh",
+    request_body = RefreshTokenRequest,
+    tag = "auth",
+    responses(
+        (status = 200, description = "Tokens refreshed succesfully", body = TokenResponse),
+        (status = 401, description = "Refresh token is invalid, expired or already used"),
+        (status = 403, description = "Account is disabled"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+#[post("/token/refresh", data = "<request>")]
+pub async fn refresh_token(
+    conn: LogsDbConn,
+    request: Json<RefreshTokenRequest>,
+) -> Result<Json<TokenResponse>, NetworkResponse> {
+    let presented = hash_refresh_token(&request.refresh_token);
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let session = match sessions::table
+                    .filter(sessions::refresh_token_hash.eq(&presented))
+                    .filter(sessions::expires_at.gt(now))
+                    .for_update()
+                    .first::<Session>(c)
+                    .optional()?
+                {
+                    Some(s) => s,
+                    None => {
+                        let reused = diesel::delete(
+                            sessions::table.filter(sessions::previous_token_hash.eq(&presented)),
+                        )
+                        .execute(c)?;
+                        return Ok(Err(NetworkResponse::Unauthorized(if reused > 0 {
+                            "Refresh token was already used, the session has been ended".to_string()
+                        } else {
+                            "Invalid or expired refresh token".to_string()
+                        })));
+                    }
+                };
+
+                let disabled = users::table
+                    .find(session.user_id)
+                    .select(users::disabled)
+                    .first::<bool>(c)?;
+                if disabled {
+                    return Ok(Err(NetworkResponse::Forbidden(
+                        "This account has been disabled".to_string(),
+                    )));
+                }
+
+                let refresh_token = create_refresh_token();
+                diesel::update(sessions::table.find(session.id))
+                    .set((
+                        sessions::refresh_token_hash.eq(hash_refresh_token(&refresh_token)),
+                        sessions::previous_token_hash.eq(Some(presented)),
+                        sessions::last_used_at.eq(now),
+                        sessions::expires_at.eq(now + refresh_token_days().days()),
+                    ))
+                    .execute(c)?;
+                Ok::<_, diesel::result::Error>(Ok((session.user_id, session.id, refresh_token)))
+            })
+        })
+        .await;
+
+    match result {
+        Ok(Ok((user_id, session_id, refresh_token))) => {
+            token_response(user_id, session_id, refresh_token).map(Json)
+        }
+        Ok(Err(response)) => Err(response),
+        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+            "Failed to refresh the session: {}",
+            err
+        ))),
+    }
+}
+
+/// Log out
+///
+/// End the session of the access token. Its refresh token stops working as well.
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Logged out succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/logout")]
+pub async fn logout(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, NetworkResponse> {
+    let session_id = key?.claims.session_id;
+
+    match conn
+        .run(move |c| diesel::delete(sessions::table.find(session_id)).execute(c))
+        .await
+    {
+        Ok(_) => Ok(Status::NoContent),
+        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+            "Failed to end the session: {}",
+            err
+        ))),
+    }
+}
+
+/// List of sessions
+///
+/// Get the devices the user is logged in on, most recently used first.
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Sessions found succesfully", body = Vec<SessionDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/sessions")]
+pub async fn user_sessions(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Json<Vec<SessionDTO>>, NetworkResponse> {
+    let claims = key?.claims;
+    let (user_id, current_id) = (claims.subject_id, claims.session_id);
+
+    match conn
+        .run(move |c| {
+            sessions::table
+                .filter(sessions::user_id.eq(user_id))
+                .filter(sessions::expires_at.gt(now))
+                .order(sessions::last_used_at.desc())
+                .load::<Session>(c)
+        })
+        .await
+    {
+        Ok(list) => Ok(Json(
+            list.into_iter()
+                .map(|s| SessionDTO::new(s, current_id))
+                .collect(),
+        )),
+        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+            "Failed to load sessions: {}",
+            err
+        ))),
+    }
+}
+
+/// Revoke session
+///
+/// Log a device out. Revoking the current session works like logging out.
+#[utoipa::path(
+    delete,
+    path = "/sessions/{session_id}",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Session revoked succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Session was not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    params(
+        ("session_id" = i32, description = "Session id", example = 4),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/sessions/<session_id>")]
+pub async fn revoke_session(
+    conn: LogsDbConn,
+    session_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, NetworkResponse> {
+    let user_id = key?.claims.subject_id;
+
+    match conn
+        .run(move |c| {
+            diesel::delete(
+                sessions::table
+                    .find(session_id)
+                    .filter(sessions::user_id.eq(user_id)),
+            )
+            .execute(c)
+        })
+        .await
+    {
+        Ok(0) => Err(NetworkResponse::NotFound("Session not found".to_string())),
+        Ok(_) => Ok(Status::NoContent),
+        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+            "Failed to revoke the session: {}",
+            err
+        ))),
+    }
+}
+
+/// Revoke other sessions
+///
+/// Log out every device except the one making the request.
+#[utoipa::path(
+    delete,
+    path = "/sessions",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Sessions revoked succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/sessions")]
+pub async fn revoke_other_sessions(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, NetworkResponse> {
+    let claims = key?.claims;
+    let (user_id, current_id) = (claims.subject_id, claims.session_id);
+
+    match conn
+        .run(move |c| {
+            diesel::delete(
+                sessions::table
+                    .filter(sessions::user_id.eq(user_id))
+                    .filter(sessions::id.ne(current_id)),
+            )
+            .execute(c)
+        })
+        .await
+    {
+        Ok(_) => Ok(Status::NoContent),
+        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+            "Failed to revoke sessions: {}",
+            err
+        ))),
+    }
+}
+
+/// Token signing keys
+///
+/// Public keys that verify access tokens, so other services can check them without a shared
+/// secret. Retired keys from `JWT_VERIFICATION_KEYS` stay listed while their tokens can still
+/// be in use. A shared signing secret is never listed, so without retired keys the set is empty.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Keys found succesfully", body = JsonWebKeySet),
+    )
+)]
+#[get("/.well-known/jwks.json")]
+pub async fn jwks() -> Json<JsonWebKeySet> {
+    Json(JsonWebKeySet {
+        keys: public_keys().keys.clone(),
+    })
+}
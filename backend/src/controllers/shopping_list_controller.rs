@@ -0,0 +1,568 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use std::collections::HashMap;
+use validator::Validate;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::shopping::{add_to_list, list_text, ListEntry};
+use crate::units::normalize_unit;
+use crate::LogsDbConn;
+
+use super::{find_ingredient, get_recipe_elements, visible_recipes, TransactionError};
+
+/// Items of a user's shopping list, unchecked ones first
+fn load_list(c: &mut PgConnection, user_id: i32) -> QueryResult<Vec<ShoppingItemDTO>> {
+    shopping_list_items::table
+        .filter(shopping_list_items::user_id.eq(user_id))
+        .order((
+            shopping_list_items::checked.asc(),
+            shopping_list_items::id.asc(),
+        ))
+        .load::<ShoppingListItem>(c)
+        .map(|items| items.into_iter().map(ShoppingItemDTO::from).collect())
+}
+
+/// Merge entries into the unchecked items of the list and store the changed and new ones.
+/// Checked items are already bought, new amounts are not added to them.
+fn merge_into_list(
+    c: &mut PgConnection,
+    user_id: i32,
+    entries: Vec<ListEntry>,
+) -> Result<(), TransactionError> {
+    let mut list = shopping_list_items::table
+        .filter(shopping_list_items::user_id.eq(user_id))
+        .filter(shopping_list_items::checked.eq(false))
+        .order(shopping_list_items::id.asc())
+        .load::<ShoppingListItem>(c)
+        .map_err(|_| TransactionError::from("Cannot read the shopping list from the database."))?
+        .into_iter()
+        .map(|i| ListEntry {
+            id: Some(i.id),
+            ingredient_id: i.ingredient_id,
+            label: i.label,
+            amount: i.amount,
+            unit: i.unit,
+            changed: false,
+        })
+        .collect::<Vec<ListEntry>>();
+    for entry in entries {
+        add_to_list(&mut list, entry);
+    }
+
+    for entry in list.into_iter().filter(|e| e.changed) {
+        match entry.id {
+            Some(id) => diesel::update(shopping_list_items::table.find(id))
+                .set((
+                    shopping_list_items::amount.eq(entry.amount),
+                    shopping_list_items::unit.eq(entry.unit),
+                ))
+                .execute(c),
+            None => diesel::insert_into(shopping_list_items::table)
+                .values(ShoppingListItemInsert {
+                    user_id,
+                    ingredient_id: entry.ingredient_id,
+                    label: entry.label,
+                    amount: entry.amount,
+                    unit: entry.unit,
+                })
+                .execute(c),
+        }
+        .map_err(|_| TransactionError::from("Cannot save the shopping list in the database."))?;
+    }
+    Ok(())
+}
+
+/// Shopping list
+///
+/// Get the shopping list of the logged in user, unchecked items first.
+#[utoipa::path(
+    get,
+    path = "/shopping-list",
+    tag = "shopping list",
+    responses(
+        (status = 200, description = "Shopping list found succesfully", body = Vec<ShoppingItemDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error loading the shopping list"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/shopping-list")]
+pub async fn shopping_list(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<ShoppingItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your shopping list.",
+            ))
+        }
+    };
+
+    match conn.run(move |c| load_list(c, user_id)).await {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read the shopping list from the database.",
+        )),
+    }
+}
+
+/// Export shopping list
+///
+/// Get the shopping list of the logged in user as plain text, one item per line.
+/// Checked items are marked with `[x]`, unchecked ones with `[ ]`.
+#[utoipa::path(
+    get,
+    path = "/shopping-list/export",
+    tag = "shopping list",
+    responses(
+        (status = 200, description = "Shopping list exported succesfully", body = String, content_type = "text/plain"),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error loading the shopping list"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/shopping-list/export")]
+pub async fn export_shopping_list(
+    conn: LogsDbConn,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<String, RecipeResponse<Vec<ShoppingItemDTO>>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to export your shopping list.",
+            )))
+        }
+    };
+
+    match conn.run(move |c| load_list(c, user_id)).await {
+        Ok(res) => Ok(list_text(&res)),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot read the shopping list from the database.",
+        ))),
+    }
+}
+
+/// Add recipes to the shopping list
+///
+/// Add the ingredients of recipes to the shopping list of the logged in user, each recipe
+/// scaled by `servings` or `factor`. Amounts of the same ingredient are added up when their
+/// units can be combined, e.g. 500 g and 1 kg flour become 1.5 kg.
+/// Checked items are not changed, new amounts get a new line.
+#[utoipa::path(
+    post,
+    path = "/shopping-list/recipes",
+    tag = "shopping list",
+    request_body = ShoppingListRecipesDTO,
+    responses(
+        (status = 200, description = "Ingredients added succesfully", body = Vec<ShoppingItemDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Recipe was not found"),
+        (status = 422, description = "Invalid servings or factor"),
+        (status = 500, description = "Error saving the shopping list"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/shopping-list/recipes", data = "<selection>")]
+pub async fn add_recipes_to_shopping_list(
+    conn: LogsDbConn,
+    selection: Json<ShoppingListRecipesDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<ShoppingItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your shopping list.",
+            ))
+        }
+    };
+
+    match selection.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let selection = selection.into_inner().recipes;
+    if selection
+        .iter()
+        .any(|r| r.servings.is_some() && r.factor.is_some())
+    {
+        return RecipeResponse::BadRequest(String::from(
+            "Scale a recipe either by servings or by factor.",
+        ));
+    }
+
+    let ids = selection.iter().map(|r| r.recipe_id).collect::<Vec<i32>>();
+    let recipes_list = match conn
+        .run(move |c| {
+            recipes::table
+                .filter(recipes::id.eq_any(ids))
+                .filter(recipes::deleted_at.is_null())
+                .filter(visible_recipes(Some(user_id)))
+                .load::<Recipe>(c)
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot read recipes from the database.",
+            ))
+        }
+    };
+    let recipes_found = match get_recipe_elements(recipes_list, &conn, Some(user_id)).await {
+        Ok(res) => res
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect::<HashMap<i32, RecipeResultDTO>>(),
+        Err(err) => return RecipeResponse::InternalServerError(err),
+    };
+
+    let mut lines = Vec::<IngredientDTO>::new();
+    for selected in selection {
+        // the same recipe can be picked more than once
+        let recipe = match recipes_found.get(&selected.recipe_id) {
+            Some(r) => r.clone(),
+            None => {
+                return RecipeResponse::NotFound(format!(
+                    "Recipe {} was not found.",
+                    selected.recipe_id
+                ))
+            }
+        };
+        let recipe = match (selected.servings, selected.factor) {
+            (Some(s), _) => {
+                let f = s as f32 / recipe.servings as f32;
+                recipe.scaled(f, s)
+            }
+            (None, Some(f)) => {
+                let s = recipe.servings;
+                recipe.scaled(f, s)
+            }
+            (None, None) => recipe,
+        };
+        lines.extend(recipe.ingredients);
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                // recipe lines carry the catalogue label, look up the ids by it
+                let labels = lines
+                    .iter()
+                    .map(|l| l.label.clone())
+                    .collect::<Vec<String>>();
+                let catalogue = ingredients::table
+                    .filter(ingredients::label.eq_any(labels))
+                    .load::<Ingredient>(c)
+                    .map_err(|_| {
+                        TransactionError::from("Cannot read ingredients from the database.")
+                    })?
+                    .into_iter()
+                    .map(|i| (i.label, i.id))
+                    .collect::<HashMap<String, i32>>();
+                let entries = lines
+                    .iter()
+                    .map(|l| {
+                        ListEntry::new(
+                            catalogue.get(&l.label).copied(),
+                            &l.label,
+                            l.amount,
+                            l.unit.as_deref(),
+                        )
+                    })
+                    .collect();
+                merge_into_list(c, user_id, entries)?;
+                Ok(load_list(c, user_id)?)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Ok(Json(res)),
+        Err(err) => RecipeResponse::InternalServerError(format!(
+            "{} The shopping list was not changed.",
+            err
+        )),
+    }
+}
+
+/// Add item to the shopping list
+///
+/// Add an item to the shopping list of the logged in user, e.g. something that is not in
+/// a recipe. It is added to an unchecked item of the same ingredient when the units can be combined.
+#[utoipa::path(
+    post,
+    path = "/shopping-list/items",
+    tag = "shopping list",
+    request_body = ShoppingItemPostDTO,
+    responses(
+        (status = 201, description = "Item added succesfully", body = Vec<ShoppingItemDTO>),
+        (status = 401, description = "Not logged in"),
+        (status = 422, description = "Invalid item"),
+        (status = 500, description = "Error saving the shopping list"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/shopping-list/items", data = "<item>")]
+pub async fn add_shopping_item(
+    conn: LogsDbConn,
+    item: Json<ShoppingItemPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<Vec<ShoppingItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your shopping list.",
+            ))
+        }
+    };
+
+    match item.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let item = item.into_inner();
+    if item.label.trim().is_empty() {
+        return RecipeResponse::BadRequest(String::from("The item needs a name."));
+    }
+
+    let result = conn
+        .run(move |c| {
+            c.transaction::<_, TransactionError, _>(|c| {
+                // items are matched against the catalogue, but never added to it
+                let ingredient = find_ingredient(c, item.label.trim()).map_err(|_| {
+                    TransactionError::from("Cannot read ingredients from the database.")
+                })?;
+                let entry = ListEntry::new(
+                    ingredient.map(|i| i.id),
+                    &item.label,
+                    item.amount,
+                    item.unit.as_deref(),
+                );
+                merge_into_list(c, user_id, vec![entry])?;
+                Ok(load_list(c, user_id)?)
+            })
+        })
+        .await;
+
+    match result {
+        Ok(res) => RecipeResponse::Created(Json(res)),
+        Err(err) => RecipeResponse::InternalServerError(format!(
+            "{} The shopping list was not changed.",
+            err
+        )),
+    }
+}
+
+/// Update shopping list item
+///
+/// Check off an item of the logged in user's shopping list, or change its name, amount or unit.
+#[utoipa::path(
+    put,
+    path = "/shopping-list/items/{item_id}",
+    tag = "shopping list",
+    request_body = ShoppingItemPutDTO,
+    responses(
+        (status = 200, description = "Item updated succesfully", body = ShoppingItemDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Item was not found"),
+        (status = 422, description = "Invalid item"),
+        (status = 500, description = "Error saving the item"),
+    ),
+    params(
+        ("item_id" = i32, description = "Shopping list item id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[put("/shopping-list/items/<item_id>", data = "<item>")]
+pub async fn update_shopping_item(
+    conn: LogsDbConn,
+    item_id: i32,
+    item: Json<ShoppingItemPutDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<ShoppingItemDTO> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your shopping list.",
+            ))
+        }
+    };
+
+    match item.validate() {
+        Ok(_) => (),
+        Err(err) => return RecipeResponse::BadRequest(err.to_string()),
+    };
+    let item = item.into_inner();
+
+    let result = conn
+        .run(move |c| {
+            let current = shopping_list_items::table
+                .find(item_id)
+                .filter(shopping_list_items::user_id.eq(user_id))
+                .first::<ShoppingListItem>(c)
+                .optional()?;
+            let current = match current {
+                Some(i) => i,
+                None => return Ok(None),
+            };
+
+            let label = item
+                .label
+                .map(|l| l.split_whitespace().collect::<Vec<&str>>().join(" "))
+                .filter(|l| !l.is_empty());
+            let ingredient_id = match &label {
+                Some(l) => find_ingredient(c, l)?.map(|i| i.id),
+                None => current.ingredient_id,
+            };
+            let unit = match item.unit {
+                Some(u) => u.as_deref().and_then(normalize_unit),
+                None => current.unit,
+            };
+            diesel::update(shopping_list_items::table.find(item_id))
+                .set((
+                    shopping_list_items::checked.eq(item.checked.unwrap_or(current.checked)),
+                    shopping_list_items::label.eq(label.unwrap_or(current.label)),
+                    shopping_list_items::ingredient_id.eq(ingredient_id),
+                    shopping_list_items::amount.eq(item.amount.unwrap_or(current.amount)),
+                    shopping_list_items::unit.eq(unit),
+                ))
+                .get_result::<ShoppingListItem>(c)
+                .map(Some)
+        })
+        .await;
+
+    match result {
+        Ok(Some(res)) => RecipeResponse::Ok(Json(ShoppingItemDTO::from(res))),
+        Ok(None) => RecipeResponse::NotFound(String::from("The item was not found.")),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot save the item in the database.",
+        )),
+    }
+}
+
+/// Delete shopping list item
+///
+/// Remove an item from the logged in user's shopping list.
+#[utoipa::path(
+    delete,
+    path = "/shopping-list/items/{item_id}",
+    tag = "shopping list",
+    responses(
+        (status = 204, description = "Item deleted succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Item was not found"),
+        (status = 500, description = "Error deleting the item"),
+    ),
+    params(
+        ("item_id" = i32, description = "Shopping list item id"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/shopping-list/items/<item_id>")]
+pub async fn delete_shopping_item(
+    conn: LogsDbConn,
+    item_id: i32,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<ShoppingItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your shopping list.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            diesel::delete(shopping_list_items::table.find(item_id))
+                .filter(shopping_list_items::user_id.eq(user_id))
+                .execute(c)
+        })
+        .await;
+
+    match result {
+        Ok(0) => Err(RecipeResponse::NotFound(String::from(
+            "The item was not found.",
+        ))),
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot delete the item from the database.",
+        ))),
+    }
+}
+
+/// Clear shopping list
+///
+/// Remove all items from the logged in user's shopping list, or with `checked=true`
+/// only the checked off ones.
+#[utoipa::path(
+    delete,
+    path = "/shopping-list?{checked}",
+    tag = "shopping list",
+    responses(
+        (status = 204, description = "Shopping list cleared succesfully"),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error clearing the shopping list"),
+    ),
+    params(
+        ("checked" = Option<bool>, Query, description = "Only remove the checked off items"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[delete("/shopping-list?<checked>")]
+pub async fn clear_shopping_list(
+    conn: LogsDbConn,
+    checked: Option<bool>,
+    key: Result<Jwt, NetworkResponse>,
+) -> Result<Status, RecipeResponse<ShoppingItemDTO>> {
+    let user_id = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return Err(RecipeResponse::Unauthorized(String::from(
+                "Please log in to be able to edit your shopping list.",
+            )))
+        }
+    };
+
+    let result = conn
+        .run(move |c| {
+            let mut query = diesel::delete(shopping_list_items::table)
+                .filter(shopping_list_items::user_id.eq(user_id))
+                .into_boxed();
+            if checked.unwrap_or(false) {
+                query = query.filter(shopping_list_items::checked.eq(true));
+            }
+            query.execute(c)
+        })
+        .await;
+
+    match result {
+        Ok(_) => Ok(Status::NoContent),
+        Err(_) => Err(RecipeResponse::InternalServerError(String::from(
+            "Cannot clear the shopping list in the database.",
+        ))),
+    }
+}
@@ -6,6 +6,8 @@ use crate::models::*;
 use crate::schema::*;
 use crate::LogsDbConn;
 
+use super::{load_recipe_elements, record_revision, refresh_search_document, TransactionError};
+
 /// List of tags
 ///
 /// Get all tags from the database.
@@ -64,6 +66,7 @@ pub async fn single_tag(conn: LogsDbConn, tag_slug: String) -> Result<Json<TagDT
     tag = "recipes",
     responses(
         (status = 201, description = "Tag created succesfully", body = TagDTO),
+        (status = 401, description = "Not logged in"),
         (status = 422, description = "Validation error"),
         (status = 500, description = "Internal Server Error"),
     ),
@@ -72,7 +75,17 @@ pub async fn single_tag(conn: LogsDbConn, tag_slug: String) -> Result<Json<TagDT
     ),
 )]
 #[post("/tags", data = "<tag>")]
-pub async fn create_tag(conn: LogsDbConn, tag: Json<TagPostDTO>) -> Result<Json<TagDTO>, Status> {
+pub async fn create_tag(
+    conn: LogsDbConn,
+    tag: Json<TagPostDTO>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<TagDTO> {
+    if key.is_err() {
+        return RecipeResponse::Unauthorized(String::from(
+            "Please log in to be able to create tags.",
+        ));
+    }
+
     match conn
         .run(|c| {
             diesel::insert_into(tags::table)
@@ -81,18 +94,16 @@ pub async fn create_tag(conn: LogsDbConn, tag: Json<TagPostDTO>) -> Result<Json<
         })
         .await
     {
-        Ok(t) => {
-            Ok(Json(TagDTO::from(t)))
-            /* let tag_url = uri!(single_tag: t.slug);
-            Created(tag_url, Ok(Json(TagDTO::from(t)))) */
-        }
-        Err(_) => Err(Status::InternalServerError),
+        Ok(t) => RecipeResponse::Created(Json(TagDTO::from(t))),
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot insert tag into the database.",
+        )),
     }
 }
 
 /// Toggle tag on a recipe
 ///
-/// Set or unset tag relationship with a recipe.
+/// Set or unset tag relationship with a recipe, the change is saved as a new revision.
 /// Returns recipe's tag list.
 #[utoipa::path(
     put,
@@ -100,6 +111,9 @@ pub async fn create_tag(conn: LogsDbConn, tag: Json<TagPostDTO>) -> Result<Json<
     tag = "recipes",
     responses(
         (status = 200, description = "Recipe-tag relationship handled succesfully", body = Vec<TagDTO>, example = json!(["vegan", "gluten free"])),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is neither the owner nor an editor of the recipe"),
+        (status = NOT_FOUND, description = "Recipe or tag was not found"),
         (status = 500, description = "Internal Server Error"),
     ),
     params(
@@ -115,19 +129,30 @@ pub async fn toggle_tag(
     conn: LogsDbConn,
     tag_slug: String,
     recipe_id: i32,
-) -> Result<Json<Vec<TagDTO>>, Status> {
+    owned: Result<OwnedRecipe, NetworkResponse>,
+) -> RecipeResponse<Vec<TagDTO>> {
+    let owned = match owned {
+        Ok(o) if o.recipe.deleted_at.is_some() => {
+            return RecipeResponse::NotFound(String::from(
+                "The recipe is in the trash, restore it before editing.",
+            ))
+        }
+        Ok(o) => o,
+        Err(err) => return RecipeResponse::from(err),
+    };
+
     let tag = match conn
         .run(|c| tags::table.filter(tags::slug.eq(tag_slug)).first::<Tag>(c))
         .await
     {
         Ok(res) => res,
-        Err(_) => return Err(Status::NotFound),
+        Err(_) => return RecipeResponse::NotFound(String::from("The tag was not found.")),
     };
 
     match conn
         .run(move |c| {
             recipes_tags::table
-                .find((tag.id, recipe_id))
+                .find((recipe_id, tag.id))
                 .first::<RecipeTag>(c)
         })
         .await
@@ -136,7 +161,9 @@ pub async fn toggle_tag(
             // remove
             match conn.run(move |c| diesel::delete(&res).execute(c)).await {
                 Ok(_) => (),
-                Err(_) => return Err(Status::InternalServerError),
+                Err(_) => {
+                    return RecipeResponse::InternalServerError(String::from("Error removing tag."))
+                }
             }
         }
         Err(diesel::NotFound) => {
@@ -153,10 +180,31 @@ pub async fn toggle_tag(
                 .await
             {
                 Ok(_) => (),
-                Err(_) => return Err(Status::InternalServerError),
+                Err(_) => {
+                    return RecipeResponse::InternalServerError(String::from("Error adding tag."))
+                }
             }
         }
-        Err(_) => return Err(Status::InternalServerError),
+        Err(_) => return RecipeResponse::InternalServerError(String::from("Error finding tag.")),
+    }
+
+    let user_id = owned.user_id;
+    if let Err(err) = conn
+        .run(move |c| {
+            refresh_search_document(c, recipe_id)
+                .map_err(|_| TransactionError::from("Cannot update the search index."))?;
+            let recipe = load_recipe_elements(c, vec![owned.recipe], Some(user_id))
+                .map_err(TransactionError)?
+                .pop()
+                .ok_or(TransactionError::from(
+                    "Cannot read recipe from the database.",
+                ))?;
+            record_revision(c, &recipe, Some(user_id))
+                .map_err(|_| TransactionError::from("Cannot save the revision."))
+        })
+        .await
+    {
+        return RecipeResponse::InternalServerError(err.0);
     }
 
     match conn
@@ -169,9 +217,11 @@ pub async fn toggle_tag(
         })
         .await
     {
-        Ok(t) => Ok(Json(
+        Ok(t) => RecipeResponse::Ok(Json(
             t.into_iter().map(TagDTO::from).collect::<Vec<TagDTO>>(),
         )),
-        Err(_) => Err(Status::InternalServerError),
+        Err(_) => {
+            RecipeResponse::InternalServerError(String::from("Cannot read tags from the database."))
+        }
     }
 }
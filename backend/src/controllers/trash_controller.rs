@@ -0,0 +1,210 @@
+use diesel::dsl::IntervalDsl;
+use diesel::prelude::*;
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use std::time::Duration;
+
+use crate::models::*;
+use crate::schema::*;
+use crate::LogsDbConn;
+
+use super::get_recipe_elements;
+use super::pagination;
+
+/// List of trashed recipes
+///
+/// Get all recipes owned by the logged in user that were moved to the trash.
+#[utoipa::path(
+    get,
+    path = "/trash?{page}&{per_page}",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Trashed recipes found succesfully", body = [PaginatedResult<RecipeResultDTO>]),
+        (status = 401, description = "Not logged in"),
+        (status = 500, description = "Error loading recipes"),
+    ),
+    params(
+        ("page" = Option<i64>, Query, description = "Pagination: page number"),
+        ("per_page" = Option<i64>, Query, description = "Pagination: results per page"),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[get("/trash?<page>&<per_page>")]
+pub async fn trash_list(
+    conn: LogsDbConn,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<PaginatedResult<RecipeResultDTO>> {
+    let user_id: i32 = match key {
+        Ok(k) => k.claims.subject_id,
+        Err(_) => {
+            return RecipeResponse::Unauthorized(String::from(
+                "Please log in to see your trashed recipes.",
+            ))
+        }
+    };
+
+    let total: i64 = match conn
+        .run(move |c| {
+            recipes::table
+                .inner_join(recipes_users::table)
+                .filter(recipes_users::user_id.eq(user_id))
+                .filter(recipes_users::role.eq(RecipeRole::Owner.as_str()))
+                .filter(recipes::deleted_at.is_not_null())
+                .count()
+                .get_result(c)
+        })
+        .await
+    {
+        Ok(c) => c,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Database error while counting records.",
+            ))
+        }
+    };
+
+    let (current_page, per_page, offset) = pagination(page, per_page, total);
+
+    let recipes_list = match conn
+        .run(move |c| {
+            recipes::table
+                .inner_join(recipes_users::table)
+                .filter(recipes_users::user_id.eq(user_id))
+                .filter(recipes_users::role.eq(RecipeRole::Owner.as_str()))
+                .filter(recipes::deleted_at.is_not_null())
+                .select(Recipe::as_select())
+                .order(recipes::deleted_at.desc())
+                .offset(offset)
+                .limit(per_page)
+                .load::<Recipe>(c)
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(err) => return RecipeResponse::InternalServerError(err.to_string()),
+    };
+
+    match get_recipe_elements(recipes_list, &conn, Some(user_id)).await {
+        Ok(res) => {
+            let paginated = PaginatedResult {
+                records: res,
+                total: Some(total),
+                current_page: Some(current_page),
+                per_page,
+                next_cursor: None,
+                prev_cursor: None,
+            };
+            RecipeResponse::Ok(Json(paginated))
+        }
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read recipes from the database.",
+        )),
+    }
+}
+
+/// Restore recipe
+///
+/// Take a recipe out of the trash. Only the owner can restore it.
+#[utoipa::path(
+    post,
+    path = "/recipes/{recipe_id}/restore",
+    tag = "recipes",
+    responses(
+        (status = 200, description = "Recipe restored succesfully", body = RecipeResultDTO),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Logged in user is not the owner of the recipe"),
+        (status = NOT_FOUND, description = "Recipe was not found in the trash"),
+    ),
+    params(
+        ("recipe_id" = i32, description = "Recipe id", example = 2),
+    ),
+    security(
+        ("name" = ["Bearer"])
+    ),
+)]
+#[post("/recipes/<recipe_id>/restore")]
+pub async fn restore(
+    conn: LogsDbConn,
+    recipe_id: i32,
+    owned: Result<OwnedRecipe, NetworkResponse>,
+    key: Result<Jwt, NetworkResponse>,
+) -> RecipeResponse<RecipeResultDTO> {
+    let recipe = match owned.and_then(|o| o.owner_only("restore")) {
+        Ok(o) => o.recipe,
+        Err(err) => return RecipeResponse::from(err),
+    };
+
+    if recipe.deleted_at.is_none() {
+        return RecipeResponse::NotFound(String::from("The recipe is not in the trash."));
+    }
+
+    let recipes_list = match conn
+        .run(move |c| {
+            diesel::update(recipes::table.find(recipe_id))
+                .set(recipes::deleted_at.eq(None::<chrono::NaiveDateTime>))
+                .get_results::<Recipe>(c)
+        })
+        .await
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return RecipeResponse::InternalServerError(String::from(
+                "Cannot restore the recipe in the database.",
+            ))
+        }
+    };
+
+    let user_id = key.ok().map(|k| k.claims.subject_id);
+
+    match get_recipe_elements(recipes_list, &conn, user_id).await {
+        Ok(res) => match res.first() {
+            Some(r) => RecipeResponse::Ok(Json(r.clone())),
+            None => RecipeResponse::NotFound(String::from("The recipe was not found.")),
+        },
+        Err(_) => RecipeResponse::InternalServerError(String::from(
+            "Cannot read recipes from the database.",
+        )),
+    }
+}
+
+/// Permanently remove recipes that have been in the trash longer than `retention_days`.
+pub fn purge_trash(c: &mut PgConnection, retention_days: i32) -> QueryResult<usize> {
+    diesel::delete(recipes::table)
+        .filter(recipes::deleted_at.lt((diesel::dsl::now - retention_days.days()).nullable()))
+        .execute(c)
+}
+
+/// Fairing that purges the trash once an hour while the server is running.
+pub fn trash_purge_fairing(retention_days: i32) -> AdHoc {
+    AdHoc::on_liftoff("Trash purge", move |rocket| {
+        Box::pin(async move {
+            let pool = match LogsDbConn::pool(rocket) {
+                Some(pool) => pool.clone(),
+                None => {
+                    eprintln!("Trash purge disabled: no database pool.");
+                    return;
+                }
+            };
+
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(Duration::from_secs(60 * 60));
+                loop {
+                    interval.tick().await;
+                    let conn = match pool.get().await {
+                        Some(conn) => conn,
+                        None => continue,
+                    };
+                    match conn.run(move |c| purge_trash(c, retention_days)).await {
+                        Ok(0) => (),
+                        Ok(num) => println!("Purged {} recipes from the trash.", num),
+                        Err(err) => eprintln!("Trash purge failed: {}", err),
+                    }
+                }
+            });
+        })
+    })
+}
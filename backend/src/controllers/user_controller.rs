@@ -3,11 +3,12 @@ use diesel::prelude::*;
 use rocket::serde::json::Json;
 use validator::Validate;
 
-use crate::jwt::*;
 use crate::models::*;
 use crate::schema::users::dsl::*;
 use crate::LogsDbConn;
 
+use super::{create_session, revoke_sessions, token_response};
+
 /// Register a new user
 ///
 /// Create a new user in the database.
@@ -65,16 +66,18 @@ pub async fn register(
 
 /// User login
 ///
-/// Authenticate a user and return a JWT token.
+/// Authenticate a user and start a session for the device.
+/// Returns a short-lived access token and a refresh token for `POST /token/refresh`.
 #[utoipa::path(
     post,
     path = "/login",
     request_body = LoginUser,
     tag = "users",
     responses(
-        (status = 200, description = "User logged in succesfully", body = String),
+        (status = 200, description = "User logged in succesfully", body = TokenResponse),
         (status = 400, description = "Invalid user input"),
         (status = 401, description = "Failed to authorize access"),
+        (status = 403, description = "Account is disabled"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal Server Error"),
     )
@@ -83,15 +86,17 @@ pub async fn register(
 pub async fn login(
     conn: LogsDbConn,
     user: Json<LoginUser>,
-) -> Result<Json<ResponseBody>, NetworkResponse> {
-    let token = login_user(conn, user).await?;
-    Ok(Json(ResponseBody::AuthToken(token)))
+    user_agent: UserAgent,
+) -> Result<Json<TokenResponse>, NetworkResponse> {
+    let tokens = login_user(conn, user, user_agent.0).await?;
+    Ok(Json(tokens))
 }
 
 pub async fn login_user(
     conn: LogsDbConn,
     login_user: Json<LoginUser>,
-) -> Result<String, NetworkResponse> {
+    user_agent: Option<String>,
+) -> Result<TokenResponse, NetworkResponse> {
     login_user
         .validate()
         .map_err(|_err| NetworkResponse::BadRequest("Invalid user input".to_string()))?;
@@ -105,35 +110,36 @@ pub async fn login_user(
         .await;
 
     match result {
-        Ok(user) => {
-            match verify(&login_user.password, &user.password) {
-                Ok(valid) => {
-                    if valid {
-                        // Generate JWT token
-                        match create_jwt(user.id) {
-                            Ok(token) => {
-                                println!("Generated token: {}", token);
-                                Ok(token)
-                            }
-                            Err(err) => {
-                                eprintln!("JWT token generation error: {:?}", err);
-                                Err(NetworkResponse::InternalServerError(
-                                    "Failed to generate JWT token".to_string(),
-                                ))
-                            }
+        Ok(user) if user.disabled => Err(NetworkResponse::Forbidden(
+            "This account has been disabled".to_string(),
+        )),
+        Ok(user) => match verify(&login_user.password, &user.password) {
+            Ok(valid) => {
+                if valid {
+                    let user_id = user.id;
+                    match conn
+                        .run(move |c| create_session(c, user_id, user_agent))
+                        .await
+                    {
+                        Ok((session_id, refresh_token)) => {
+                            token_response(user_id, session_id, refresh_token)
                         }
-                    } else {
-                        Err(NetworkResponse::Unauthorized(
-                            "Failed to authorize access".to_string(),
-                        ))
+                        Err(err) => Err(NetworkResponse::InternalServerError(format!(
+                            "Failed to start a session: {}",
+                            err
+                        ))),
                     }
+                } else {
+                    Err(NetworkResponse::Unauthorized(
+                        "Failed to authorize access".to_string(),
+                    ))
                 }
-                Err(err) => Err(NetworkResponse::InternalServerError(format!(
-                    "Failed to verify password: {}",
-                    err
-                ))),
             }
-        }
+            Err(err) => Err(NetworkResponse::InternalServerError(format!(
+                "Failed to verify password: {}",
+                err
+            ))),
+        },
         Err(err) => Err(NetworkResponse::NotFound(format!(
             "Failed to find user: {}",
             err
@@ -185,6 +191,7 @@ async fn fetch_user_profile(
     match user {
         Ok(user) => Ok(UserProfile {
             username: user.username,
+            role: UserRole::parse(&user.role).unwrap_or_default(),
         }),
         Err(err) => Err(NetworkResponse::NotFound(format!(
             "Failed to find user: {}",
@@ -195,7 +202,9 @@ async fn fetch_user_profile(
 
 /// Change User Password
 ///
-/// Updates the password of the current user.
+/// Updates the password of the current user. This is the only thing a user can do
+/// while an admin requires a password reset, logging in with the temporary password.
+/// Ends all sessions of the user, including the current one, so every device has to log in again.
 #[utoipa::path(
     put,
     path = "/profile/change_password",
@@ -246,9 +255,12 @@ pub async fn change_password(
                 };
                 match conn
                     .run(move |c| {
-                        diesel::update(users.filter(id.eq(user_id)))
-                            .set(password.eq(new_password))
-                            .execute(c)
+                        c.transaction(|c| {
+                            diesel::update(users.filter(id.eq(user_id)))
+                                .set((password.eq(new_password), password_reset_required.eq(false)))
+                                .execute(c)?;
+                            revoke_sessions(c, user_id)
+                        })
                     })
                     .await
                 {
@@ -271,4 +283,4 @@ pub async fn change_password(
             err
         ))),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,74 @@
+//! Minimal iCalendar (RFC 5545) writer for the meal plan export.
+//!
+//! Times are written without time zone ("floating"), a meal at 19:00 stays
+//! at 19:00 in whatever zone the calendar app is in.
+
+use chrono::NaiveDateTime;
+
+pub struct CalendarEvent {
+    /// Globally unique and stable, calendar apps update events by it
+    pub uid: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub summary: String,
+    pub description: String,
+}
+
+/// A complete calendar, `stamp` is the time the calendar was generated
+pub fn calendar(name: &str, events: &[CalendarEvent], stamp: NaiveDateTime) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//Crimson Eagle//Recipe App//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+    for event in events {
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!("UID:{}", escape_text(&event.uid)),
+            format!("DTSTAMP:{}Z", format_time(stamp)),
+            format!("DTSTART:{}", format_time(event.start)),
+            format!("DTEND:{}", format_time(event.end)),
+            format!("SUMMARY:{}", escape_text(&event.summary)),
+            format!("DESCRIPTION:{}", escape_text(&event.description)),
+            String::from("END:VEVENT"),
+        ]);
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines
+        .iter()
+        .map(|l| fold_line(l) + "\r\n")
+        .collect::<String>()
+}
+
+fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// Escape a TEXT value: backslashes, separators and line breaks
+pub fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Split lines longer than 75 bytes, continuation lines start with a space.
+/// Never splits inside a multi-byte character.
+pub fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        // the leading space of a continuation line counts as well
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
@@ -0,0 +1,275 @@
+use crate::models::{IngredientDTO, ParsedIngredientDTO};
+use crate::units::find_unit;
+
+const UNICODE_FRACTIONS: [(char, f32); 15] = [
+    ('½', 1.0 / 2.0),
+    ('⅓', 1.0 / 3.0),
+    ('⅔', 2.0 / 3.0),
+    ('¼', 1.0 / 4.0),
+    ('¾', 3.0 / 4.0),
+    ('⅕', 1.0 / 5.0),
+    ('⅖', 2.0 / 5.0),
+    ('⅗', 3.0 / 5.0),
+    ('⅘', 4.0 / 5.0),
+    ('⅙', 1.0 / 6.0),
+    ('⅚', 5.0 / 6.0),
+    ('⅛', 1.0 / 8.0),
+    ('⅜', 3.0 / 8.0),
+    ('⅝', 5.0 / 8.0),
+    ('⅞', 7.0 / 8.0),
+];
+
+/// "2 to 3", "2 bis 3", "2 à 3", "2 o 3"
+const RANGE_WORDS: [&str; 8] = ["to", "or", "bis", "oder", "à", "a", "ou", "o"];
+
+/// "2 cups of flour", "200 g de farine", "1 kg di farina"
+const CONNECTORS: [&str; 4] = ["of", "de", "di", "von"];
+
+/// Amount phrases that end a line instead of a quantity
+const TO_TASTE: [&str; 10] = [
+    "to taste",
+    "as needed",
+    "nach geschmack",
+    "nach belieben",
+    "au goût",
+    "au gout",
+    "al gusto",
+    "a piacere",
+    "q.b.",
+    "qb",
+];
+
+const BULLETS: [char; 4] = ['-', '*', '•', '·'];
+
+const FRACTION_SLASHES: [char; 2] = ['/', '⁄'];
+
+/// Parse one line of a pasted ingredient list, e.g. "1 1/2 cups all-purpose flour, sifted".
+///
+/// The quantity may be a whole or decimal number (with a point or a comma), a fraction,
+/// a unicode fraction, a mixed number or a range. Parenthesised text, everything after
+/// the first comma and phrases like "to taste" end up in the note.
+pub fn parse_ingredient_line(line: &str) -> Result<ParsedIngredientDTO, String> {
+    let mut notes = Vec::<String>::new();
+
+    let text = line.trim().trim_start_matches(BULLETS).trim_start();
+    let text = extract_parentheses(text, &mut notes);
+    let text = text.trim();
+
+    // quantity
+    let (amount, amount_max, rest) = match parse_quantity(text) {
+        Some((min, max, rest)) => (Some(min), max, rest.trim_start()),
+        None => match split_word(text) {
+            // "a pinch of salt"
+            Some((word, rest)) if ["a", "an"].contains(&word.to_lowercase().as_str()) => {
+                match split_word(rest).and_then(|(w, _)| find_unit(w)) {
+                    Some(_) => (Some(1.0), None, rest),
+                    None => (None, None, text),
+                }
+            }
+            _ => (None, None, text),
+        },
+    };
+
+    // unit, only after an amount or when followed by "of", so "can" or "in" don't eat labels
+    let (unit, rest) = match parse_unit(rest) {
+        Some((unit, after)) => match split_word(after) {
+            Some((word, after_connector)) if CONNECTORS.contains(&word.to_lowercase().as_str()) => {
+                (Some(unit), after_connector)
+            }
+            _ if amount.is_some() => (Some(unit), after),
+            _ => (None, rest),
+        },
+        None => (None, rest),
+    };
+
+    // note after the first comma
+    let (label, comma_note) = match rest.split_once(',') {
+        Some((label, note)) => (label, Some(note.trim())),
+        None => (rest, None),
+    };
+    let mut label = label.trim();
+
+    // "salt to taste"
+    let lowercase = label.to_lowercase();
+    if let Some(phrase) = TO_TASTE.iter().find(|p| {
+        lowercase.ends_with(&format!(" {}", p)) && label.is_char_boundary(label.len() - p.len())
+    }) {
+        let split = label.len() - phrase.len();
+        notes.push(String::from(label[split..].trim()));
+        label = label[..split].trim();
+    }
+    if let Some(note) = comma_note.filter(|n| !n.is_empty()) {
+        notes.push(String::from(note));
+    }
+
+    // French elision, "100 g d'huile"
+    let label = label
+        .strip_prefix("d'")
+        .or_else(|| label.strip_prefix("d’"))
+        .unwrap_or(label);
+    let label = label.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if label.is_empty() {
+        return Err(format!("No ingredient found in \"{}\".", line.trim()));
+    }
+
+    Ok(ParsedIngredientDTO {
+        line: String::from(line.trim()),
+        ingredient: IngredientDTO {
+            unit,
+            label,
+            amount,
+        },
+        amount_max,
+        note: match notes.is_empty() {
+            true => None,
+            false => Some(notes.join(", ")),
+        },
+    })
+}
+
+/// Parse every non-empty line, the error names the first line that could not be parsed.
+pub fn parse_ingredient_lines(lines: &[String]) -> Result<Vec<ParsedIngredientDTO>, String> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            parse_ingredient_line(line).map_err(|err| format!("Line {}: {}", n + 1, err))
+        })
+        .collect()
+}
+
+/// Move the content of every (...) into `notes`, return the text without them
+fn extract_parentheses(text: &str, notes: &mut Vec<String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let close = match rest[open..].find(')') {
+            Some(close) => open + close,
+            None => break,
+        };
+        result.push_str(&rest[..open]);
+        result.push(' ');
+        let note = rest[open + 1..close].trim();
+        if !note.is_empty() {
+            notes.push(String::from(note));
+        }
+        rest = &rest[close + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Amount with an optional upper bound: "2", "2-3", "2 to 3"
+fn parse_quantity(text: &str) -> Option<(f32, Option<f32>, &str)> {
+    let (min, rest) = parse_number(text)?;
+
+    let after = rest.trim_start();
+    if let Some(r) = after.strip_prefix(['-', '–', '—']) {
+        if let Some((max, r)) = parse_number(r.trim_start()) {
+            return Some((min, Some(max), r));
+        }
+    }
+    if let Some((word, r)) = split_word(after) {
+        if RANGE_WORDS.contains(&word.to_lowercase().as_str()) {
+            if let Some((max, r)) = parse_number(r) {
+                return Some((min, Some(max), r));
+            }
+        }
+    }
+
+    Some((min, None, rest))
+}
+
+/// A single number at the start of `text`: "2", "1.5", "1,5", "3/4", "½", "1½", "1 ½", "1 1/2"
+fn parse_number(text: &str) -> Option<(f32, &str)> {
+    if let Some(res) = unicode_fraction(text) {
+        return Some(res);
+    }
+
+    let (whole, rest) = digits(text)?;
+    let value: f32 = whole.parse().ok()?;
+
+    // fraction
+    if let Some(r) = rest.strip_prefix(FRACTION_SLASHES) {
+        if let Some((denominator, r)) = digits(r) {
+            let denominator: f32 = denominator.parse().ok()?;
+            if denominator == 0.0 {
+                return None;
+            }
+            return Some((value / denominator, r));
+        }
+    }
+
+    // decimal point or comma
+    if let Some(r) = rest.strip_prefix(['.', ',']) {
+        if let Some((decimals, r)) = digits(r) {
+            let value: f32 = format!("{}.{}", whole, decimals).parse().ok()?;
+            return Some((value, r));
+        }
+    }
+
+    // mixed number
+    let trimmed = rest.trim_start();
+    if let Some((fraction, r)) = unicode_fraction(trimmed) {
+        return Some((value + fraction, r));
+    }
+    if trimmed.len() < rest.len() {
+        if let Some((numerator, r)) = digits(trimmed) {
+            if let Some(r) = r.strip_prefix(FRACTION_SLASHES) {
+                if let Some((denominator, r)) = digits(r) {
+                    let numerator: f32 = numerator.parse().ok()?;
+                    let denominator: f32 = denominator.parse().ok()?;
+                    if denominator != 0.0 {
+                        return Some((value + numerator / denominator, r));
+                    }
+                }
+            }
+        }
+    }
+
+    Some((value, rest))
+}
+
+fn unicode_fraction(text: &str) -> Option<(f32, &str)> {
+    let first = text.chars().next()?;
+    UNICODE_FRACTIONS
+        .iter()
+        .find(|(c, _)| *c == first)
+        .map(|(c, value)| (*value, &text[c.len_utf8()..]))
+}
+
+/// Leading ASCII digits and the rest of the text
+fn digits(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    match end {
+        0 => None,
+        _ => Some(text.split_at(end)),
+    }
+}
+
+/// First whitespace separated word and the rest of the text
+fn split_word(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    match text.find(char::is_whitespace) {
+        Some(end) => Some((&text[..end], text[end..].trim_start())),
+        None => Some((text, "")),
+    }
+}
+
+/// Known unit at the start of `text`, also when glued to the number ("500g")
+/// or written in two words ("fl oz")
+pub(crate) fn parse_unit(text: &str) -> Option<(String, &str)> {
+    let (first, rest) = split_word(text)?;
+    if let Some((second, after)) = split_word(rest) {
+        if let Some(unit) = find_unit(&format!("{} {}", first, second)) {
+            return Some((String::from(unit.symbol), after));
+        }
+    }
+    find_unit(first).map(|unit| (String::from(unit.symbol), rest))
+}
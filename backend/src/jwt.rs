@@ -1,41 +1,309 @@
 use crate::models::*;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
 use chrono::Utc;
 use jsonwebtoken::errors::{Error, ErrorKind};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters,
+    OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use lazy_static::lazy_static;
+use rand::distributions::{Alphanumeric, DistString};
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 
-pub fn create_jwt(id: i32) -> Result<String, Error> {
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set.");
+/// Keys that sign and verify tokens
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    key_id: String,
+    encoding: EncodingKey,
+    /// Every key a token may be signed with, by key id
+    decoding: HashMap<String, (Algorithm, DecodingKey)>,
+    /// Public keys for other services, empty with a shared secret
+    public: JwkSet,
+}
+
+lazy_static! {
+    static ref KEYS: JwtKeys = JwtKeys::from_env().unwrap_or_else(|err| panic!("{}", err));
+}
+
+/// Read the keys now rather than on the first login, so a bad configuration stops the start.
+pub fn load_keys() {
+    lazy_static::initialize(&KEYS);
+}
+
+/// Public keys of the `/.well-known/jwks.json` document
+pub fn public_keys() -> &'static JwkSet {
+    KEYS.public_keys()
+}
+
+impl JwtKeys {
+    /// `JWT_ALGORITHM` picks the signing algorithm:
+    /// - `HS512`, the default, signs with the shared `JWT_SECRET`
+    /// - `RS256` and `EdDSA` sign with the PEM private key in the file `JWT_PRIVATE_KEY`
+    ///
+    /// `JWT_KEY_ID` names the signing key, by default its JWK thumbprint (RFC 7638).
+    /// `JWT_VERIFICATION_KEYS` is a JWK set file of retired public keys whose tokens stay valid,
+    /// so a new signing key does not log everyone out.
+    fn from_env() -> Result<JwtKeys, String> {
+        let key_id = env::var("JWT_KEY_ID").ok();
+        let keys = match env::var("JWT_ALGORITHM").as_deref() {
+            Err(_) | Ok("HS512") => {
+                let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set.")?;
+                JwtKeys::with_secret(&secret, key_id)
+            }
+            Ok(name @ ("RS256" | "EdDSA")) => {
+                let algorithm = if name == "RS256" {
+                    Algorithm::RS256
+                } else {
+                    Algorithm::EdDSA
+                };
+                let path = env::var("JWT_PRIVATE_KEY")
+                    .map_err(|_| "JWT_PRIVATE_KEY must be set for asymmetric signing.")?;
+                let pem = fs::read_to_string(&path)
+                    .map_err(|err| format!("Cannot read JWT_PRIVATE_KEY {}: {}", path, err))?;
+                JwtKeys::with_private_key(algorithm, &pem, key_id)
+                    .map_err(|err| format!("Invalid JWT_PRIVATE_KEY {}: {}", path, err))?
+            }
+            Ok(other) => {
+                return Err(format!(
+                    "JWT_ALGORITHM must be HS512, RS256 or EdDSA, not {}.",
+                    other
+                ))
+            }
+        };
+
+        match env::var("JWT_VERIFICATION_KEYS") {
+            Ok(path) => {
+                let json = fs::read_to_string(&path).map_err(|err| {
+                    format!("Cannot read JWT_VERIFICATION_KEYS {}: {}", path, err)
+                })?;
+                serde_json::from_str::<JwkSet>(&json)
+                    .map_err(|err| err.to_string())
+                    .and_then(|set| keys.with_verification_keys(set))
+                    .map_err(|err| format!("Invalid JWT_VERIFICATION_KEYS {}: {}", path, err))
+            }
+            Err(_) => Ok(keys),
+        }
+    }
+
+    /// HS512 keys from a shared secret, named `default` unless a key id is given.
+    /// Nothing is published, other services would need the secret itself.
+    pub fn with_secret(secret: &str, key_id: Option<String>) -> JwtKeys {
+        let key_id = key_id.unwrap_or_else(|| String::from("default"));
+        let decoding = DecodingKey::from_secret(secret.as_bytes());
+        JwtKeys {
+            algorithm: Algorithm::HS512,
+            key_id: key_id.clone(),
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: HashMap::from([(key_id, (Algorithm::HS512, decoding))]),
+            public: JwkSet { keys: Vec::new() },
+        }
+    }
+
+    /// RS256 or EdDSA keys from a PEM private key, named by its JWK thumbprint
+    /// unless a key id is given
+    pub fn with_private_key(
+        algorithm: Algorithm,
+        pem: &str,
+        key_id: Option<String>,
+    ) -> Result<JwtKeys, String> {
+        let (encoding, mut jwk, thumbprint) = private_key(algorithm, pem)?;
+        let key_id = key_id.unwrap_or(thumbprint);
+        jwk.common.key_id = Some(key_id.clone());
+        let decoding = DecodingKey::from_jwk(&jwk).map_err(|err| err.to_string())?;
+        Ok(JwtKeys {
+            algorithm,
+            key_id: key_id.clone(),
+            encoding,
+            decoding: HashMap::from([(key_id, (algorithm, decoding))]),
+            public: JwkSet { keys: vec![jwk] },
+        })
+    }
+
+    /// Also accept tokens signed by these retired public keys, and publish them.
+    /// Every key needs a `kid`, the signing key wins over a retired copy of itself.
+    pub fn with_verification_keys(mut self, set: JwkSet) -> Result<JwtKeys, String> {
+        for jwk in set.keys {
+            let kid = jwk.common.key_id.clone().ok_or("every key needs a kid")?;
+            let algorithm = match (&jwk.algorithm, jwk.common.algorithm) {
+                (AlgorithmParameters::RSA(_), None | Some(Algorithm::RS256)) => Algorithm::RS256,
+                (AlgorithmParameters::OctetKeyPair(_), None | Some(Algorithm::EdDSA)) => {
+                    Algorithm::EdDSA
+                }
+                _ => return Err(format!("key {} must be an RS256 or EdDSA key", kid)),
+            };
+            if self.decoding.contains_key(&kid) {
+                continue;
+            }
+            let key = DecodingKey::from_jwk(&jwk).map_err(|err| format!("key {}: {}", kid, err))?;
+            self.decoding.insert(kid, (algorithm, key));
+            self.public.keys.push(jwk);
+        }
+        Ok(self)
+    }
+
+    /// Public keys other services verify tokens with, the signing key first
+    pub fn public_keys(&self) -> &JwkSet {
+        &self.public
+    }
+
+    /// Token for the claims, signed with the current key and naming it in the `kid` header
+    pub fn sign(&self, claims: &Claims) -> Result<String, Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.key_id.clone());
+        encode(&header, claims, &self.encoding)
+    }
+
+    /// Verify a token with the key its `kid` names. The algorithm comes from the key,
+    /// never from the token, so a token cannot pick a weaker check for itself.
+    /// Tokens without a `kid` are from before key ids and were signed with the shared secret.
+    pub fn verify(&self, token: &str) -> Result<Claims, ErrorKind> {
+        let kid = match decode_header(token)
+            .map_err(|err| err.kind().to_owned())?
+            .kid
+        {
+            Some(kid) => kid,
+            None if self.algorithm == Algorithm::HS512 => self.key_id.clone(),
+            None => return Err(ErrorKind::InvalidToken),
+        };
+        let (algorithm, key) = self.decoding.get(&kid).ok_or(ErrorKind::InvalidToken)?;
 
+        match decode::<Claims>(token, key, &Validation::new(*algorithm)) {
+            Ok(token) => Ok(token.claims),
+            Err(err) => Err(err.kind().to_owned()),
+        }
+    }
+}
+
+/// Signing key, public JWK and JWK thumbprint (RFC 7638) of a PEM encoded
+/// RSA (PKCS#1 or PKCS#8) or Ed25519 (PKCS#8) private key
+pub fn private_key(algorithm: Algorithm, pem: &str) -> Result<(EncodingKey, Jwk, String), String> {
+    let (label, der) = read_pem(pem)?;
+    let (parameters, members, encoding) = match (algorithm, label.as_str()) {
+        (Algorithm::RS256, "PRIVATE KEY" | "RSA PRIVATE KEY") => {
+            let pair = if label == "PRIVATE KEY" {
+                RsaKeyPair::from_pkcs8(&der)
+            } else {
+                RsaKeyPair::from_der(&der)
+            }
+            .map_err(|err| format!("not a usable RSA key ({})", err))?;
+            let public = pair.public_key();
+            let n = URL_SAFE_NO_PAD.encode(public.modulus().big_endian_without_leading_zero());
+            let e = URL_SAFE_NO_PAD.encode(public.exponent().big_endian_without_leading_zero());
+            // required members in lexical order
+            let members = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+            let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n,
+                e,
+            });
+            let encoding = EncodingKey::from_rsa_pem(pem.as_bytes());
+            (parameters, members, encoding)
+        }
+        (Algorithm::EdDSA, "PRIVATE KEY") => {
+            let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+                .map_err(|err| format!("not an Ed25519 key ({})", err))?;
+            let x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+            let members = format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x);
+            let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            });
+            let encoding = EncodingKey::from_ed_pem(pem.as_bytes());
+            (parameters, members, encoding)
+        }
+        _ => {
+            return Err(format!(
+                "{} is not a private key for {:?}",
+                label, algorithm
+            ))
+        }
+    };
+
+    let encoding = encoding.map_err(|err| err.to_string())?;
+    let thumbprint = URL_SAFE_NO_PAD.encode(digest(&SHA256, members.as_bytes()));
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            algorithm: Some(algorithm),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    };
+    Ok((encoding, jwk, thumbprint))
+}
+
+/// Label and DER contents of the first block of a PEM file
+fn read_pem(pem: &str) -> Result<(String, Vec<u8>), String> {
+    let mut lines = pem.lines().map(str::trim).filter(|l| !l.is_empty());
+    let label = lines
+        .next()
+        .and_then(|l| l.strip_prefix("-----BEGIN "))
+        .and_then(|l| l.strip_suffix("-----"))
+        .ok_or("not a PEM file")?;
+    let body = lines
+        .take_while(|l| !l.starts_with("-----END "))
+        .collect::<String>();
+    let der = STANDARD
+        .decode(body)
+        .map_err(|err| format!("broken PEM contents ({})", err))?;
+    Ok((String::from(label), der))
+}
+
+/// Lifetime of access tokens, `ACCESS_TOKEN_MINUTES` or 15 minutes
+pub fn access_token_minutes() -> i64 {
+    env::var("ACCESS_TOKEN_MINUTES")
+        .ok()
+        .and_then(|m| m.parse().ok())
+        .unwrap_or(15)
+}
+
+/// How long a session lasts without a refresh, `REFRESH_TOKEN_DAYS` or 30 days
+pub fn refresh_token_days() -> i64 {
+    env::var("REFRESH_TOKEN_DAYS")
+        .ok()
+        .and_then(|d| d.parse().ok())
+        .unwrap_or(30)
+}
+
+pub fn create_jwt(id: i32, session_id: i32) -> Result<String, Error> {
     let expiration = Utc::now()
-        .checked_add_signed(chrono::Duration::hours(6))
+        .checked_add_signed(chrono::Duration::minutes(access_token_minutes()))
         .expect("Invalid timestamp")
         .timestamp();
 
     let claims = Claims {
         subject_id: id,
+        session_id,
         exp: expiration as usize,
     };
 
-    let header = Header::new(Algorithm::HS512);
-
-    encode(
-        &header,
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
+    KEYS.sign(&claims)
 }
 
 pub fn decode_jwt(token: String) -> Result<Claims, ErrorKind> {
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set.");
-    let token = token.trim_start_matches("Bearer").trim();
-
-    match decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::new(Algorithm::HS512),
-    ) {
-        Ok(token) => Ok(token.claims),
-        Err(err) => Err(err.kind().to_owned()),
-    }
+    KEYS.verify(token.trim_start_matches("Bearer").trim())
+}
+
+/// New random refresh token, the client gets it once and the database only keeps its hash
+pub fn create_refresh_token() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 48)
+}
+
+/// Hex encoded SHA-256 of a refresh token. Refresh tokens are long and random,
+/// so a fast hash is enough and lets the database look them up.
+pub fn hash_refresh_token(token: &str) -> String {
+    digest(&SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
@@ -11,14 +11,25 @@ use rocket_sync_db_pools::{database, diesel};
 
 mod controllers;
 use controllers::{
-    bookmark_controller, recipe_controller, recipe_create_controller, recipe_update_controller,
-    tag_controller, user_controller,
+    admin_controller, bookmark_controller, collaborator_controller, collection_controller,
+    comment_controller, fork_controller, ingredient_controller, meal_plan_controller,
+    pantry_controller, rating_controller, recipe_controller, recipe_create_controller,
+    recipe_filter_controller, recipe_update_controller, revision_controller, session_controller,
+    shopping_list_controller, tag_controller, trash_controller, user_controller,
 };
 
 mod apidoc;
+mod ical;
+mod ingredient_parser;
 mod jwt;
 mod models;
+mod nutrient_import;
+mod nutrition;
+mod pantry;
+mod revisions;
 mod schema;
+mod shopping;
+mod units;
 
 #[cfg(test)]
 mod tests;
@@ -26,10 +37,45 @@ mod tests;
 #[database("postgres_logs")]
 pub struct LogsDbConn(diesel::PgConnection);
 
-#[launch]
+/// Serves the API, or runs a maintenance command:
+/// `import-nutrients <directory>` loads ingredient nutrients from a USDA FoodData Central download
+/// `grant-admin <username>` gives a registered user the admin role
+fn main() {
+    dotenv().ok();
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    match args.first().map(String::as_str) {
+        Some("import-nutrients") => match nutrient_import::run(&args[1..]) {
+            Ok(report) => println!("{}", report),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        Some("grant-admin") => match admin_controller::grant_admin_command(&args[1..]) {
+            Ok(report) => println!("{}", report),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            if let Err(err) = ::rocket::async_main(async { rocket().await.launch().await }) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
 async fn rocket() -> Rocket<Build> {
     dotenv().ok();
+    jwt::load_keys();
     let frontend_url = env::var("FRONTEND_URL").expect("FRONTEND_URL must be set");
+    let trash_retention_days: i32 = env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(30);
+    let admin_username = env::var("ADMIN_USERNAME").ok();
 
     let allowed_origins = AllowedOrigins::some_exact(&[
         frontend_url,
@@ -62,18 +108,85 @@ async fn rocket() -> Rocket<Build> {
         .mount(
             "/",
             routes![
+                admin_controller::admin_users,
+                admin_controller::update_user_role,
+                admin_controller::disable_user,
+                admin_controller::enable_user,
+                admin_controller::reset_user_password,
+                admin_controller::admin_delete_recipe,
+                admin_controller::admin_restore_recipe,
+                admin_controller::rename_tag,
+                admin_controller::delete_tag,
                 bookmark_controller::bookmarked_list,
                 bookmark_controller::toggle_bookmark,
                 recipe_controller::recipe,
                 recipe_controller::search,
+                recipe_filter_controller::filter_recipes,
                 recipe_controller::single_recipe,
                 recipe_create_controller::create_recipe,
                 recipe_update_controller::update_recipe,
                 recipe_controller::delete,
+                ingredient_controller::ingredient_list,
+                ingredient_controller::merge_ingredient,
+                ingredient_controller::add_ingredient_synonym,
+                ingredient_controller::parse_ingredients,
+                shopping_list_controller::shopping_list,
+                shopping_list_controller::export_shopping_list,
+                shopping_list_controller::add_recipes_to_shopping_list,
+                shopping_list_controller::add_shopping_item,
+                shopping_list_controller::update_shopping_item,
+                shopping_list_controller::delete_shopping_item,
+                shopping_list_controller::clear_shopping_list,
+                meal_plan_controller::meal_plan,
+                meal_plan_controller::single_meal,
+                meal_plan_controller::plan_meal,
+                meal_plan_controller::update_meal,
+                meal_plan_controller::delete_meal,
+                meal_plan_controller::export_meal_plan,
+                meal_plan_controller::create_meal_plan_feed,
+                meal_plan_controller::delete_meal_plan_feed,
+                pantry_controller::pantry,
+                pantry_controller::add_pantry_item,
+                pantry_controller::update_pantry_item,
+                pantry_controller::delete_pantry_item,
+                pantry_controller::cookable_recipes,
+                rating_controller::rate_recipe,
+                rating_controller::delete_rating,
+                rating_controller::recipe_reviews,
+                comment_controller::recipe_comments,
+                comment_controller::add_comment,
+                comment_controller::update_comment,
+                comment_controller::delete_comment,
+                revision_controller::list_revisions,
+                revision_controller::single_revision,
+                revision_controller::revision_diff,
+                revision_controller::revert_recipe,
+                fork_controller::fork_recipe,
+                fork_controller::recipe_forks,
+                collaborator_controller::collaborators,
+                collaborator_controller::invite_collaborator,
+                collaborator_controller::update_collaborator,
+                collaborator_controller::remove_collaborator,
+                collaborator_controller::transfer_ownership,
+                collection_controller::user_collections,
+                collection_controller::add_collection,
+                collection_controller::rename_collection,
+                collection_controller::delete_collection,
+                collection_controller::add_collection_recipe,
+                collection_controller::remove_collection_recipe,
+                collection_controller::reorder_collection,
                 tag_controller::tag_list,
                 tag_controller::single_tag,
                 tag_controller::create_tag,
                 tag_controller::toggle_tag,
+                trash_controller::trash_list,
+                trash_controller::restore,
+                session_controller::refresh_token,
+                session_controller::logout,
+                session_controller::user_sessions,
+                session_controller::revoke_session,
+                session_controller::revoke_other_sessions,
+                session_controller::jwks,
                 user_controller::login,
                 user_controller::register,
                 user_controller::profile,
@@ -82,4 +195,6 @@ async fn rocket() -> Rocket<Build> {
             ],
         )
         .attach(cors)
+        .attach(trash_controller::trash_purge_fairing(trash_retention_days))
+        .attach(admin_controller::admin_bootstrap_fairing(admin_username))
 }
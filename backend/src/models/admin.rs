@@ -0,0 +1,99 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{Jwt, NetworkResponse, User};
+
+/// What a user may do across the whole app
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum UserRole {
+    #[default]
+    User,
+    /// Manages users, tags, the ingredient catalogue and any recipe
+    Admin,
+}
+
+impl UserRole {
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::User => "user",
+            UserRole::Admin => "admin",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<UserRole> {
+        [UserRole::User, UserRole::Admin]
+            .into_iter()
+            .find(|r| r.as_str() == name)
+    }
+}
+
+/// Logged in user with the admin role
+#[derive(Debug)]
+pub struct AdminUser {
+    pub user_id: i32,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = NetworkResponse;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, NetworkResponse> {
+        match req.guard::<Jwt>().await {
+            Outcome::Success(key) if key.role == UserRole::Admin => Outcome::Success(AdminUser {
+                user_id: key.claims.subject_id,
+            }),
+            Outcome::Success(_) => Outcome::Failure((
+                Status::Forbidden,
+                NetworkResponse::Forbidden(String::from("Only admins can do this.")),
+            )),
+            Outcome::Failure(f) => Outcome::Failure(f),
+            Outcome::Forward(f) => Outcome::Forward(f),
+        }
+    }
+}
+
+/// User account as admins see it
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminUserDTO {
+    #[schema(example = 3)]
+    pub id: i32,
+    #[schema(example = "jane")]
+    pub username: String,
+    pub role: UserRole,
+    /// Disabled accounts cannot log in
+    pub disabled: bool,
+    /// The user has to change the password before doing anything else
+    pub password_reset_required: bool,
+}
+
+impl From<User> for AdminUserDTO {
+    fn from(u: User) -> Self {
+        Self {
+            id: u.id,
+            username: u.username,
+            role: UserRole::parse(&u.role).unwrap_or_default(),
+            disabled: u.disabled,
+            password_reset_required: u.password_reset_required,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct UserRolePutDTO {
+    pub role: UserRole,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PasswordResetDTO {
+    /// One-time password to hand to the user, it only allows changing the password
+    #[schema(example = "k3Xa9pQw2mZt")]
+    pub temporary_password: String,
+}
@@ -0,0 +1,71 @@
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// What a user attached to a recipe may do with it
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum RecipeRole {
+    /// Edits and deletes the recipe and manages its collaborators, one per recipe
+    Owner,
+    /// Edits the recipe
+    Editor,
+    /// Reads the recipe, even when it is private
+    Viewer,
+}
+
+impl RecipeRole {
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecipeRole::Owner => "owner",
+            RecipeRole::Editor => "editor",
+            RecipeRole::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<RecipeRole> {
+        [RecipeRole::Owner, RecipeRole::Editor, RecipeRole::Viewer]
+            .into_iter()
+            .find(|r| r.as_str() == name)
+    }
+
+    pub fn can_edit(&self) -> bool {
+        matches!(self, RecipeRole::Owner | RecipeRole::Editor)
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CollaboratorDTO {
+    #[schema(example = "jane")]
+    pub username: String,
+    pub role: RecipeRole,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CollaboratorPostDTO {
+    #[schema(example = "jane")]
+    #[validate(length(min = 3, max = 255))]
+    pub username: String,
+    /// editor or viewer, ownership is handed over with a transfer
+    pub role: RecipeRole,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CollaboratorPutDTO {
+    /// editor or viewer
+    pub role: RecipeRole,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct OwnershipTransferDTO {
+    /// The new owner, the current owner stays on as an editor
+    #[schema(example = "jane")]
+    #[validate(length(min = 3, max = 255))]
+    pub username: String,
+}
@@ -0,0 +1,106 @@
+use crate::models::{Recipe, User};
+use crate::schema::*;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Clone, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = collections)]
+pub struct Collection {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub is_default: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug)]
+#[diesel(belongs_to(Collection))]
+#[diesel(belongs_to(Recipe))]
+#[diesel(table_name = collection_recipes)]
+#[diesel(primary_key(collection_id, recipe_id))]
+pub struct CollectionRecipe {
+    pub collection_id: i32,
+    pub recipe_id: i32,
+    pub position: i32,
+    pub added_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CollectionDTO {
+    #[schema(example = 4)]
+    pub id: i32,
+    #[schema(example = "Weeknight")]
+    pub name: String,
+    /// Toggled bookmarks are put into the default collection
+    #[schema(example = false)]
+    pub is_default: bool,
+    /// Number of recipes in the collection, recipes in the trash are not counted
+    #[schema(example = 12)]
+    pub recipe_count: i64,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<(Collection, i64)> for CollectionDTO {
+    fn from(c: (Collection, i64)) -> Self {
+        Self {
+            id: c.0.id,
+            name: c.0.name,
+            is_default: c.0.is_default,
+            recipe_count: c.1,
+            created_at: c.0.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CollectionPostDTO {
+    #[schema(example = "Weeknight")]
+    #[validate(length(min = 1, max = 120))]
+    pub name: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CollectionPutDTO {
+    #[schema(example = "Christmas")]
+    #[validate(length(min = 1, max = 120))]
+    pub name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CollectionOrderDTO {
+    /// All recipes of the collection the user can see, in their new order
+    #[schema(example = json!(vec![12, 3, 7]))]
+    pub recipe_ids: Vec<i32>,
+}
+
+impl CollectionOrderDTO {
+    /// New order of a whole collection, from its recipe ids in their current order with
+    /// whether the user can still see them. The listed recipes come first, hidden ones keep
+    /// their order after them. `None` unless every visible recipe is listed once.
+    pub fn arranged(self, current: &[(i32, bool)]) -> Option<Vec<i32>> {
+        let mut visible = current
+            .iter()
+            .filter(|(_, visible)| *visible)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut given = self.recipe_ids.clone();
+        visible.sort_unstable();
+        given.sort_unstable();
+        if visible != given {
+            return None;
+        }
+        let hidden = current
+            .iter()
+            .filter(|(_, visible)| !*visible)
+            .map(|(id, _)| *id);
+        Some(self.recipe_ids.into_iter().chain(hidden).collect())
+    }
+}
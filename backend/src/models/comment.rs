@@ -0,0 +1,84 @@
+use crate::models::{Recipe, User};
+use crate::schema::*;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Recipe))]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = comments)]
+pub struct Comment {
+    pub id: i32,
+    pub recipe_id: i32,
+    pub user_id: i32,
+    pub parent_id: Option<i32>,
+    pub body: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = comments)]
+pub struct CommentInsert {
+    pub recipe_id: i32,
+    pub user_id: i32,
+    pub parent_id: Option<i32>,
+    pub body: String,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CommentDTO {
+    #[schema(example = 12)]
+    pub id: i32,
+    /// Comment that this one replies to
+    #[schema(example = json!(None::<i32>))]
+    pub parent_id: Option<i32>,
+    /// Author of the comment
+    #[schema(example = "johndoe")]
+    pub username: String,
+    #[schema(example = "Can I use spelt flour instead?")]
+    pub body: String,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+    /// Time of the last edit
+    #[schema(example = json!(None::<chrono::NaiveDateTime>))]
+    pub updated_at: Option<chrono::NaiveDateTime>,
+    /// Replies, oldest first. Replies cannot be replied to, so theirs is always empty.
+    pub replies: Vec<CommentDTO>,
+}
+
+impl From<(Comment, User)> for CommentDTO {
+    fn from(c: (Comment, User)) -> Self {
+        Self {
+            id: c.0.id,
+            parent_id: c.0.parent_id,
+            username: c.1.username,
+            body: c.0.body,
+            created_at: c.0.created_at,
+            updated_at: c.0.updated_at,
+            replies: Vec::<CommentDTO>::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CommentPostDTO {
+    #[schema(example = "Can I use spelt flour instead?")]
+    #[validate(length(min = 1, max = 5000))]
+    pub body: String,
+    /// Top-level comment to reply to
+    #[schema(example = json!(None::<i32>))]
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CommentPutDTO {
+    #[schema(example = "Can I use spelt or rye flour instead?")]
+    #[validate(length(min = 1, max = 5000))]
+    pub body: String,
+}
@@ -0,0 +1,126 @@
+use crate::models::RecipeResultDTO;
+use crate::schema::*;
+use chrono::{NaiveDate, NaiveTime};
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug)]
+#[diesel(table_name = meal_plan_entries)]
+pub struct MealPlanEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub recipe_id: i32,
+    pub date: NaiveDate,
+    pub slot: String,
+    pub servings: i16,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = meal_plan_entries)]
+pub struct MealPlanEntryInsert {
+    pub user_id: i32,
+    pub recipe_id: i32,
+    pub date: NaiveDate,
+    pub slot: String,
+    pub servings: i16,
+}
+
+/// Meal of the day a recipe is planned for
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum MealSlot {
+    Breakfast,
+    Lunch,
+    Dinner,
+    Snack,
+}
+
+impl MealSlot {
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MealSlot::Breakfast => "breakfast",
+            MealSlot::Lunch => "lunch",
+            MealSlot::Dinner => "dinner",
+            MealSlot::Snack => "snack",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<MealSlot> {
+        [
+            MealSlot::Breakfast,
+            MealSlot::Lunch,
+            MealSlot::Dinner,
+            MealSlot::Snack,
+        ]
+        .into_iter()
+        .find(|s| s.as_str() == name)
+    }
+
+    /// Time of day the meal is put in the calendar, also orders the meals of a day
+    pub fn time(&self) -> NaiveTime {
+        let (hour, minute) = match self {
+            MealSlot::Breakfast => (8, 0),
+            MealSlot::Lunch => (12, 30),
+            MealSlot::Snack => (16, 0),
+            MealSlot::Dinner => (19, 0),
+        };
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MealPlanEntryDTO {
+    #[schema(example = 4)]
+    pub id: i32,
+    #[schema(example = "2023-07-26", value_type = String, format = Date)]
+    pub date: NaiveDate,
+    pub slot: MealSlot,
+    #[schema(example = 2)]
+    pub servings: i16,
+    /// The recipe, scaled to the planned servings
+    pub recipe: RecipeResultDTO,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct MealPlanPostDTO {
+    #[schema(example = "2023-07-26", value_type = String, format = Date)]
+    pub date: NaiveDate,
+    pub slot: MealSlot,
+    #[schema(example = 2)]
+    pub recipe_id: i32,
+    /// Defaults to the servings of the recipe
+    #[schema(example = 2)]
+    #[validate(range(min = 1, max = 1000))]
+    pub servings: Option<i16>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct MealPlanPutDTO {
+    #[schema(example = "2023-07-27", value_type = Option<String>, format = Date)]
+    pub date: Option<NaiveDate>,
+    pub slot: Option<MealSlot>,
+    #[schema(example = 3)]
+    pub recipe_id: Option<i32>,
+    #[schema(example = 4)]
+    #[validate(range(min = 1, max = 1000))]
+    pub servings: Option<i16>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MealPlanFeedDTO {
+    /// Secret of the subscription url, anyone who knows it can read the meal plan
+    #[schema(example = "p0TuQ2yWv8a3J5kzXc7HbN1mLr4sEd6f")]
+    pub token: String,
+    /// Path of the calendar to subscribe to, relative to the api
+    #[schema(example = "/mealplan.ics?token=p0TuQ2yWv8a3J5kzXc7HbN1mLr4sEd6f")]
+    pub path: String,
+}
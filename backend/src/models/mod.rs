@@ -1,5 +1,18 @@
+pub mod admin;
+pub mod collaborator;
+pub mod collection;
+pub mod comment;
+pub mod meal_plan;
+pub mod pantry;
+pub mod rating;
 pub mod recipe;
 pub mod recipe_dto;
+pub mod revision;
+pub mod session;
+pub mod shopping_list;
 pub mod user;
 
-pub use self::{recipe::*, recipe_dto::*, user::*};
+pub use self::{
+    admin::*, collaborator::*, collection::*, comment::*, meal_plan::*, pantry::*, rating::*,
+    recipe::*, recipe_dto::*, revision::*, session::*, shopping_list::*, user::*,
+};
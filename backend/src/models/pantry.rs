@@ -0,0 +1,134 @@
+use crate::models::{nullable, IngredientDTO, RecipeResultDTO};
+use crate::schema::*;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug)]
+#[diesel(table_name = pantry_items)]
+pub struct PantryItem {
+    pub id: i32,
+    pub user_id: i32,
+    pub ingredient_id: Option<i32>,
+    pub label: String,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = pantry_items)]
+pub struct PantryItemInsert {
+    pub user_id: i32,
+    pub ingredient_id: Option<i32>,
+    pub label: String,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PantryItemDTO {
+    #[schema(example = 7)]
+    pub id: i32,
+    /// Catalogue ingredient, `None` for items that are not in the catalogue
+    #[schema(example = 3)]
+    pub ingredient_id: Option<i32>,
+    #[schema(example = "flour")]
+    pub label: String,
+    #[schema(example = 1.5)]
+    pub amount: Option<f32>,
+    #[schema(example = "kg")]
+    pub unit: Option<String>,
+    #[schema(example = "2023-08-31", value_type = Option<String>, format = Date)]
+    pub expires_on: Option<NaiveDate>,
+}
+
+impl From<PantryItem> for PantryItemDTO {
+    fn from(p: PantryItem) -> Self {
+        Self {
+            id: p.id,
+            ingredient_id: p.ingredient_id,
+            label: p.label,
+            amount: p.amount,
+            unit: p.unit,
+            expires_on: p.expires_on,
+        }
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct PantryItemPostDTO {
+    /// Ingredient name, matched against the catalogue by name, plural or synonym.
+    /// Names that are not in the catalogue are kept as they are.
+    #[schema(example = "flour")]
+    #[validate(length(min = 1, max = 120))]
+    pub label: String,
+    /// Leave out when the quantity is not tracked
+    #[schema(example = 1.5)]
+    #[validate(range(min = 0.0, max = 100000.0))]
+    pub amount: Option<f32>,
+    #[schema(example = "kg")]
+    #[validate(length(max = 120))]
+    pub unit: Option<String>,
+    #[schema(example = "2023-08-31", value_type = Option<String>, format = Date)]
+    pub expires_on: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct PantryItemPutDTO {
+    #[schema(example = "flour")]
+    #[validate(length(min = 1, max = 120))]
+    pub label: Option<String>,
+    /// Leave out to keep the current amount, `null` to stop tracking the quantity
+    #[schema(example = 0.5, value_type = Option<f32>)]
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(range(min = 0.0, max = 100000.0))]
+    pub amount: Option<Option<f32>>,
+    /// Leave out to keep the current unit, `null` to remove it
+    #[schema(example = "kg", value_type = Option<String>)]
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 120))]
+    pub unit: Option<Option<String>>,
+    /// Leave out to keep the current date, `null` to remove it
+    #[schema(example = "2023-09-15", value_type = Option<String>, format = Date)]
+    #[serde(default, deserialize_with = "nullable")]
+    pub expires_on: Option<Option<NaiveDate>>,
+}
+
+/// Order of the cookable recipes
+#[derive(FromFormField, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum CookableSort {
+    /// Most of the ingredients in the pantry first
+    Coverage,
+    /// Recipes using the pantry items that expire soonest first
+    Expiring,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct CookableRecipeDTO {
+    pub recipe: RecipeResultDTO,
+    /// Share of the recipe's ingredients that are in the pantry, 0 to 1
+    #[schema(example = 0.75)]
+    pub coverage: f32,
+    /// Number of the recipe's ingredients that are in the pantry
+    #[schema(example = 6)]
+    pub covered: i64,
+    /// Number of distinct ingredients of the recipe
+    #[schema(example = 8)]
+    pub total: i64,
+    /// Ingredients to get, with the amount that is missing when the pantry has too little
+    pub missing: Vec<IngredientDTO>,
+    /// Earliest expiry date of the pantry items the recipe uses
+    #[schema(example = "2023-07-28", value_type = Option<String>, format = Date)]
+    pub expires_on: Option<NaiveDate>,
+}
@@ -0,0 +1,73 @@
+use crate::models::{Recipe, User};
+use crate::schema::*;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
+#[diesel(belongs_to(Recipe))]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = ratings)]
+#[diesel(primary_key(recipe_id, user_id))]
+pub struct Rating {
+    pub recipe_id: i32,
+    pub user_id: i32,
+    pub stars: i16,
+    pub review: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RatingPutDTO {
+    /// 1 to 5 stars
+    #[schema(example = 4)]
+    #[validate(range(min = 1, max = 5))]
+    pub stars: i16,
+    /// Written review, leave out to only rate the recipe
+    #[schema(example = "Crispy crust, I added some chili.")]
+    #[validate(length(max = 5000))]
+    pub review: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RatingDTO {
+    #[schema(example = 4)]
+    pub stars: i16,
+    #[schema(example = "Crispy crust, I added some chili.")]
+    pub review: Option<String>,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl From<Rating> for RatingDTO {
+    fn from(r: Rating) -> Self {
+        Self {
+            stars: r.stars,
+            review: r.review,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReviewDTO {
+    /// Author of the review
+    #[schema(example = "johndoe")]
+    pub username: String,
+    #[schema(example = 4)]
+    pub stars: i16,
+    #[schema(example = "Crispy crust, I added some chili.")]
+    pub review: String,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub updated_at: chrono::NaiveDateTime,
+}
@@ -1,7 +1,11 @@
+use crate::controllers::recipe_role;
 use crate::models::*;
 use crate::schema::*;
+use crate::LogsDbConn;
 use chrono;
 use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
 use rocket::serde::json::Json;
 use rocket::serde::Serialize;
 
@@ -9,7 +13,8 @@ use rocket::serde::Serialize;
 pub struct Recipe {
     pub id: i32,
     pub title: String,
-    pub servings: String,
+    pub servings: i16,
+    pub servings_unit: Option<String>,
     pub timer: Option<i16>,
     pub kcal: Option<i16>,
     pub carbs: Option<i16>,
@@ -18,6 +23,11 @@ pub struct Recipe {
     pub image: Option<serde_json::Value>,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// Recipe this one was copied from
+    pub forked_from: Option<i32>,
+    /// public, unlisted or private, see `Visibility`
+    pub visibility: String,
 }
 
 #[derive(Queryable, Identifiable, Clone, Associations, PartialEq, Debug)] //PartialEq
@@ -38,16 +48,42 @@ pub struct RecipeIngredient {
     pub amount: Option<f32>,
     pub recipe_id: i32,
     pub ingredient_id: i32,
+    pub unit: Option<String>,
 }
 
 #[derive(Queryable, Identifiable, Clone, Debug)]
 #[diesel(table_name = ingredients)]
 pub struct Ingredient {
     pub id: i32,
-    pub unit: Option<String>,
     pub label: String,
 }
 
+#[derive(Queryable, Identifiable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Ingredient))]
+#[diesel(table_name = ingredient_synonyms)]
+pub struct IngredientSynonym {
+    pub id: i32,
+    pub ingredient_id: i32,
+    pub label: String,
+}
+
+/// Nutrients per 100 g of an ingredient, imported from USDA FoodData Central
+#[derive(Queryable, Identifiable, Selectable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Ingredient))]
+#[diesel(table_name = ingredient_nutrients)]
+#[diesel(primary_key(ingredient_id))]
+pub struct IngredientNutrient {
+    pub ingredient_id: i32,
+    pub fdc_id: i32,
+    pub description: String,
+    pub kcal: f32,
+    pub carbs: f32,
+    pub proteins: f32,
+    pub fats: f32,
+    pub grams_per_ml: Option<f32>,
+    pub grams_per_piece: Option<f32>,
+}
+
 #[derive(Queryable, Identifiable, Selectable, Debug)]
 #[diesel(table_name = tags)]
 pub struct Tag {
@@ -74,6 +110,8 @@ pub struct RecipeTag {
 pub struct RecipeUser {
     pub recipe_id: i32,
     pub user_id: i32,
+    /// owner, editor or viewer, see `RecipeRole`
+    pub role: String,
 }
 
 #[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
@@ -86,6 +124,29 @@ pub struct Bookmark {
     pub user_id: i32,
 }
 
+#[derive(QueryableByName, Debug)]
+pub struct RecipeSearchHit {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub id: i32,
+    /// Sort key as text, for the cursors
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub key: Option<String>,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub snippet: String,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct RowCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct SortKey {
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub key: Option<String>,
+}
+
 #[derive(Responder, Debug)]
 pub enum RecipeResponse<T> {
     #[response(status = 200)]
@@ -96,8 +157,142 @@ pub enum RecipeResponse<T> {
     BadRequest(String),
     #[response(status = 401)]
     Unauthorized(String),
+    #[response(status = 403)]
+    Forbidden(String),
     #[response(status = 404)]
     NotFound(String),
     #[response(status = 500)]
     InternalServerError(String),
 }
+
+impl<T> From<NetworkResponse> for RecipeResponse<T> {
+    fn from(r: NetworkResponse) -> Self {
+        match r {
+            NetworkResponse::BadRequest(m) => RecipeResponse::BadRequest(m),
+            NetworkResponse::Unauthorized(m) => RecipeResponse::Unauthorized(m),
+            NetworkResponse::Forbidden(m) => RecipeResponse::Forbidden(m),
+            NetworkResponse::NotFound(m) => RecipeResponse::NotFound(m),
+            NetworkResponse::Ok(m)
+            | NetworkResponse::Created(m)
+            | NetworkResponse::Conflict(m)
+            | NetworkResponse::InternalServerError(m) => RecipeResponse::InternalServerError(m),
+        }
+    }
+}
+
+/// Request guard for routes that mutate a recipe.
+///
+/// The recipe id is read from the `<recipe_id>` segment of the matched route,
+/// the caller has to be logged in and listed as an owner or editor in `recipes_users`.
+/// Routes that only the owner may use check `role` themselves.
+#[derive(Debug)]
+pub struct OwnedRecipe {
+    pub recipe: Recipe,
+    /// The logged in owner or editor
+    pub user_id: i32,
+    pub role: RecipeRole,
+}
+
+impl OwnedRecipe {
+    /// Fails with 403 unless the caller is the owner
+    pub fn owner_only(self, action: &str) -> Result<Self, NetworkResponse> {
+        match self.role {
+            RecipeRole::Owner => Ok(self),
+            _ => Err(NetworkResponse::Forbidden(format!(
+                "Only the owner of the recipe can {} it.",
+                action
+            ))),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for OwnedRecipe {
+    type Error = NetworkResponse;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, NetworkResponse> {
+        let user_id = match req.guard::<Jwt>().await {
+            Outcome::Success(key) => key.claims.subject_id,
+            Outcome::Failure(_) => {
+                return Outcome::Failure((
+                    Status::Unauthorized,
+                    NetworkResponse::Unauthorized(String::from(
+                        "Please log in to be able to edit recipes.",
+                    )),
+                ))
+            }
+            Outcome::Forward(f) => return Outcome::Forward(f),
+        };
+
+        let recipe_id = match req.route().and_then(|route| {
+            route
+                .uri
+                .unmounted_origin
+                .path()
+                .segments()
+                .position(|s| s == "<recipe_id>")
+        }) {
+            Some(n) => match req.param::<i32>(n) {
+                Some(Ok(id)) => id,
+                _ => {
+                    return Outcome::Failure((
+                        Status::BadRequest,
+                        NetworkResponse::BadRequest(String::from("Invalid recipe id.")),
+                    ))
+                }
+            },
+            None => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    NetworkResponse::InternalServerError(String::from(
+                        "Route has no recipe id segment.",
+                    )),
+                ))
+            }
+        };
+
+        let conn = match req.guard::<LogsDbConn>().await {
+            Outcome::Success(conn) => conn,
+            _ => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    NetworkResponse::InternalServerError(String::from(
+                        "Cannot connect to the database.",
+                    )),
+                ))
+            }
+        };
+
+        let result = conn
+            .run(move |c| {
+                let recipe = recipes::table.find(recipe_id).first::<Recipe>(c)?;
+                let role = recipe_role(c, recipe_id, user_id)?;
+                Ok::<_, diesel::result::Error>((recipe, role))
+            })
+            .await;
+
+        match result {
+            Ok((recipe, Some(role))) if role.can_edit() => Outcome::Success(OwnedRecipe {
+                recipe,
+                user_id,
+                role,
+            }),
+            Ok(_) => Outcome::Failure((
+                Status::Forbidden,
+                NetworkResponse::Forbidden(String::from(
+                    "Only the owner or an editor of the recipe can modify it.",
+                )),
+            )),
+            Err(diesel::NotFound) => Outcome::Failure((
+                Status::NotFound,
+                NetworkResponse::NotFound(String::from("The recipe was not found.")),
+            )),
+            Err(_) => Outcome::Failure((
+                Status::InternalServerError,
+                NetworkResponse::InternalServerError(String::from(
+                    "Cannot read ownership from the database.",
+                )),
+            )),
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use crate::models::*;
 use crate::schema::*;
+use crate::units::{convert, normalize_unit, UnitSystem};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use chrono;
 use diesel::prelude::*;
 use rocket::serde::{Deserialize, Serialize};
 use slug::slugify;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Insertable, Deserialize, ToSchema)]
@@ -13,8 +16,10 @@ use validator::Validate;
 pub struct RecipesInput {
     #[schema(example = "Veggie Pizza")]
     pub title: String,
-    #[schema(example = "4")]
-    pub servings: String,
+    #[schema(example = 4)]
+    pub servings: i16,
+    #[schema(example = "slices")]
+    pub servings_unit: Option<String>,
     #[schema(example = 90)]
     pub timer: Option<i16>,
     #[schema(example = 130)]
@@ -35,6 +40,7 @@ pub struct RecipesInput {
         secure_url: "https://res.cloudinary.com/dgxfgifvw/image/upload/v1688953484/foodly/vwgblqojf6tuhezhddy9.jpg",
     }))]
     pub image: Option<serde_json::Value>,
+    pub visibility: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -49,15 +55,17 @@ pub struct UploadResult {
     pub secure_url: String,
 }
 
-#[derive(Serialize, Clone, ToSchema, Debug)]
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
 #[serde(crate = "rocket::serde")]
 pub struct RecipeResultDTO {
     #[schema(example = 123)]
     pub id: i32,
     #[schema(example = "Veggie Pizza")]
     pub title: String,
-    #[schema(example = "4")]
-    pub servings: String,
+    #[schema(example = 4)]
+    pub servings: i16,
+    #[schema(example = "slices")]
+    pub servings_unit: Option<String>,
     #[schema(example = 90)]
     pub timer: Option<i16>,
     #[schema(example = 130)]
@@ -90,9 +98,35 @@ pub struct RecipeResultDTO {
     pub created_at: Option<chrono::NaiveDateTime>,
     #[schema(example = json!(Some(chrono::Utc::now())))]
     pub updated_at: Option<chrono::NaiveDateTime>,
+    #[schema(example = json!(None::<chrono::NaiveDateTime>))]
+    pub deleted_at: Option<chrono::NaiveDateTime>,
     pub tags: Vec<TagDTO>,
     pub bookmarked: Option<bool>,
-    pub owned: Option<bool>,
+    /// Role of the caller on the recipe, absent when they are not a collaborator
+    pub role: Option<RecipeRole>,
+    #[schema(example = "Put <b>pizza</b> into oven.")]
+    pub snippet: Option<String>,
+    pub nutrition: Option<NutritionDTO>,
+    /// Average of the star ratings, absent while the recipe has none
+    #[schema(example = 4.5)]
+    pub average_rating: Option<f32>,
+    #[schema(example = 12)]
+    pub rating_count: i64,
+    /// Stars the caller gave the recipe
+    #[schema(example = 5)]
+    pub my_rating: Option<i16>,
+    /// Number of comments and replies
+    #[schema(example = 3)]
+    pub comment_count: i64,
+    /// Id of the recipe this one was forked from
+    #[schema(example = json!(None::<i32>))]
+    pub forked_from: Option<i32>,
+    /// Number of forks of this recipe
+    #[schema(example = 2)]
+    #[serde(default)]
+    pub fork_count: i64,
+    #[serde(default)]
+    pub visibility: Visibility,
 }
 
 impl From<Recipe> for RecipeResultDTO {
@@ -101,6 +135,7 @@ impl From<Recipe> for RecipeResultDTO {
             id: r.id,
             title: r.title,
             servings: r.servings,
+            servings_unit: r.servings_unit,
             timer: r.timer,
             kcal: r.kcal,
             carbs: r.carbs,
@@ -111,9 +146,19 @@ impl From<Recipe> for RecipeResultDTO {
             ingredients: Vec::<IngredientDTO>::new(),
             created_at: r.created_at,
             updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
             tags: Vec::<TagDTO>::new(),
             bookmarked: None,
-            owned: None,
+            role: None,
+            snippet: None,
+            nutrition: None,
+            average_rating: None,
+            rating_count: 0,
+            my_rating: None,
+            comment_count: 0,
+            forked_from: r.forked_from,
+            fork_count: 0,
+            visibility: Visibility::parse(&r.visibility).unwrap_or_default(),
         }
     }
 }
@@ -123,7 +168,8 @@ impl From<&Recipe> for RecipeResultDTO {
         Self {
             id: r.id,
             title: r.title.clone(),
-            servings: r.servings.clone(),
+            servings: r.servings,
+            servings_unit: r.servings_unit.clone(),
             timer: r.timer,
             kcal: r.kcal,
             carbs: r.carbs,
@@ -134,10 +180,157 @@ impl From<&Recipe> for RecipeResultDTO {
             ingredients: Vec::<IngredientDTO>::new(),
             created_at: r.created_at,
             updated_at: r.updated_at,
+            deleted_at: r.deleted_at,
             tags: Vec::<TagDTO>::new(),
             bookmarked: None,
-            owned: None,
+            role: None,
+            snippet: None,
+            nutrition: None,
+            average_rating: None,
+            rating_count: 0,
+            my_rating: None,
+            comment_count: 0,
+            forked_from: r.forked_from,
+            fork_count: 0,
+            visibility: Visibility::parse(&r.visibility).unwrap_or_default(),
+        }
+    }
+}
+
+impl RecipeResultDTO {
+    /// Recipe for a different yield: ingredient amounts and nutrition are multiplied by `factor`
+    pub fn scaled(mut self, factor: f32, servings: i16) -> Self {
+        let scale = |v: i16| (v as f32 * factor).round().min(i16::MAX as f32) as i16;
+        self.servings = servings;
+        self.kcal = self.kcal.map(scale);
+        self.carbs = self.carbs.map(scale);
+        self.proteins = self.proteins.map(scale);
+        self.fats = self.fats.map(scale);
+        for ingredient in &mut self.ingredients {
+            ingredient.amount = ingredient.amount.map(|a| kitchen_round(a * factor));
         }
+        self.nutrition = self.nutrition.map(|n| n.scaled(factor, servings));
+        self
+    }
+}
+
+impl RecipeResultDTO {
+    /// Ingredient amounts converted into the given measurement system
+    pub fn converted(mut self, system: UnitSystem) -> Self {
+        for ingredient in &mut self.ingredients {
+            if let (Some(amount), Some(unit)) = (ingredient.amount, &ingredient.unit) {
+                let (amount, unit) = convert(amount, unit, system);
+                ingredient.amount = Some(amount);
+                ingredient.unit = Some(unit);
+            }
+        }
+        self
+    }
+}
+
+/// Nutrition values, `None` when neither entered nor computable
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct NutritionValues {
+    #[schema(example = 520.0)]
+    pub kcal: Option<f32>,
+    #[schema(example = 64.5)]
+    pub carbs: Option<f32>,
+    #[schema(example = 21.0)]
+    pub proteins: Option<f32>,
+    #[schema(example = 18.2)]
+    pub fats: Option<f32>,
+}
+
+impl NutritionValues {
+    /// Values multiplied by `factor`, kcal rounded to whole numbers, the rest to one decimal
+    pub fn scaled(self, factor: f32) -> Self {
+        let round = |v: f32, step: f64| ((v as f64 * factor as f64 / step).round() * step) as f32;
+        Self {
+            kcal: self.kcal.map(|v| round(v, 1.0)),
+            carbs: self.carbs.map(|v| round(v, 0.1)),
+            proteins: self.proteins.map(|v| round(v, 0.1)),
+            fats: self.fats.map(|v| round(v, 0.1)),
+        }
+    }
+
+    /// Each value taken from `self`, or from `other` where `self` has none
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            kcal: self.kcal.or(other.kcal),
+            carbs: self.carbs.or(other.carbs),
+            proteins: self.proteins.or(other.proteins),
+            fats: self.fats.or(other.fats),
+        }
+    }
+}
+
+/// Nutrition of a recipe. Values entered on the recipe override the ones computed
+/// from the ingredients, field by field.
+#[derive(Serialize, Deserialize, Clone, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct NutritionDTO {
+    /// Whole recipe, entered or computed
+    pub per_recipe: NutritionValues,
+    /// `per_recipe` divided by the servings
+    pub per_serving: NutritionValues,
+    /// Whole recipe, added up from the ingredients that have nutrient data
+    pub computed: NutritionValues,
+    /// Ingredients left out of `computed`: no nutrient data, or an amount that
+    /// cannot be weighed, e.g. a volume without known density
+    #[schema(example = json!(vec!["saffron"]))]
+    pub unmatched: Vec<String>,
+}
+
+impl NutritionDTO {
+    pub fn new(
+        computed: NutritionValues,
+        manual: NutritionValues,
+        servings: i16,
+        unmatched: Vec<String>,
+    ) -> Self {
+        let computed = computed.scaled(1.0);
+        let per_recipe = manual.or(computed).scaled(1.0);
+        Self {
+            per_recipe,
+            per_serving: per_recipe.scaled(1.0 / servings.max(1) as f32),
+            computed,
+            unmatched,
+        }
+    }
+
+    /// Nutrition for a different yield, per serving follows the new servings
+    pub fn scaled(self, factor: f32, servings: i16) -> Self {
+        let per_recipe = self.per_recipe.scaled(factor);
+        Self {
+            per_recipe,
+            per_serving: per_recipe.scaled(1.0 / servings.max(1) as f32),
+            computed: self.computed.scaled(factor),
+            unmatched: self.unmatched,
+        }
+    }
+}
+
+/// Round a scaled amount to what can be measured in a kitchen:
+/// steps of 5 from 100, whole numbers from 10, quarters from 1, hundredths below.
+/// Nothing is rounded down to zero.
+pub fn kitchen_round(amount: f32) -> f32 {
+    // f64 keeps steps like 0.01 exact enough to come back as e.g. 0.2, not 0.19999999
+    let amount = amount as f64;
+    let step = if amount >= 100.0 {
+        5.0
+    } else if amount >= 10.0 {
+        1.0
+    } else if amount >= 1.0 {
+        0.25
+    } else {
+        0.01
+    };
+    let rounded = (amount / step).round() * step;
+    if rounded == 0.0 && amount > 0.0 {
+        step as f32
+    } else {
+        rounded as f32
     }
 }
 
@@ -147,12 +340,16 @@ pub struct RecipePostDTO {
     #[schema(example = "Veggie Pizza")]
     #[validate(length(max = 120))]
     pub title: String,
-    #[schema(example = "4")]
+    #[schema(example = 4)]
+    #[validate(range(min = 1, max = 1000))]
+    pub servings: i16,
+    #[schema(example = "slices")]
     #[validate(length(max = 120))]
-    pub servings: String,
+    pub servings_unit: Option<String>,
     #[schema(example = 90)]
     #[validate(range(min = 0, max = 30000))]
     pub timer: Option<i16>,
+    /// Manual nutrition for the whole recipe, overrides the value computed from the ingredients
     #[schema(example = 130)]
     #[validate(range(min = 0, max = 30000))]
     pub kcal: Option<i16>,
@@ -186,6 +383,12 @@ pub struct RecipePostDTO {
     pub ingredients: Option<Vec<IngredientDTO>>,
     #[schema(example = json!(Some(vec!["vegan", "vegetarian"])))]
     pub tags: Option<Vec<String>>,
+    /// Raw ingredient lines, parsed and added to `ingredients`.
+    /// Preparation notes and upper bounds of ranges are not stored.
+    #[schema(example = json!(Some(vec!["1 1/2 cups all-purpose flour, sifted", "2-3 cloves garlic (minced)"])))]
+    pub ingredient_lines: Option<Vec<String>>,
+    /// Defaults to public
+    pub visibility: Option<Visibility>,
 }
 
 #[derive(Deserialize, Validate, ToSchema)]
@@ -194,24 +397,33 @@ pub struct RecipePutDTO {
     #[schema(example = "Veggie Pizza")]
     #[validate(length(max = 120))]
     pub title: Option<String>,
-    #[schema(example = "4")]
+    #[schema(example = 4)]
+    #[validate(range(min = 1, max = 1000))]
+    pub servings: Option<i16>,
+    #[schema(example = "slices")]
     #[validate(length(max = 120))]
-    pub servings: Option<String>,
+    pub servings_unit: Option<String>,
     #[schema(example = 90)]
     #[validate(range(min = 0, max = 30000))]
     pub timer: Option<i16>,
-    #[schema(example = 130)]
+    /// Manual nutrition for the whole recipe, overrides the computed value.
+    /// Leave out to keep the current value, `null` to use the computed one again.
+    #[schema(example = 130, value_type = Option<i16>)]
+    #[serde(default, deserialize_with = "nullable")]
     #[validate(range(min = 0, max = 30000))]
-    pub kcal: Option<i16>,
-    #[schema(example = 25)]
+    pub kcal: Option<Option<i16>>,
+    #[schema(example = 25, value_type = Option<i16>)]
+    #[serde(default, deserialize_with = "nullable")]
     #[validate(range(min = 0, max = 30000))]
-    pub carbs: Option<i16>,
-    #[schema(example = 3)]
+    pub carbs: Option<Option<i16>>,
+    #[schema(example = 3, value_type = Option<i16>)]
+    #[serde(default, deserialize_with = "nullable")]
     #[validate(range(min = 0, max = 30000))]
-    pub proteins: Option<i16>,
-    #[schema(example = 2)]
+    pub proteins: Option<Option<i16>>,
+    #[schema(example = 2, value_type = Option<i16>)]
+    #[serde(default, deserialize_with = "nullable")]
     #[validate(range(min = 0, max = 30000))]
-    pub fats: Option<i16>,
+    pub fats: Option<Option<i16>>,
     #[schema(example = json!({
         public_id: "vwgblqojf6tuhezhddy9",
         width: 800,
@@ -233,6 +445,39 @@ pub struct RecipePutDTO {
     pub ingredients: Option<Vec<IngredientDTO>>,
     #[schema(example = json!(Some(vec!["vegan", "vegetarian"])))]
     pub tags: Option<Vec<String>>,
+    pub visibility: Option<Visibility>,
+}
+
+/// Update that brings a recipe back to the state of a snapshot
+impl From<RecipeResultDTO> for RecipePutDTO {
+    fn from(r: RecipeResultDTO) -> Self {
+        Self {
+            title: Some(r.title),
+            servings: Some(r.servings),
+            // an empty unit clears it
+            servings_unit: Some(r.servings_unit.unwrap_or_default()),
+            timer: r.timer,
+            kcal: Some(r.kcal),
+            carbs: Some(r.carbs),
+            proteins: Some(r.proteins),
+            fats: Some(r.fats),
+            image: r.image,
+            instructions: Some(r.instructions),
+            ingredients: Some(r.ingredients),
+            tags: Some(r.tags.into_iter().map(|t| t.label).collect()),
+            // who can see the recipe is not part of its content
+            visibility: None,
+        }
+    }
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field (`None`)
+pub(crate) fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: rocket::serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 impl From<RecipePostDTO> for RecipesInput {
@@ -240,12 +485,14 @@ impl From<RecipePostDTO> for RecipesInput {
         Self {
             title: r.title,
             servings: r.servings,
+            servings_unit: r.servings_unit,
             timer: r.timer,
             kcal: r.kcal,
             carbs: r.carbs,
             proteins: r.proteins,
             fats: r.fats,
             image: r.image,
+            visibility: String::from(r.visibility.unwrap_or_default().as_str()),
         }
     }
 }
@@ -254,13 +501,15 @@ impl From<&RecipePostDTO> for RecipesInput {
     fn from(r: &RecipePostDTO) -> Self {
         Self {
             title: r.title.clone(),
-            servings: r.servings.clone(),
+            servings: r.servings,
+            servings_unit: r.servings_unit.clone(),
             timer: r.timer,
             kcal: r.kcal,
             carbs: r.carbs,
             proteins: r.proteins,
             fats: r.fats,
             image: r.image.clone(),
+            visibility: String::from(r.visibility.unwrap_or_default().as_str()),
         }
     }
 }
@@ -277,7 +526,6 @@ pub struct InstructionInsert {
 #[derive(Insertable, Debug)] //PartialEq
 #[diesel(table_name = ingredients)]
 pub struct IngredientInsert {
-    pub unit: Option<String>,
     pub label: String,
 }
 
@@ -289,6 +537,7 @@ pub struct RecipeIngredientInsert {
     pub amount: Option<f32>,
     pub recipe_id: i32,
     pub ingredient_id: i32,
+    pub unit: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Validate, Clone, ToSchema, Debug)]
@@ -305,16 +554,81 @@ pub struct IngredientDTO {
     pub amount: Option<f32>,
 }
 
+impl IngredientDTO {
+    /// Same ingredient with the unit in its canonical spelling
+    pub fn normalized(mut self) -> Self {
+        self.unit = self.unit.as_deref().and_then(normalize_unit);
+        self
+    }
+}
+
 impl From<(RecipeIngredient, Ingredient)> for IngredientDTO {
     fn from(r: (RecipeIngredient, Ingredient)) -> Self {
         Self {
-            unit: r.1.unit,
+            unit: r.0.unit,
             label: r.1.label,
             amount: r.0.amount,
         }
     }
 }
 
+/// Catalogue entry of an ingredient, independent of the units recipes use it in
+#[derive(QueryableByName, Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct IngredientCatalogDTO {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    #[schema(example = 12)]
+    pub id: i32,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    #[schema(example = "green onion")]
+    pub label: String,
+    #[diesel(sql_type = diesel::sql_types::Array<diesel::sql_types::Text>)]
+    #[schema(example = json!(vec!["scallion", "spring onion"]))]
+    pub synonyms: Vec<String>,
+    /// Number of recipes using the ingredient, trashed ones not counted
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    #[schema(example = 7)]
+    pub usage_count: i64,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct IngredientMergeDTO {
+    /// Ingredients merged into the one of the path, they are deleted and become its synonyms
+    #[schema(example = json!(vec![14, 27]))]
+    #[validate(length(min = 1, max = 100))]
+    pub ids: Vec<i32>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct IngredientSynonymDTO {
+    #[schema(example = "scallion")]
+    #[validate(length(min = 1, max = 120))]
+    pub label: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct IngredientParseDTO {
+    #[schema(example = json!(vec!["1 1/2 cups all-purpose flour, sifted", "2-3 cloves garlic (minced)", "salt to taste"]))]
+    #[validate(length(max = 200))]
+    pub lines: Vec<String>,
+}
+
+#[derive(Serialize, Clone, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ParsedIngredientDTO {
+    #[schema(example = "2-3 cloves garlic (minced)")]
+    pub line: String,
+    pub ingredient: IngredientDTO,
+    /// Upper bound of a range, `ingredient.amount` holds the lower one
+    #[schema(example = 3.0)]
+    pub amount_max: Option<f32>,
+    #[schema(example = "minced")]
+    pub note: Option<String>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct TagPostDTO {
@@ -366,10 +680,269 @@ impl From<&Tag> for TagDTO {
 #[serde(crate = "rocket::serde")]
 pub struct PaginatedResult<T> {
     pub records: Vec<T>,
+    /// Number of matching records, only counted in cursor mode when asked for
     #[schema(example = 102)]
-    pub total: i64,
+    pub total: Option<i64>,
+    /// Page number, empty in cursor mode
     #[schema(example = 1)]
-    pub current_page: i64,
+    pub current_page: Option<i64>,
     #[schema(example = 12)]
     pub per_page: i64,
+    /// Cursor of the following page, empty on the last page
+    pub next_cursor: Option<String>,
+    /// Cursor of the preceding page, empty on the first page
+    pub prev_cursor: Option<String>,
+}
+
+#[derive(FromFormField, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[schema(rename_all = "lowercase")]
+pub enum TagMatch {
+    All,
+    Any,
+}
+
+#[derive(FromForm, IntoParams, Clone, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct RecipeFilter {
+    /// Tag slugs the recipes have to be tagged with
+    #[param(example = json!(["vegan", "quick"]))]
+    pub tags: Vec<String>,
+    /// Whether recipes need all of the tags or any of them, defaults to all
+    pub tag_mode: Option<TagMatch>,
+    /// Tag slugs the recipes must not be tagged with
+    pub exclude_tags: Vec<String>,
+    /// Maximum preparation time in minutes
+    #[param(example = 30)]
+    pub max_timer: Option<i16>,
+    pub min_kcal: Option<i16>,
+    pub max_kcal: Option<i16>,
+    pub min_carbs: Option<i16>,
+    pub max_carbs: Option<i16>,
+    pub min_proteins: Option<i16>,
+    pub max_proteins: Option<i16>,
+    pub min_fats: Option<i16>,
+    pub max_fats: Option<i16>,
+    /// Ingredients the recipes have to contain, matched by name, plural or synonym
+    #[param(example = json!(["garlic"]))]
+    pub ingredients: Vec<String>,
+    /// Ingredients the recipes must not contain, matched by name, plural or synonym
+    pub exclude_ingredients: Vec<String>,
+    /// Only recipes owned by the logged in user
+    pub owned: Option<bool>,
+    /// Only recipes bookmarked by the logged in user
+    pub bookmarked: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TagFacet {
+    #[schema(example = "gluten free")]
+    pub label: String,
+    #[schema(example = "gluten-free")]
+    pub slug: String,
+    #[schema(example = 12)]
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TimerFacet {
+    #[schema(example = 15)]
+    pub min: i16,
+    #[schema(example = 30)]
+    pub max: Option<i16>,
+    #[schema(example = 7)]
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RecipeFacets {
+    pub tags: Vec<TagFacet>,
+    pub timers: Vec<TimerFacet>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct FilteredResult {
+    #[serde(flatten)]
+    pub result: PaginatedResult<RecipeResultDTO>,
+    pub facets: RecipeFacets,
+}
+
+/// Who can see a recipe
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed and found by everyone
+    #[default]
+    Public,
+    /// Readable by everyone with the link, but left out of listings and search
+    Unlisted,
+    /// Only the owners can see it
+    Private,
+}
+
+impl Visibility {
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Visibility> {
+        [
+            Visibility::Public,
+            Visibility::Unlisted,
+            Visibility::Private,
+        ]
+        .into_iter()
+        .find(|v| v.as_str() == name)
+    }
+}
+
+#[derive(FromFormField, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+#[schema(rename_all = "snake_case")]
+pub enum RecipeSort {
+    #[field(value = "title")]
+    Title,
+    #[field(value = "created_at")]
+    CreatedAt,
+    #[field(value = "updated_at")]
+    UpdatedAt,
+    #[field(value = "timer")]
+    Timer,
+    #[field(value = "kcal")]
+    Kcal,
+    #[field(value = "ingredients")]
+    Ingredients,
+    #[field(value = "bookmarks")]
+    Bookmarks,
+    #[field(value = "rating")]
+    Rating,
+    #[field(value = "relevance")]
+    Relevance,
+}
+
+#[derive(FromFormField, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn reversed(&self) -> SortDirection {
+        match self {
+            SortDirection::Asc => SortDirection::Desc,
+            SortDirection::Desc => SortDirection::Asc,
+        }
+    }
+
+    fn keyword(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+impl RecipeSort {
+    /// SQL expression the recipes are ordered by.
+    /// `rank` is only available in full-text search queries.
+    pub fn expression(&self) -> &'static str {
+        match self {
+            RecipeSort::Title => "recipes.title",
+            RecipeSort::CreatedAt => "recipes.created_at",
+            RecipeSort::UpdatedAt => "recipes.updated_at",
+            RecipeSort::Timer => "recipes.timer",
+            // entered value, or else the one computed from the ingredients
+            RecipeSort::Kcal => {
+                "COALESCE(recipes.kcal::real, (SELECT recipe_nutrition.kcal FROM recipe_nutrition WHERE recipe_nutrition.recipe_id = recipes.id))"
+            }
+            RecipeSort::Ingredients => {
+                "(SELECT COUNT(*) FROM recipe_ingredients WHERE recipe_ingredients.recipe_id = recipes.id)"
+            }
+            RecipeSort::Bookmarks => {
+                "(SELECT COUNT(*) FROM bookmarks WHERE bookmarks.recipe_id = recipes.id)"
+            }
+            RecipeSort::Rating => {
+                "(SELECT AVG(ratings.stars)::real FROM ratings WHERE ratings.recipe_id = recipes.id)"
+            }
+            RecipeSort::Relevance => "rank",
+        }
+    }
+
+    pub fn default_direction(&self) -> SortDirection {
+        match self {
+            RecipeSort::Title | RecipeSort::Timer | RecipeSort::Kcal => SortDirection::Asc,
+            _ => SortDirection::Desc,
+        }
+    }
+
+    /// SQL type of the sort expression, used to cast cursor keys back
+    pub fn key_type(&self) -> &'static str {
+        match self {
+            RecipeSort::Title => "TEXT",
+            RecipeSort::CreatedAt | RecipeSort::UpdatedAt => "TIMESTAMP",
+            RecipeSort::Timer => "SMALLINT",
+            RecipeSort::Ingredients | RecipeSort::Bookmarks => "BIGINT",
+            RecipeSort::Kcal | RecipeSort::Rating | RecipeSort::Relevance => "REAL",
+        }
+    }
+
+    /// ORDER BY clause, ties are broken by id in the same direction so pages stay stable
+    pub fn order_clause(&self, direction: Option<SortDirection>) -> String {
+        let direction = direction.unwrap_or(self.default_direction()).keyword();
+        format!(
+            "{} {} NULLS LAST, recipes.id {}",
+            self.expression(),
+            direction,
+            direction
+        )
+    }
+
+    /// Exact reverse of `order_clause`, for walking a list backwards from a cursor
+    pub fn reverse_order_clause(&self, direction: SortDirection) -> String {
+        let direction = direction.reversed().keyword();
+        format!(
+            "{} {} NULLS FIRST, recipes.id {}",
+            self.expression(),
+            direction,
+            direction
+        )
+    }
+}
+
+/// Position in a sorted recipe list: the sort key and id of a boundary record.
+///
+/// Handed out to clients as an opaque base64 string.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RecipeCursor {
+    pub sort: RecipeSort,
+    pub direction: SortDirection,
+    /// Sort key of the record as text, empty if the key is NULL
+    pub key: Option<String>,
+    pub id: i32,
+    /// Records before the position instead of after it
+    pub backward: bool,
+}
+
+impl RecipeCursor {
+    pub fn encode(&self) -> String {
+        let json = rocket::serde::json::to_string(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Option<RecipeCursor> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        rocket::serde::json::from_slice(&json).ok()
+    }
 }
@@ -0,0 +1,104 @@
+use crate::models::{IngredientDTO, Recipe};
+use crate::schema::*;
+use diesel::prelude::*;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug)]
+#[diesel(belongs_to(Recipe))]
+#[diesel(table_name = recipe_revisions)]
+pub struct RecipeRevision {
+    pub id: i32,
+    pub recipe_id: i32,
+    pub revision: i32,
+    pub user_id: Option<i32>,
+    /// `RecipeResultDTO` of the recipe as it was saved
+    pub snapshot: serde_json::Value,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = recipe_revisions)]
+pub struct RecipeRevisionInsert {
+    pub recipe_id: i32,
+    pub revision: i32,
+    pub user_id: Option<i32>,
+    pub snapshot: serde_json::Value,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RevisionDTO {
+    /// Revision number, counting up from 1 per recipe
+    #[schema(example = 3)]
+    pub revision: i32,
+    /// User who saved the revision, empty for recipes that existed before revisions were kept
+    #[schema(example = "johndoe")]
+    pub username: Option<String>,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct FieldChangeDTO {
+    #[schema(example = "servings")]
+    pub field: String,
+    #[schema(example = 2)]
+    pub from: serde_json::Value,
+    #[schema(example = 4)]
+    pub to: serde_json::Value,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct InstructionChangeDTO {
+    pub change: ChangeKind,
+    /// Step number in the older revision, empty for added steps
+    #[schema(example = 2)]
+    pub from_step: Option<usize>,
+    /// Step number in the newer revision, empty for removed steps
+    #[schema(example = 2)]
+    pub to_step: Option<usize>,
+    #[schema(example = "Put pizza into oven.")]
+    pub from: Option<String>,
+    #[schema(example = "Put pizza into the hot oven.")]
+    pub to: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct IngredientChangeDTO {
+    pub change: ChangeKind,
+    #[schema(example = "flour")]
+    pub label: String,
+    pub from: Option<IngredientDTO>,
+    pub to: Option<IngredientDTO>,
+}
+
+/// Changes between two revisions of a recipe
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RecipeDiffDTO {
+    #[schema(example = 2)]
+    pub from: i32,
+    #[schema(example = 5)]
+    pub to: i32,
+    /// Changed fields like title, servings or the nutrition overrides
+    pub fields: Vec<FieldChangeDTO>,
+    pub instructions: Vec<InstructionChangeDTO>,
+    pub ingredients: Vec<IngredientChangeDTO>,
+    #[schema(example = json!(vec!["vegan"]))]
+    pub tags_added: Vec<String>,
+    #[schema(example = json!(vec!["vegetarian"]))]
+    pub tags_removed: Vec<String>,
+}
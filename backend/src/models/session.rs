@@ -0,0 +1,99 @@
+use crate::models::User;
+use crate::schema::*;
+use diesel::prelude::*;
+use jsonwebtoken::jwk::Jwk;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = sessions)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub refresh_token_hash: String,
+    pub previous_token_hash: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// Logged in device
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionDTO {
+    #[schema(example = 4)]
+    pub id: i32,
+    /// Browser or app that logged in
+    #[schema(example = "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0")]
+    pub user_agent: Option<String>,
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub created_at: chrono::NaiveDateTime,
+    /// Time of the last token refresh
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub last_used_at: chrono::NaiveDateTime,
+    /// The session ends when it is not refreshed until then
+    #[schema(example = json!(chrono::Utc::now()))]
+    pub expires_at: chrono::NaiveDateTime,
+    /// Whether this is the session of the request
+    pub current: bool,
+}
+
+impl SessionDTO {
+    pub fn new(s: Session, current_id: i32) -> Self {
+        Self {
+            id: s.id,
+            user_agent: s.user_agent,
+            created_at: s.created_at,
+            last_used_at: s.last_used_at,
+            expires_at: s.expires_at,
+            current: s.id == current_id,
+        }
+    }
+}
+
+/// Successful token response as in OAuth 2.0 (RFC 6749, section 5.1)
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenResponse {
+    /// Short-lived token for the `Authorization: Bearer` header
+    pub access_token: String,
+    #[schema(example = "Bearer")]
+    pub token_type: String,
+    /// Lifetime of the access token in seconds
+    #[schema(example = 900)]
+    pub expires_in: i64,
+    /// Single-use token for `POST /token/refresh`, every refresh returns a new one
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+/// `User-Agent` header of the request, names the device in the session list
+pub struct UserAgent(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UserAgent {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(UserAgent(
+            req.headers().get_one("user-agent").map(String::from),
+        ))
+    }
+}
+
+/// Public keys that verify access tokens, a JSON Web Key Set (RFC 7517)
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct JsonWebKeySet {
+    /// JSON Web Keys, tokens name theirs in the `kid` header
+    #[schema(value_type = Vec<Object>, example = json!([{"use": "sig", "alg": "EdDSA", "kid": "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k", "kty": "OKP", "crv": "Ed25519", "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}]))]
+    pub keys: Vec<Jwk>,
+}
@@ -0,0 +1,118 @@
+use crate::models::nullable;
+use crate::schema::*;
+use chrono;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug)]
+#[diesel(table_name = shopping_list_items)]
+pub struct ShoppingListItem {
+    pub id: i32,
+    pub user_id: i32,
+    pub ingredient_id: Option<i32>,
+    pub label: String,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+    pub checked: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = shopping_list_items)]
+pub struct ShoppingListItemInsert {
+    pub user_id: i32,
+    pub ingredient_id: Option<i32>,
+    pub label: String,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ShoppingItemDTO {
+    #[schema(example = 12)]
+    pub id: i32,
+    /// Catalogue ingredient, `None` for items that are not in the catalogue
+    #[schema(example = 3)]
+    pub ingredient_id: Option<i32>,
+    #[schema(example = "flour")]
+    pub label: String,
+    #[schema(example = 1.5)]
+    pub amount: Option<f32>,
+    #[schema(example = "kg")]
+    pub unit: Option<String>,
+    #[schema(example = false)]
+    pub checked: bool,
+}
+
+impl From<ShoppingListItem> for ShoppingItemDTO {
+    fn from(i: ShoppingListItem) -> Self {
+        Self {
+            id: i.id,
+            ingredient_id: i.ingredient_id,
+            label: i.label,
+            amount: i.amount,
+            unit: i.unit,
+            checked: i.checked,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ShoppingRecipeDTO {
+    #[schema(example = 2)]
+    pub recipe_id: i32,
+    /// Scale the recipe to this many servings
+    #[schema(example = 6)]
+    #[validate(range(min = 1, max = 1000))]
+    pub servings: Option<i16>,
+    /// Scale the recipe by this factor, defaults to 1
+    #[schema(example = 1.5)]
+    #[validate(range(min = 0.01, max = 100.0))]
+    pub factor: Option<f32>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ShoppingListRecipesDTO {
+    #[validate(length(min = 1, max = 50))]
+    #[validate]
+    pub recipes: Vec<ShoppingRecipeDTO>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ShoppingItemPostDTO {
+    #[schema(example = "kitchen paper")]
+    #[validate(length(min = 1, max = 120))]
+    pub label: String,
+    #[schema(example = 2.0)]
+    #[validate(range(min = 0.0, max = 100000.0))]
+    pub amount: Option<f32>,
+    #[schema(example = "rolls")]
+    #[validate(length(max = 120))]
+    pub unit: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ShoppingItemPutDTO {
+    #[schema(example = true)]
+    pub checked: Option<bool>,
+    #[schema(example = "flour")]
+    #[validate(length(min = 1, max = 120))]
+    pub label: Option<String>,
+    /// Leave out to keep the current amount, `null` to remove it
+    #[schema(example = 2.0, value_type = Option<f32>)]
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(range(min = 0.0, max = 100000.0))]
+    pub amount: Option<Option<f32>>,
+    /// Leave out to keep the current unit, `null` to remove it
+    #[schema(example = "kg", value_type = Option<String>)]
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 120))]
+    pub unit: Option<Option<String>>,
+}
@@ -1,5 +1,7 @@
 use crate::jwt::decode_jwt;
-use crate::schema::users;
+use crate::models::UserRole;
+use crate::schema::{sessions, users};
+use crate::LogsDbConn;
 use diesel::prelude::*;
 use jsonwebtoken::errors::Error;
 use lazy_static::lazy_static;
@@ -17,6 +19,9 @@ pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
+    pub role: String,
+    pub disabled: bool,
+    pub password_reset_required: bool,
 }
 
 lazy_static! {
@@ -61,6 +66,7 @@ pub struct LoginUser {
 #[derive(Serialize)]
 pub struct UserProfile {
     pub username: String,
+    pub role: UserRole,
 }
 
 #[derive(Responder, Debug)]
@@ -73,6 +79,8 @@ pub enum NetworkResponse {
     BadRequest(String),
     #[response(status = 401)]
     Unauthorized(String),
+    #[response(status = 403)]
+    Forbidden(String),
     #[response(status = 404)]
     NotFound(String),
     #[response(status = 409)]
@@ -84,7 +92,6 @@ pub enum NetworkResponse {
 #[derive(Serialize)]
 pub enum ResponseBody {
     Message(String),
-    AuthToken(String),
 }
 
 #[derive(Serialize)]
@@ -96,12 +103,15 @@ pub struct Response {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Claims {
     pub subject_id: i32,
+    /// Session the token was issued for, logging out or revoking it ends the token too
+    pub session_id: i32,
     pub exp: usize,
 }
 
 #[derive(Debug)]
 pub struct Jwt {
     pub claims: Claims,
+    pub role: UserRole,
 }
 
 #[rocket::async_trait]
@@ -127,7 +137,7 @@ impl<'r> FromRequest<'r> for Jwt {
                 ))
             }
             Some(key) => match is_valid(key) {
-                Ok(claims) => Outcome::Success(Jwt { claims }),
+                Ok(claims) => check_account(req, claims).await,
                 Err(err) => match &err.kind() {
                     jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
                         let response = Response {
@@ -178,6 +188,74 @@ impl<'r> FromRequest<'r> for Jwt {
     }
 }
 
+/// Accounts can be disabled or sent to a password reset and sessions can be revoked while
+/// their tokens are still valid, so every authenticated request checks the account and session.
+async fn check_account(req: &Request<'_>, claims: Claims) -> Outcome<Jwt, NetworkResponse> {
+    fn message(text: &str) -> String {
+        let response = Response {
+            body: ResponseBody::Message(String::from(text)),
+        };
+        serde_json::to_string(&response).unwrap()
+    }
+
+    let conn = match req.guard::<LogsDbConn>().await {
+        Outcome::Success(conn) => conn,
+        _ => {
+            return Outcome::Failure((
+                Status::InternalServerError,
+                NetworkResponse::InternalServerError(String::from(
+                    "Cannot connect to the database.",
+                )),
+            ))
+        }
+    };
+
+    let (user_id, session_id) = (claims.subject_id, claims.session_id);
+    let account = conn
+        .run(move |c| {
+            users::table
+                .inner_join(sessions::table)
+                .filter(users::id.eq(user_id))
+                .filter(sessions::id.eq(session_id))
+                .filter(sessions::expires_at.gt(diesel::dsl::now))
+                .select((users::role, users::disabled, users::password_reset_required))
+                .first::<(String, bool, bool)>(c)
+                .optional()
+        })
+        .await;
+
+    match account {
+        Ok(Some((_, true, _))) => Outcome::Failure((
+            Status::Forbidden,
+            NetworkResponse::Forbidden(message("This account has been disabled.")),
+        )),
+        Ok(Some((_, _, true))) if req.uri().path() != "/profile/change_password" => {
+            Outcome::Failure((
+                Status::Forbidden,
+                NetworkResponse::Forbidden(message(
+                    "A password reset is required, please change your password.",
+                )),
+            ))
+        }
+        Ok(Some((role, _, _))) => Outcome::Success(Jwt {
+            claims,
+            role: UserRole::parse(&role).unwrap_or_default(),
+        }),
+        Ok(None) => Outcome::Failure((
+            Status::Unauthorized,
+            NetworkResponse::Unauthorized(message(
+                "Error validating Jwt token - Session has ended",
+            )),
+        )),
+        Err(_) => Outcome::Failure((
+            Status::InternalServerError,
+            NetworkResponse::InternalServerError(String::from(
+                "Cannot read the account from the database.",
+            )),
+        )),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChangePasswordRequest {
     pub old_password: String,
@@ -0,0 +1,335 @@
+//! Offline importer for the ingredient nutrient table.
+//!
+//! Reads a USDA FoodData Central CSV download (https://fdc.nal.usda.gov/download-datasets.html),
+//! e.g. the SR Legacy or Foundation Foods one, and stores nutrients per 100 g for every
+//! catalogue ingredient whose name or synonym matches a food description, then recomputes
+//! the nutrition of every recipe. Run it again after new ingredients were added:
+//!
+//! `crimson-eagle_recipe_backend import-nutrients <directory with food.csv>`
+
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Nullable, Text};
+use rocket::serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+use crate::controllers::refresh_nutrition;
+use crate::ingredient_parser::parse_unit;
+use crate::units::{find_unit, Dimension};
+
+/// FoodData Central nutrient ids, the first one a food has is used
+const KCAL: [i32; 3] = [1008, 2048, 2047];
+const CARBS: [i32; 2] = [1005, 1050];
+const PROTEINS: [i32; 1] = [1003];
+const FATS: [i32; 2] = [1004, 1085];
+
+/// Measure unit id of portions described only by their modifier, like "large" or "cup, chopped"
+const UNDETERMINED_UNIT: i32 = 9999;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct FoodRecord {
+    fdc_id: i32,
+    data_type: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct FoodNutrientRecord {
+    fdc_id: i32,
+    nutrient_id: i32,
+    amount: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct FoodPortionRecord {
+    fdc_id: i32,
+    amount: Option<f64>,
+    measure_unit_id: Option<i32>,
+    portion_description: Option<String>,
+    modifier: Option<String>,
+    gram_weight: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct MeasureUnitRecord {
+    id: i32,
+    name: String,
+}
+
+/// A food with the nutrients the recipes use, per 100 g
+#[derive(Default)]
+struct Food {
+    data_type: String,
+    description: String,
+    nutrients: HashMap<i32, f32>,
+    grams_per_ml: Option<f32>,
+    grams_per_piece: Option<f32>,
+}
+
+impl Food {
+    fn nutrient(&self, ids: &[i32]) -> Option<f32> {
+        ids.iter().find_map(|id| self.nutrients.get(id).copied())
+    }
+}
+
+/// Run the import, `args` are the command line arguments after the command name
+pub fn run(args: &[String]) -> Result<String, String> {
+    let directory = match args.first() {
+        Some(d) => Path::new(d),
+        None => {
+            return Err(String::from(
+                "Usage: import-nutrients <directory with food.csv>",
+            ))
+        }
+    };
+    let foods = read_foods(directory)?;
+
+    let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL must be set.")?;
+    let mut c = PgConnection::establish(&database_url)
+        .map_err(|err| format!("Cannot connect to the database: {}", err))?;
+
+    let unmatched = c
+        .transaction(|c| store_nutrients(c, &foods))
+        .map_err(|err| format!("Cannot store nutrients in the database: {}", err))?;
+
+    let mut report = format!(
+        "Read {} foods, {} ingredients have no nutrient data.",
+        foods.len(),
+        unmatched.len()
+    );
+    for label in unmatched {
+        report.push_str(&format!("\n  {}", label));
+    }
+    Ok(report)
+}
+
+fn read_foods(directory: &Path) -> Result<HashMap<i32, Food>, String> {
+    let mut foods = HashMap::<i32, Food>::new();
+    for record in read_csv::<FoodRecord>(&directory.join("food.csv"))? {
+        foods.insert(
+            record.fdc_id,
+            Food {
+                data_type: record.data_type,
+                description: record.description,
+                ..Default::default()
+            },
+        );
+    }
+
+    let wanted = [&KCAL[..], &CARBS, &PROTEINS, &FATS].concat();
+    for record in read_csv::<FoodNutrientRecord>(&directory.join("food_nutrient.csv"))? {
+        if let (Some(food), Some(amount)) = (foods.get_mut(&record.fdc_id), record.amount) {
+            if wanted.contains(&record.nutrient_id) {
+                food.nutrients.insert(record.nutrient_id, amount);
+            }
+        }
+    }
+
+    // portions are optional, without them only weighed amounts can be computed
+    let measure_units = match directory.join("measure_unit.csv") {
+        path if path.exists() => read_csv::<MeasureUnitRecord>(&path)?
+            .into_iter()
+            .map(|m| (m.id, m.name))
+            .collect::<HashMap<i32, String>>(),
+        _ => HashMap::new(),
+    };
+    let portions_path = directory.join("food_portion.csv");
+    if portions_path.exists() {
+        for record in read_csv::<FoodPortionRecord>(&portions_path)? {
+            if let Some(food) = foods.get_mut(&record.fdc_id) {
+                add_portion(food, &record, &measure_units);
+            }
+        }
+    }
+
+    foods.retain(|_, f| f.nutrient(&KCAL).is_some());
+    Ok(foods)
+}
+
+fn read_csv<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>, String> {
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|err| format!("Cannot open {}: {}", path.display(), err))?;
+    reader
+        .deserialize::<T>()
+        .collect::<Result<Vec<T>, csv::Error>>()
+        .map_err(|err| format!("Cannot read {}: {}", path.display(), err))
+}
+
+/// Take the density from the first volume portion ("1 cup, chopped") and the weight
+/// of a piece from the first count portion ("1 large", "1 clove"), medium ones preferred
+fn add_portion(food: &mut Food, portion: &FoodPortionRecord, units: &HashMap<i32, String>) {
+    let gram_weight = match portion.gram_weight {
+        Some(g) if g > 0.0 => g,
+        _ => return,
+    };
+    let amount = portion.amount.filter(|a| *a > 0.0).unwrap_or(1.0);
+
+    let text = match portion
+        .measure_unit_id
+        .filter(|id| *id != UNDETERMINED_UNIT)
+    {
+        Some(id) => units.get(&id).cloned().unwrap_or_default(),
+        None => portion
+            .modifier
+            .clone()
+            .or_else(|| portion.portion_description.clone())
+            .unwrap_or_default(),
+    }
+    .to_lowercase();
+    if text.is_empty() || text.contains("serving") {
+        return;
+    }
+
+    let unit = parse_unit(&text.replace(',', " ")).and_then(|(symbol, _)| find_unit(&symbol));
+    match unit.map(|u| (u.dimension, u.factor)) {
+        Some((Dimension::Volume, factor)) => {
+            if food.grams_per_ml.is_none() {
+                food.grams_per_ml = Some((gram_weight / (amount * factor)) as f32);
+            }
+        }
+        Some((Dimension::Mass, _)) | Some((Dimension::Length, _)) => (),
+        // "large", "medium", "clove", "slice"
+        Some((Dimension::Count, _)) | None => {
+            if food.grams_per_piece.is_none() || text.contains("medium") {
+                food.grams_per_piece = Some((gram_weight / amount) as f32);
+            }
+        }
+    }
+}
+
+/// Replace the nutrient table, returns the labels of the ingredients without a match.
+///
+/// A food matches an ingredient when the start of its description does, also in reverse
+/// order: "Oil, olive, salad or cooking" matches "olive oil". Failing that, the last word
+/// is enough: "Wheat flour, white" matches "flour". Raw foods, Foundation and SR Legacy
+/// data and short descriptions are preferred.
+fn store_nutrients(c: &mut PgConnection, foods: &HashMap<i32, Food>) -> QueryResult<Vec<String>> {
+    diesel::sql_query(
+        "CREATE TEMPORARY TABLE usda_foods (
+            fdc_id INTEGER PRIMARY KEY,
+            data_type TEXT NOT NULL,
+            description TEXT NOT NULL,
+            kcal REAL NOT NULL,
+            carbs REAL NOT NULL,
+            proteins REAL NOT NULL,
+            fats REAL NOT NULL,
+            grams_per_ml REAL,
+            grams_per_piece REAL
+        ) ON COMMIT DROP",
+    )
+    .execute(c)?;
+
+    let foods = foods.iter().collect::<Vec<(&i32, &Food)>>();
+    diesel::sql_query(
+        "INSERT INTO usda_foods
+        SELECT * FROM unnest($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind::<Array<Integer>, _>(foods.iter().map(|(id, _)| **id).collect::<Vec<i32>>())
+    .bind::<Array<Text>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.data_type.clone())
+            .collect::<Vec<String>>(),
+    )
+    .bind::<Array<Text>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.description.clone())
+            .collect::<Vec<String>>(),
+    )
+    .bind::<Array<diesel::sql_types::Float>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.nutrient(&KCAL).unwrap_or(0.0))
+            .collect::<Vec<f32>>(),
+    )
+    .bind::<Array<diesel::sql_types::Float>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.nutrient(&CARBS).unwrap_or(0.0))
+            .collect::<Vec<f32>>(),
+    )
+    .bind::<Array<diesel::sql_types::Float>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.nutrient(&PROTEINS).unwrap_or(0.0))
+            .collect::<Vec<f32>>(),
+    )
+    .bind::<Array<diesel::sql_types::Float>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.nutrient(&FATS).unwrap_or(0.0))
+            .collect::<Vec<f32>>(),
+    )
+    .bind::<Array<Nullable<diesel::sql_types::Float>>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.grams_per_ml)
+            .collect::<Vec<Option<f32>>>(),
+    )
+    .bind::<Array<Nullable<diesel::sql_types::Float>>, _>(
+        foods
+            .iter()
+            .map(|(_, f)| f.grams_per_piece)
+            .collect::<Vec<Option<f32>>>(),
+    )
+    .execute(c)?;
+
+    diesel::sql_query("DELETE FROM ingredient_nutrients").execute(c)?;
+    diesel::sql_query(
+        "INSERT INTO ingredient_nutrients
+        SELECT DISTINCT ON (n.ingredient_id)
+            n.ingredient_id, f.fdc_id, f.description, f.kcal, f.carbs, f.proteins, f.fats,
+            f.grams_per_ml, f.grams_per_piece
+        FROM (
+            SELECT id AS ingredient_id, ingredient_key(label) AS key FROM ingredients
+            UNION ALL
+            SELECT ingredient_id, ingredient_key(label) FROM ingredient_synonyms
+        ) n
+        JOIN (
+            SELECT *,
+                ingredient_key(split_part(description, ',', 1)) AS key,
+                ingredient_key(split_part(description, ',', 2) || ' ' || split_part(description, ',', 1)) AS reversed_key
+            FROM usda_foods
+        ) f ON n.key IN (f.key, f.reversed_key) OR right(f.key, length(n.key) + 1) = ' ' || n.key
+        ORDER BY n.ingredient_id,
+            n.key IN (f.key, f.reversed_key) DESC,
+            f.description ~* '\\mraw\\M' DESC,
+            CASE f.data_type
+                WHEN 'foundation_food' THEN 0
+                WHEN 'sr_legacy_food' THEN 1
+                WHEN 'survey_fndds_food' THEN 2
+                ELSE 3
+            END,
+            n.key = f.key DESC,
+            length(f.description),
+            f.fdc_id",
+    )
+    .execute(c)?;
+
+    // recipe nutrition is computed from these, chunked to stay below the bind parameter limit
+    let recipe_ids = crate::schema::recipes::table
+        .select(crate::schema::recipes::id)
+        .load::<i32>(c)?;
+    for chunk in recipe_ids.chunks(1000) {
+        refresh_nutrition(c, chunk)?;
+    }
+
+    ingredients_without_nutrients(c)
+}
+
+fn ingredients_without_nutrients(c: &mut PgConnection) -> QueryResult<Vec<String>> {
+    use crate::schema::{ingredient_nutrients, ingredients};
+
+    ingredients::table
+        .left_join(ingredient_nutrients::table)
+        .filter(ingredient_nutrients::ingredient_id.is_null())
+        .select(ingredients::label)
+        .order(ingredients::label.asc())
+        .load::<String>(c)
+}
@@ -0,0 +1,90 @@
+use crate::models::{IngredientNutrient, NutritionDTO, NutritionValues};
+use crate::units::{find_unit, Dimension};
+
+/// One ingredient line of a recipe with the nutrient data of its ingredient, if any
+pub struct NutritionLine<'a> {
+    pub label: &'a str,
+    pub amount: Option<f32>,
+    pub unit: Option<&'a str>,
+    pub nutrient: Option<&'a IngredientNutrient>,
+}
+
+/// Weight of an amount in grams. Volumes need the density of the ingredient,
+/// pieces and count units like "clove" the weight of one piece.
+pub fn grams(amount: f32, unit: Option<&str>, nutrient: &IngredientNutrient) -> Option<f64> {
+    let amount = amount as f64;
+    let unit = match unit {
+        Some(u) => find_unit(u)?,
+        None => return nutrient.grams_per_piece.map(|g| amount * g as f64),
+    };
+    match unit.dimension {
+        Dimension::Mass => Some(amount * unit.factor),
+        Dimension::Volume => nutrient
+            .grams_per_ml
+            .map(|g| amount * unit.factor * g as f64),
+        Dimension::Count => nutrient.grams_per_piece.map(|g| amount * g as f64),
+        Dimension::Length => None,
+    }
+}
+
+/// Add up the nutrients of the ingredient lines. Lines without amount, like "salt to taste",
+/// are skipped; lines without nutrient data or weight are listed as unmatched.
+/// `manual` values entered on the recipe override the computed ones.
+pub fn compute_nutrition(
+    lines: &[NutritionLine],
+    servings: i16,
+    manual: NutritionValues,
+) -> NutritionDTO {
+    let mut unmatched = Vec::<String>::new();
+    let mut matched = false;
+    let (mut kcal, mut carbs, mut proteins, mut fats) = (0.0, 0.0, 0.0, 0.0);
+
+    for line in lines {
+        let amount = match line.amount {
+            Some(a) => a,
+            None => continue,
+        };
+        match line
+            .nutrient
+            .and_then(|n| grams(amount, line.unit, n).map(|g| (n, g / 100.0)))
+        {
+            Some((n, portions)) => {
+                matched = true;
+                kcal += n.kcal as f64 * portions;
+                carbs += n.carbs as f64 * portions;
+                proteins += n.proteins as f64 * portions;
+                fats += n.fats as f64 * portions;
+            }
+            None => {
+                if !unmatched.iter().any(|l| l == line.label) {
+                    unmatched.push(String::from(line.label));
+                }
+            }
+        }
+    }
+
+    let computed = match matched {
+        true => NutritionValues {
+            kcal: Some(kcal as f32),
+            carbs: Some(carbs as f32),
+            proteins: Some(proteins as f32),
+            fats: Some(fats as f32),
+        },
+        false => NutritionValues::default(),
+    };
+
+    NutritionDTO::new(computed, manual, servings, unmatched)
+}
+
+/// Ingredient whose nutrient data moves to `target` when duplicates are merged into it:
+/// the first of `merged` that has any, and none when the target has its own.
+/// `with_nutrients` lists the ingredients that have nutrient data.
+pub fn merged_nutrient_source(target: i32, merged: &[i32], with_nutrients: &[i32]) -> Option<i32> {
+    if with_nutrients.contains(&target) {
+        return None;
+    }
+    merged
+        .iter()
+        .find(|id| with_nutrients.contains(id))
+        .copied()
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+
+use crate::models::{kitchen_round, IngredientDTO};
+use crate::units::amount_in;
+
+/// Some of an ingredient in the pantry, `amount` `None` when the quantity is not tracked
+pub struct Stock {
+    pub ingredient_id: i32,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+}
+
+/// An ingredient line of a recipe
+pub struct Need<'a> {
+    pub ingredient_id: i32,
+    pub label: &'a str,
+    pub amount: Option<f32>,
+    pub unit: Option<&'a str>,
+}
+
+/// How much of a recipe the pantry covers
+#[derive(Debug)]
+pub struct Coverage {
+    /// distinct ingredients of the recipe that are in the pantry in sufficient quantity
+    pub covered: usize,
+    /// distinct ingredients of the recipe
+    pub total: usize,
+    /// lines that are not in the pantry, with the amount still to get for those that are short
+    pub missing: Vec<IngredientDTO>,
+    /// earliest expiry date of the pantry items the recipe uses
+    pub expires_on: Option<NaiveDate>,
+}
+
+impl Coverage {
+    /// Share of the ingredients that are covered, 0 to 1
+    pub fn ratio(&self) -> f32 {
+        match self.total {
+            0 => 0.0,
+            total => self.covered as f32 / total as f32,
+        }
+    }
+}
+
+/// Compare the ingredient lines of a recipe with the pantry. A line is covered when the pantry
+/// has the ingredient, and enough of it when both amounts are known and their units convert.
+/// Quantities that cannot be compared, like "2 cloves" against "1 bulb", count as enough.
+pub fn coverage(needs: &[Need], stock: &[Stock]) -> Coverage {
+    let mut ingredients = Vec::<(i32, bool)>::new();
+    let mut missing = Vec::<IngredientDTO>::new();
+    let mut expires_on: Option<NaiveDate> = None;
+
+    for need in needs {
+        let available = stock
+            .iter()
+            .filter(|s| s.ingredient_id == need.ingredient_id)
+            .collect::<Vec<&Stock>>();
+        if let Some(date) = available.iter().filter_map(|s| s.expires_on).min() {
+            expires_on = Some(expires_on.map_or(date, |e| e.min(date)));
+        }
+
+        let shortfall = match (available.is_empty(), need.amount) {
+            (true, amount) => Some(amount),
+            (false, None) => None,
+            (false, Some(amount)) => {
+                let have = available
+                    .iter()
+                    .map(|s| {
+                        s.amount
+                            .and_then(|a| amount_in(a, s.unit.as_deref(), need.unit))
+                    })
+                    .sum::<Option<f32>>();
+                match have {
+                    Some(have) if have < amount => Some(Some(kitchen_round(amount - have))),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(amount) = shortfall {
+            missing.push(IngredientDTO {
+                unit: need.unit.map(String::from),
+                label: String::from(need.label),
+                amount,
+            });
+        }
+
+        match ingredients
+            .iter_mut()
+            .find(|(id, _)| *id == need.ingredient_id)
+        {
+            Some((_, covered)) => *covered = *covered && shortfall.is_none(),
+            None => ingredients.push((need.ingredient_id, shortfall.is_none())),
+        }
+    }
+
+    Coverage {
+        covered: ingredients.iter().filter(|(_, covered)| *covered).count(),
+        total: ingredients.len(),
+        missing,
+        expires_on,
+    }
+}
@@ -0,0 +1,164 @@
+use serde_json::json;
+
+use crate::models::{
+    ChangeKind, FieldChangeDTO, IngredientChangeDTO, IngredientDTO, InstructionChangeDTO,
+    RecipeDiffDTO, RecipeResultDTO,
+};
+
+/// Fields of a recipe that are compared one by one
+const FIELDS: [&str; 9] = [
+    "title",
+    "servings",
+    "servings_unit",
+    "timer",
+    "kcal",
+    "carbs",
+    "proteins",
+    "fats",
+    "image",
+];
+
+/// Changes that turn revision `from` of a recipe into revision `to`
+pub fn recipe_diff(
+    from_revision: i32,
+    from: &RecipeResultDTO,
+    to_revision: i32,
+    to: &RecipeResultDTO,
+) -> RecipeDiffDTO {
+    let (from_json, to_json) = (json!(from), json!(to));
+    let fields = FIELDS
+        .iter()
+        .filter(|f| from_json[f] != to_json[f])
+        .map(|f| FieldChangeDTO {
+            field: String::from(*f),
+            from: from_json[f].clone(),
+            to: to_json[f].clone(),
+        })
+        .collect::<Vec<FieldChangeDTO>>();
+
+    let tags_added = to
+        .tags
+        .iter()
+        .filter(|t| !from.tags.iter().any(|f| f.slug == t.slug))
+        .map(|t| t.label.clone())
+        .collect::<Vec<String>>();
+    let tags_removed = from
+        .tags
+        .iter()
+        .filter(|f| !to.tags.iter().any(|t| t.slug == f.slug))
+        .map(|f| f.label.clone())
+        .collect::<Vec<String>>();
+
+    RecipeDiffDTO {
+        from: from_revision,
+        to: to_revision,
+        fields,
+        instructions: instruction_changes(&from.instructions, &to.instructions),
+        ingredients: ingredient_changes(&from.ingredients, &to.ingredients),
+        tags_added,
+        tags_removed,
+    }
+}
+
+/// Steps that were added, removed or changed. Steps that are kept in the same order, even if
+/// they moved to another position, are not reported. A removed step that is followed by an
+/// added one in the same place counts as changed.
+pub fn instruction_changes(from: &[String], to: &[String]) -> Vec<InstructionChangeDTO> {
+    // length of the longest common subsequence of from[i..] and to[j..]
+    let mut lcs = vec![vec![0usize; to.len() + 1]; from.len() + 1];
+    for i in (0..from.len()).rev() {
+        for j in (0..to.len()).rev() {
+            lcs[i][j] = if from[i] == to[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::<InstructionChangeDTO>::new();
+    let mut removed = Vec::<usize>::new();
+    let mut added = Vec::<usize>::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        if i < from.len() && j < to.len() && from[i] == to[j] {
+            flush_steps(from, to, &mut removed, &mut added, &mut changes);
+            i += 1;
+            j += 1;
+        } else if j < to.len() && (i == from.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(j);
+            j += 1;
+        } else if i < from.len() {
+            removed.push(i);
+            i += 1;
+        } else {
+            flush_steps(from, to, &mut removed, &mut added, &mut changes);
+            return changes;
+        }
+    }
+}
+
+/// Report a run of removed and added steps between two kept ones
+fn flush_steps(
+    from: &[String],
+    to: &[String],
+    removed: &mut Vec<usize>,
+    added: &mut Vec<usize>,
+    changes: &mut Vec<InstructionChangeDTO>,
+) {
+    let paired = removed.len().min(added.len());
+    for k in 0..removed.len().max(added.len()) {
+        let old = removed.get(k).copied();
+        let new = added.get(k).copied();
+        changes.push(InstructionChangeDTO {
+            change: match k < paired {
+                true => ChangeKind::Changed,
+                false if old.is_some() => ChangeKind::Removed,
+                false => ChangeKind::Added,
+            },
+            from_step: old.map(|s| s + 1),
+            to_step: new.map(|s| s + 1),
+            from: old.map(|s| from[s].clone()),
+            to: new.map(|s| to[s].clone()),
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// Ingredient lines matched by label. Lines of the same ingredient are matched in order.
+pub fn ingredient_changes(
+    from: &[IngredientDTO],
+    to: &[IngredientDTO],
+) -> Vec<IngredientChangeDTO> {
+    let mut unmatched = to.iter().collect::<Vec<&IngredientDTO>>();
+    let mut changes = Vec::<IngredientChangeDTO>::new();
+
+    for old in from {
+        let position = unmatched
+            .iter()
+            .position(|new| new.label.to_lowercase() == old.label.to_lowercase());
+        match position.map(|p| unmatched.remove(p)) {
+            Some(new) if new.amount == old.amount && new.unit == old.unit => (),
+            Some(new) => changes.push(IngredientChangeDTO {
+                change: ChangeKind::Changed,
+                label: new.label.clone(),
+                from: Some(old.clone()),
+                to: Some(new.clone()),
+            }),
+            None => changes.push(IngredientChangeDTO {
+                change: ChangeKind::Removed,
+                label: old.label.clone(),
+                from: Some(old.clone()),
+                to: None,
+            }),
+        }
+    }
+    changes.extend(unmatched.into_iter().map(|new| IngredientChangeDTO {
+        change: ChangeKind::Added,
+        label: new.label.clone(),
+        from: None,
+        to: Some(new.clone()),
+    }));
+    changes
+}
@@ -1,9 +1,67 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
+}
+
+diesel::table! {
+    collection_recipes (collection_id, recipe_id) {
+        collection_id -> Int4,
+        recipe_id -> Int4,
+        position -> Int4,
+        added_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    collections (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        is_default -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    comments (id) {
+        id -> Int4,
+        recipe_id -> Int4,
+        user_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        body -> Text,
+        created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    ingredient_nutrients (ingredient_id) {
+        ingredient_id -> Int4,
+        fdc_id -> Int4,
+        description -> Varchar,
+        kcal -> Float4,
+        carbs -> Float4,
+        proteins -> Float4,
+        fats -> Float4,
+        grams_per_ml -> Nullable<Float4>,
+        grams_per_piece -> Nullable<Float4>,
+    }
+}
+
+diesel::table! {
+    ingredient_synonyms (id) {
+        id -> Int4,
+        ingredient_id -> Int4,
+        label -> Varchar,
+    }
+}
+
 diesel::table! {
     ingredients (id) {
         id -> Int4,
-        unit -> Nullable<Varchar>,
         label -> Varchar,
     }
 }
@@ -17,12 +75,89 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    meal_plan_entries (id) {
+        id -> Int4,
+        user_id -> Int4,
+        recipe_id -> Int4,
+        date -> Date,
+        slot -> Varchar,
+        servings -> Int2,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    meal_plan_feeds (user_id) {
+        user_id -> Int4,
+        token -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    pantry_items (id) {
+        id -> Int4,
+        user_id -> Int4,
+        ingredient_id -> Nullable<Int4>,
+        label -> Varchar,
+        amount -> Nullable<Float4>,
+        unit -> Nullable<Varchar>,
+        expires_on -> Nullable<Date>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    ratings (recipe_id, user_id) {
+        recipe_id -> Int4,
+        user_id -> Int4,
+        stars -> Int2,
+        review -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     recipe_ingredients (id) {
         id -> Int4,
         amount -> Nullable<Float4>,
         recipe_id -> Int4,
         ingredient_id -> Int4,
+        unit -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    recipe_revisions (id) {
+        id -> Int4,
+        recipe_id -> Int4,
+        revision -> Int4,
+        user_id -> Nullable<Int4>,
+        snapshot -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recipe_nutrition (recipe_id) {
+        recipe_id -> Int4,
+        kcal -> Nullable<Float4>,
+        carbs -> Nullable<Float4>,
+        proteins -> Nullable<Float4>,
+        fats -> Nullable<Float4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    recipe_search_documents (recipe_id) {
+        recipe_id -> Int4,
+        content -> Text,
+        document -> Tsvector,
     }
 }
 
@@ -30,7 +165,8 @@ diesel::table! {
     recipes (id) {
         id -> Int4,
         title -> Varchar,
-        servings -> Varchar,
+        servings -> Int2,
+        servings_unit -> Nullable<Varchar>,
         timer -> Nullable<Int2>,
         kcal -> Nullable<Int2>,
         carbs -> Nullable<Int2>,
@@ -39,6 +175,9 @@ diesel::table! {
         image -> Nullable<Json>,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        forked_from -> Nullable<Int4>,
+        visibility -> Varchar,
     }
 }
 
@@ -46,6 +185,7 @@ diesel::table! {
     recipes_users (recipe_id, user_id) {
         recipe_id -> Int4,
         user_id -> Int4,
+        role -> Varchar,
     }
 }
 
@@ -56,6 +196,32 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        refresh_token_hash -> Varchar,
+        previous_token_hash -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_used_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    shopping_list_items (id) {
+        id -> Int4,
+        user_id -> Int4,
+        ingredient_id -> Nullable<Int4>,
+        label -> Varchar,
+        amount -> Nullable<Float4>,
+        unit -> Nullable<Varchar>,
+        checked -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tags (id) {
         id -> Int4,
@@ -76,26 +242,64 @@ diesel::table! {
         id -> Int4,
         username -> Varchar,
         password -> Varchar,
+        role -> Varchar,
+        disabled -> Bool,
+        password_reset_required -> Bool,
     }
 }
 
+diesel::joinable!(collection_recipes -> collections (collection_id));
+diesel::joinable!(collection_recipes -> recipes (recipe_id));
+diesel::joinable!(collections -> users (user_id));
+diesel::joinable!(comments -> recipes (recipe_id));
+diesel::joinable!(comments -> users (user_id));
+diesel::joinable!(ingredient_nutrients -> ingredients (ingredient_id));
+diesel::joinable!(ingredient_synonyms -> ingredients (ingredient_id));
 diesel::joinable!(recipe_ingredients -> ingredients (ingredient_id));
 diesel::joinable!(instructions -> recipes (recipe_id));
+diesel::joinable!(meal_plan_entries -> recipes (recipe_id));
+diesel::joinable!(meal_plan_entries -> users (user_id));
+diesel::joinable!(meal_plan_feeds -> users (user_id));
+diesel::joinable!(pantry_items -> ingredients (ingredient_id));
+diesel::joinable!(pantry_items -> users (user_id));
+diesel::joinable!(ratings -> recipes (recipe_id));
+diesel::joinable!(ratings -> users (user_id));
 diesel::joinable!(recipe_ingredients -> recipes (recipe_id));
+diesel::joinable!(recipe_nutrition -> recipes (recipe_id));
+diesel::joinable!(recipe_revisions -> recipes (recipe_id));
+diesel::joinable!(recipe_revisions -> users (user_id));
+diesel::joinable!(recipe_search_documents -> recipes (recipe_id));
 diesel::joinable!(bookmarks -> recipes (recipe_id));
 diesel::joinable!(bookmarks -> users (user_id));
 diesel::joinable!(recipes_users -> recipes (recipe_id));
 diesel::joinable!(recipes_users -> users (user_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(shopping_list_items -> ingredients (ingredient_id));
+diesel::joinable!(shopping_list_items -> users (user_id));
 diesel::joinable!(recipes_tags -> recipes (recipe_id));
 diesel::joinable!(recipes_tags -> tags (tag_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    collection_recipes,
+    collections,
+    comments,
+    ingredient_nutrients,
+    ingredient_synonyms,
     ingredients,
     instructions,
+    meal_plan_entries,
+    meal_plan_feeds,
+    pantry_items,
+    ratings,
     recipe_ingredients,
+    recipe_nutrition,
+    recipe_revisions,
+    recipe_search_documents,
     recipes,
     bookmarks,
     recipes_users,
+    sessions,
+    shopping_list_items,
     tags,
     recipes_tags,
     users,
@@ -0,0 +1,88 @@
+use crate::models::ShoppingItemDTO;
+use crate::units::{combine, normalize_unit};
+
+/// An item of a shopping list while new ingredients are merged into it
+#[derive(Clone, PartialEq, Debug)]
+pub struct ListEntry {
+    /// `None` for items that are not stored yet
+    pub id: Option<i32>,
+    pub ingredient_id: Option<i32>,
+    pub label: String,
+    pub amount: Option<f32>,
+    pub unit: Option<String>,
+    /// amount or unit changed since it was loaded
+    pub changed: bool,
+}
+
+impl ListEntry {
+    pub fn new(
+        ingredient_id: Option<i32>,
+        label: &str,
+        amount: Option<f32>,
+        unit: Option<&str>,
+    ) -> Self {
+        Self {
+            id: None,
+            ingredient_id,
+            label: label.split_whitespace().collect::<Vec<&str>>().join(" "),
+            amount,
+            unit: unit.and_then(normalize_unit),
+            changed: true,
+        }
+    }
+
+    fn same_ingredient(&self, other: &ListEntry) -> bool {
+        match (self.ingredient_id, other.ingredient_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.label.to_lowercase() == other.label.to_lowercase(),
+        }
+    }
+}
+
+/// Add an entry to the list. Amounts of the same ingredient add up when their units can be
+/// combined, otherwise the entry gets its own line: "500 g flour" and "2 cups flour" stay apart.
+/// An entry without amount, like "salt", is covered by any line of its ingredient.
+pub fn add_to_list(list: &mut Vec<ListEntry>, entry: ListEntry) {
+    let mut same = list.iter_mut().filter(|e| e.same_ingredient(&entry));
+    let amount = match entry.amount {
+        Some(a) => a,
+        None => {
+            if same.next().is_none() {
+                list.push(entry);
+            }
+            return;
+        }
+    };
+
+    for existing in same {
+        let merged = match existing.amount {
+            None => Some((amount, entry.unit.clone())),
+            Some(a) => combine(a, existing.unit.as_deref(), amount, entry.unit.as_deref()),
+        };
+        if let Some((amount, unit)) = merged {
+            existing.amount = Some(amount);
+            existing.unit = unit;
+            existing.changed = true;
+            return;
+        }
+    }
+    list.push(entry);
+}
+
+/// Plain text version of a shopping list, one item per line, checked ones marked with `[x]`
+pub fn list_text(items: &[ShoppingItemDTO]) -> String {
+    items
+        .iter()
+        .map(|i| {
+            let mut line = String::from(if i.checked { "[x]" } else { "[ ]" });
+            if let Some(amount) = i.amount {
+                line.push_str(&format!(" {}", amount));
+            }
+            if let Some(unit) = &i.unit {
+                line.push_str(&format!(" {}", unit));
+            }
+            line.push_str(&format!(" {}\n", i.label));
+            line
+        })
+        .collect()
+}
@@ -0,0 +1,353 @@
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::kitchen_round;
+
+/// What a unit measures, only units of the same dimension convert into each other.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dimension {
+    Mass,
+    Volume,
+    Count,
+    Length,
+}
+
+/// Measurement system to convert recipe amounts into.
+/// Imperial covers the US customary cooking units as well.
+#[derive(FromFormField, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[schema(rename_all = "lowercase")]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+pub struct Unit {
+    /// canonical name, stored in the database
+    pub symbol: &'static str,
+    pub dimension: Dimension,
+    /// `None` for units used by both systems, like teaspoons or pieces, these are never converted
+    pub system: Option<UnitSystem>,
+    /// size in the base unit of the dimension: g, ml, piece or cm
+    pub factor: f64,
+    /// smallest amount this unit is chosen for when converting into it
+    pub min_amount: Option<f64>,
+    pub aliases: &'static [&'static str],
+}
+
+use Dimension::*;
+use UnitSystem::*;
+
+pub const UNITS: &[Unit] = &[
+    // mass
+    Unit {
+        symbol: "mg",
+        dimension: Mass,
+        system: Some(Metric),
+        factor: 0.001,
+        min_amount: None,
+        aliases: &["milligram", "milligrams", "milligramme", "milligrammes"],
+    },
+    Unit {
+        symbol: "g",
+        dimension: Mass,
+        system: Some(Metric),
+        factor: 1.0,
+        min_amount: Some(0.0),
+        aliases: &["gr", "gram", "grams", "gramme", "grammes"],
+    },
+    Unit {
+        symbol: "kg",
+        dimension: Mass,
+        system: Some(Metric),
+        factor: 1000.0,
+        min_amount: Some(1.0),
+        aliases: &[
+            "kgs",
+            "kilo",
+            "kilos",
+            "kilogram",
+            "kilograms",
+            "kilogramme",
+        ],
+    },
+    Unit {
+        symbol: "oz",
+        dimension: Mass,
+        system: Some(Imperial),
+        factor: 28.349523125,
+        min_amount: Some(0.0),
+        aliases: &["ounce", "ounces"],
+    },
+    Unit {
+        symbol: "lb",
+        dimension: Mass,
+        system: Some(Imperial),
+        factor: 453.59237,
+        min_amount: Some(1.0),
+        aliases: &["lbs", "pound", "pounds"],
+    },
+    // volume
+    Unit {
+        symbol: "ml",
+        dimension: Volume,
+        system: Some(Metric),
+        factor: 1.0,
+        min_amount: Some(0.0),
+        aliases: &["milliliter", "milliliters", "millilitre", "millilitres"],
+    },
+    Unit {
+        symbol: "cl",
+        dimension: Volume,
+        system: Some(Metric),
+        factor: 10.0,
+        min_amount: None,
+        aliases: &["centiliter", "centiliters", "centilitre", "centilitres"],
+    },
+    Unit {
+        symbol: "dl",
+        dimension: Volume,
+        system: Some(Metric),
+        factor: 100.0,
+        min_amount: None,
+        aliases: &["deciliter", "deciliters", "decilitre", "decilitres"],
+    },
+    Unit {
+        symbol: "l",
+        dimension: Volume,
+        system: Some(Metric),
+        factor: 1000.0,
+        min_amount: Some(1.0),
+        aliases: &["ltr", "liter", "liters", "litre", "litres"],
+    },
+    Unit {
+        symbol: "tsp",
+        dimension: Volume,
+        system: None,
+        factor: 4.92892159375,
+        min_amount: Some(0.0),
+        aliases: &["tsps", "teaspoon", "teaspoons", "tl", "teelöffel"],
+    },
+    Unit {
+        symbol: "tbsp",
+        dimension: Volume,
+        system: None,
+        factor: 14.78676478125,
+        min_amount: Some(1.0),
+        aliases: &[
+            "tbs",
+            "tbl",
+            "tbsps",
+            "tablespoon",
+            "tablespoons",
+            "el",
+            "esslöffel",
+        ],
+    },
+    Unit {
+        symbol: "fl oz",
+        dimension: Volume,
+        system: Some(Imperial),
+        factor: 29.5735295625,
+        min_amount: None,
+        aliases: &["floz", "fl. oz", "fluid ounce", "fluid ounces"],
+    },
+    Unit {
+        symbol: "cup",
+        dimension: Volume,
+        system: Some(Imperial),
+        factor: 236.5882365,
+        min_amount: Some(0.25),
+        aliases: &["cups"],
+    },
+    Unit {
+        symbol: "pt",
+        dimension: Volume,
+        system: Some(Imperial),
+        factor: 473.176473,
+        min_amount: None,
+        aliases: &["pint", "pints"],
+    },
+    Unit {
+        symbol: "qt",
+        dimension: Volume,
+        system: Some(Imperial),
+        factor: 946.352946,
+        min_amount: None,
+        aliases: &["quart", "quarts"],
+    },
+    Unit {
+        symbol: "gal",
+        dimension: Volume,
+        system: Some(Imperial),
+        factor: 3785.411784,
+        min_amount: None,
+        aliases: &["gallon", "gallons"],
+    },
+    // count
+    Unit {
+        symbol: "pc",
+        dimension: Count,
+        system: None,
+        factor: 1.0,
+        min_amount: None,
+        aliases: &["pcs", "piece", "pieces"],
+    },
+    Unit {
+        symbol: "clove",
+        dimension: Count,
+        system: None,
+        factor: 1.0,
+        min_amount: None,
+        aliases: &["cloves"],
+    },
+    Unit {
+        symbol: "slice",
+        dimension: Count,
+        system: None,
+        factor: 1.0,
+        min_amount: None,
+        aliases: &["slices"],
+    },
+    Unit {
+        symbol: "can",
+        dimension: Count,
+        system: None,
+        factor: 1.0,
+        min_amount: None,
+        aliases: &["cans", "tin", "tins"],
+    },
+    Unit {
+        symbol: "pinch",
+        dimension: Count,
+        system: None,
+        factor: 1.0,
+        min_amount: None,
+        aliases: &["pinches"],
+    },
+    // length
+    Unit {
+        symbol: "mm",
+        dimension: Length,
+        system: Some(Metric),
+        factor: 0.1,
+        min_amount: None,
+        aliases: &["millimeter", "millimeters", "millimetre", "millimetres"],
+    },
+    Unit {
+        symbol: "cm",
+        dimension: Length,
+        system: Some(Metric),
+        factor: 1.0,
+        min_amount: Some(0.0),
+        aliases: &["centimeter", "centimeters", "centimetre", "centimetres"],
+    },
+    Unit {
+        symbol: "in",
+        dimension: Length,
+        system: Some(Imperial),
+        factor: 2.54,
+        min_amount: Some(0.0),
+        aliases: &["inch", "inches", "\""],
+    },
+];
+
+/// Look up a unit by its symbol or one of its aliases, case-insensitively.
+pub fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim().trim_end_matches('.').to_lowercase();
+    UNITS
+        .iter()
+        .find(|u| u.symbol == name || u.aliases.contains(&name.as_str()))
+}
+
+/// Canonical spelling of a unit, e.g. "grams" and "gr" both become "g".
+/// Unknown units are only trimmed, empty ones become `None`.
+pub fn normalize_unit(name: &str) -> Option<String> {
+    match find_unit(name) {
+        Some(unit) => Some(String::from(unit.symbol)),
+        None => match name.trim() {
+            "" => None,
+            trimmed => Some(String::from(trimmed)),
+        },
+    }
+}
+
+/// Convert an amount into the given system, picking the largest unit the amount fills.
+/// Units of the other system convert, unknown and shared units are returned unchanged.
+pub fn convert(amount: f32, unit: &str, system: UnitSystem) -> (f32, String) {
+    let from = match find_unit(unit) {
+        Some(u) if u.system.is_some() && u.system != Some(system) => u,
+        _ => return (amount, String::from(unit)),
+    };
+    let base = amount as f64 * from.factor;
+
+    let target = UNITS
+        .iter()
+        .filter(|u| u.dimension == from.dimension)
+        // US recipes measure small amounts in spoons, metric ones don't
+        .filter(|u| u.system == Some(system) || (u.system.is_none() && system == Imperial))
+        .filter_map(|u| u.min_amount.map(|min| (u, min)))
+        .filter(|(u, min)| base / u.factor >= *min)
+        .max_by(|(a, _), (b, _)| a.factor.total_cmp(&b.factor));
+
+    match target {
+        Some((to, _)) => (
+            kitchen_round((base / to.factor) as f32),
+            String::from(to.symbol),
+        ),
+        None => (amount, String::from(unit)),
+    }
+}
+
+/// Add two amounts of the same ingredient. Equal units add up as they are, mass and volume
+/// units are combined in the larger of the two: 500 g and 1 kg make 1.5 kg.
+/// `None` when the units cannot be combined, e.g. grams and cups or cloves and pieces.
+pub fn combine(
+    amount: f32,
+    unit: Option<&str>,
+    other_amount: f32,
+    other_unit: Option<&str>,
+) -> Option<(f32, Option<String>)> {
+    let (unit, other_unit) = (
+        unit.and_then(normalize_unit),
+        other_unit.and_then(normalize_unit),
+    );
+    if unit == other_unit {
+        return Some((round_sum(amount as f64 + other_amount as f64), unit));
+    }
+
+    let from = find_unit(other_unit.as_deref()?)?;
+    let to = find_unit(unit.as_deref()?)?;
+    if from.dimension != to.dimension || !matches!(from.dimension, Mass | Volume) {
+        return None;
+    }
+    let base = amount as f64 * to.factor + other_amount as f64 * from.factor;
+    let target = if from.factor > to.factor { from } else { to };
+    Some((
+        round_sum(base / target.factor),
+        Some(String::from(target.symbol)),
+    ))
+}
+
+fn round_sum(amount: f64) -> f32 {
+    ((amount * 100.0).round() / 100.0) as f32
+}
+
+/// An amount expressed in another unit of the same dimension, e.g. 1.5 kg in g.
+/// `None` when the units cannot be converted into each other.
+pub fn amount_in(amount: f32, unit: Option<&str>, target: Option<&str>) -> Option<f32> {
+    let (unit, target) = (
+        unit.and_then(normalize_unit),
+        target.and_then(normalize_unit),
+    );
+    if unit == target {
+        return Some(amount);
+    }
+
+    let from = find_unit(unit.as_deref()?)?;
+    let to = find_unit(target.as_deref()?)?;
+    if from.dimension != to.dimension || !matches!(from.dimension, Mass | Volume) {
+        return None;
+    }
+    Some((amount as f64 * from.factor / to.factor) as f32)
+}
@@ -78,22 +78,22 @@ const RecipeFull = ({ params }: RecipeFullProps) => {
                     <span className="mr-1 p-1 bg-gray-100 rounded-lg">
                         <Icon icon="fluent-emoji-high-contrast:fire" className="w-7 h-7" />
                     </span>
-                    { recipe.kcal } Kcal</p>
+                    { recipe.nutrition?.per_recipe.kcal ?? recipe.kcal } Kcal</p>
                 <p className="w-1/2 mb-2 flex items-center">
                     <span className="mr-1 p-1 bg-gray-100 rounded-lg">
                         <Icon icon="fluent-emoji-high-contrast:bread" className="w-7 h-7" />
                     </span>
-                    { recipe.carbs }g carbs</p>
+                    { recipe.nutrition?.per_recipe.carbs ?? recipe.carbs }g carbs</p>
                 <p className="w-1/2 mb-2 flex items-center">
                     <span className="mr-1 p-1 bg-gray-100 rounded-lg">
                         <Icon icon="fluent-emoji-high-contrast:cut-of-meat" className="w-7 h-7" />
                     </span>
-                    { recipe.proteins }g proteins</p>
+                    { recipe.nutrition?.per_recipe.proteins ?? recipe.proteins }g proteins</p>
                 <p className="w-1/2 mb-2 flex items-center">
                     <span className="mr-1 p-1 bg-gray-100 rounded-lg">
                         <Icon icon="fluent-emoji-high-contrast:avocado" className="w-7 h-7" />
                     </span>
-                    { recipe.fats }g fats</p>
+                    { recipe.nutrition?.per_recipe.fats ?? recipe.fats }g fats</p>
             </div>
             <p className="text-sm mb-2">Created At: { created_at }</p>
             <p className="text-sm">Updated At: { updated_at }</p>
@@ -1,6 +1,6 @@
 import { Icon } from "@iconify/react";
 import React, { useState } from "react";
-import { getJwtToken } from "./UserAuth";
+import { authFetch } from "@/context/user-state";
 
 interface AddRecipeProps {
   onAdd: () => void;
@@ -20,17 +20,16 @@ const AddRecipe: React.FC<AddRecipeProps> = ({ onAdd }) => {
 
     const recipeData = {
       title: recipeTitle,
-      servings: recipeServings,
+      servings: Number(recipeServings),
     };
 
     try {
-      const response = await fetch(
+      const response = await authFetch(
         "https://crimson-eagles-recipe-app.onrender.com/recipes",
         {
           method: "POST",
           headers: {
             "Content-Type": "application/json",
-            Authorization: `Bearer ${getJwtToken()}`,
           },
           body: JSON.stringify(recipeData),
         }
@@ -90,7 +89,8 @@ const AddRecipe: React.FC<AddRecipeProps> = ({ onAdd }) => {
                   Servings
                 </label>
                 <input
-                  type="text"
+                  type="number"
+                  min="1"
                   id="servings"
                   value={recipeServings}
                   onChange={(e) => setRecipeServings(e.target.value)}
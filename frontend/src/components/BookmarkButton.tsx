@@ -1,7 +1,7 @@
 import React from "react";
 import { Icon } from "@iconify/react";
 import { Recipe } from "@/lib/types";
-import { getJwtToken } from "@/components/UserAuth";
+import { authFetch } from "@/context/user-state";
 
 interface BookmarkButtonProps {
   recipe: Recipe;
@@ -12,11 +12,8 @@ const BookmarkButton: React.FC<BookmarkButtonProps> = ({ recipe, onBookmark }) =
   const handleClick = async (e: React.MouseEvent<HTMLButtonElement>) => {
     e.stopPropagation();
     try {
-      const response = await fetch(`https://crimson-eagles-recipe-app.onrender.com/bookmarks/${recipe.id}`, {
+      const response = await authFetch(`https://crimson-eagles-recipe-app.onrender.com/bookmarks/${recipe.id}`, {
         method: "PUT",
-        headers: {
-          Authorization: `Bearer ${getJwtToken()}`,
-        },
       });
       if (response.ok) {
         onBookmark(recipe);
@@ -1,6 +1,6 @@
 import React, { useState } from "react";
 import { Icon } from "@iconify/react";
-import { getJwtToken } from "./UserAuth";
+import { authFetch } from "@/context/user-state";
 
 
 interface DeleteButtonProps {
@@ -17,11 +17,10 @@ const DeleteButton: React.FC<DeleteButtonProps> = ({ onRecipeDeleted }) => {
     setError(null);
     setIsSubmitting(true);
     try {
-      const response = await fetch(`https://crimson-eagles-recipe-app.onrender.com/recipes/${inputValue}`, {
+      const response = await authFetch(`https://crimson-eagles-recipe-app.onrender.com/recipes/${inputValue}`, {
         method: "DELETE",
         headers: {
           "Content-Type": "application/json",
-          // Authorization: `Bearer ${getJwtToken()}`, // add this back in if the backend implements authentication 
         },
       });
       if (response.ok) {
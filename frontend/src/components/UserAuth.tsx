@@ -41,8 +41,9 @@ const UserAuth: React.FC = () => {
     
         if (response && response.ok) {
             console.log('User logged in successfully');
-            const { AuthToken } = await response.json();
-            localStorage.setItem('jwtToken', AuthToken);
+            const { access_token, refresh_token } = await response.json();
+            localStorage.setItem('jwtToken', access_token);
+            localStorage.setItem('refreshToken', refresh_token);
             setIsLoggedIn(true);
             setShowForm(false);
           } else if (response) {
@@ -72,7 +73,18 @@ const UserAuth: React.FC = () => {
     
 
     const logOutUser = () => {
+        const jwtToken = getJwtToken();
+        if (jwtToken) {
+            // end the session on the server, so its refresh token stops working too
+            fetch('https://crimson-eagles-recipe-app.onrender.com/logout', {
+                method: 'POST',
+                headers: {
+                    Authorization: `Bearer ${jwtToken}`,
+                },
+            }).catch(console.log);
+        }
         localStorage.removeItem('jwtToken');
+        localStorage.removeItem('refreshToken');
         setIsLoggedIn(false);
         console.log('Token cleared, user logged out');
     };
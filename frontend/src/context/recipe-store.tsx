@@ -1,6 +1,6 @@
 import { create } from 'zustand'
 import type { Recipe, Tag, SearchState, Pagination } from "@/lib/types"
-import type { UserState } from './user-state'
+import { type UserState, authFetch } from './user-state'
 
 const buildurl = (query: string | null, filter: Tag[]) => {
     let queries: string[] = []
@@ -82,10 +82,7 @@ const useRecipeStore = create<RecipeState>((set, get) => ({
             lastUpdate: Date.now()
         })
 		
-        const fetching = (userState.isLoggedIn) ? fetch(url, {
-            headers: {
-                'Authorization': `Bearer ${ userState.token }`,
-            },
+        const fetching = (userState.isLoggedIn) ? authFetch(url, {
             credentials: 'include',
         }) : fetch(url)
 
@@ -132,10 +129,7 @@ const useRecipeStore = create<RecipeState>((set, get) => ({
         const url = `${ process.env.apiUrl }/recipes/${ id }`
 		
         const userState = get().userState
-        const fetching = (userState.isLoggedIn) ? fetch(url, {
-            headers: {
-                'Authorization': `Bearer ${ userState.token }`,
-            },
+        const fetching = (userState.isLoggedIn) ? authFetch(url, {
             credentials: 'include',
         }) : fetch(url)
 
@@ -177,11 +171,8 @@ const useRecipeStore = create<RecipeState>((set, get) => ({
         if (!userState.isLoggedIn)
             return
 
-        fetch(`${ process.env.apiUrl }/bookmarks/${ id }`, {
+        authFetch(`${ process.env.apiUrl }/bookmarks/${ id }`, {
             method: 'PUT',
-            headers: {
-                'Authorization': `Bearer ${ userState.token }`,
-            },
             credentials: 'include',
         })
             .then(res => res.json())
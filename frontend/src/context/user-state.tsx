@@ -20,7 +20,69 @@ type User = {
 	password: string,
 }
 type Auth = {
-	AuthToken: string,
+	access_token: string,
+	token_type: string,
+	expires_in: number,
+	refresh_token: string,
+}
+
+const storeTokens = (auth: Auth) => {
+	localStorage.setItem('jwtToken', auth.access_token)
+	localStorage.setItem('refreshToken', auth.refresh_token)
+}
+
+let refreshing: Promise<string> | null = null
+
+// trades the stored refresh token for a new token pair, resolves to the new access token
+// requests that fail at the same time share one refresh, a refresh token only works once
+const refreshSession = (): Promise<string> => {
+	if (!refreshing) {
+		refreshing = requestRefresh().finally(() => {
+			refreshing = null
+		})
+	}
+	return refreshing
+}
+
+const requestRefresh = (): Promise<string> => {
+	const refreshToken = localStorage.getItem('refreshToken')
+	if (!refreshToken) {
+		return Promise.reject(new Error("Authorization expired."))
+	}
+	return fetch(`${ process.env.apiUrl }/token/refresh`, {
+		method: 'POST',
+		headers: {
+			'Content-Type': 'application/json',
+		},
+		body: JSON.stringify({
+			refresh_token: refreshToken,
+		}),
+	})
+	.then(res => {
+		if (!res.ok) {
+			localStorage.removeItem('refreshToken')
+			throw new Error("Authorization expired.")
+		}
+		return res.json()
+	})
+	.then((res: Auth) => {
+		storeTokens(res)
+		return res.access_token
+	})
+}
+
+// fetch with the stored access token, an expired one is refreshed once and the request retried
+export const authFetch = (url: string, init: RequestInit = {}): Promise<Response> => {
+	const send = (token: string | null) => {
+		const headers = new Headers(init.headers)
+		if (token) headers.set('Authorization', `Bearer ${ token }`)
+		return fetch(url, { ...init, headers })
+	}
+	return send(localStorage.getItem('jwtToken'))
+	.then(res => {
+		if (res.status != 401 || !localStorage.getItem('refreshToken')) return res
+		return refreshSession().then(send, () => res)
+	})
 }
 type UserContextProps = {
 	userState: UserState,
@@ -51,31 +113,18 @@ export const UserContextProvider = ({ children }: { children: ReactNode }) => {
 		const token = localStorage.getItem('jwtToken')
 		
 		if (token) {
-			fetch(`${ process.env.apiUrl }/profile`, {
-				method: 'GET',
-				headers: {
-					'Content-Type': 'application/json',
-					'Authorization': `Bearer ${ token }`,
-				},
-			})
+			authFetch(`${ process.env.apiUrl }/profile`)
 			.then(res => {
-				if (!res.ok) {
-					if (res.status == 401) {
-						localStorage.removeItem('jwtToken')
-						throw new Error("Authorization expired.")
-					}
-					throw new Error("Status not 200")
-				}
-				else return res.json()
+				if (res.ok) return localStorage.getItem('jwtToken')
+				if (res.status == 401) localStorage.removeItem('jwtToken')
+				throw new Error("Status not 200")
 			})
-			.then(res => {
-				if (res.username) {
-					setUserState(prev => ({
-						...prev,
-						isLoggedIn: true,
-						token: token,
-					}))
-				}
+			.then(validToken => {
+				setUserState(prev => ({
+					...prev,
+					isLoggedIn: true,
+					token: validToken,
+				}))
 			})
 			.catch(console.log) // most likely the token is not valid anymore
 		}
@@ -131,9 +180,9 @@ export const UserContextProvider = ({ children }: { children: ReactNode }) => {
 			setUserState(prev => ({
 				...prev,
 				isLoggedIn: true,
-				token: res.AuthToken,
+				token: res.access_token,
 			}))
-			localStorage.setItem('jwtToken', res.AuthToken)
+			storeTokens(res)
 		})
 		.finally(() => {
 			setUserState(prev => ({
@@ -144,7 +193,18 @@ export const UserContextProvider = ({ children }: { children: ReactNode }) => {
 	}
 
 	const logout = () => {
+		const token = localStorage.getItem('jwtToken')
+		if (token) {
+			fetch(`${ process.env.apiUrl }/logout`, {
+				method: 'POST',
+				headers: {
+					'Authorization': `Bearer ${ token }`,
+				},
+			})
+			.catch(console.log)
+		}
         localStorage.removeItem('jwtToken')
+        localStorage.removeItem('refreshToken')
 		setUserState({
 			isLoading: false,
 			isLoggedIn: false,
@@ -166,4 +226,4 @@ export const UserContextProvider = ({ children }: { children: ReactNode }) => {
 	)
 }
 
-export const useUserContext = () => useContext(UserContext)
\ No newline at end of file
+export const useUserContext = () => useContext(UserContext)
\ No newline at end of file
@@ -1,7 +1,8 @@
 export interface Recipe {
   id: number;
   title: string;
-  servings: string;
+  servings: number;
+  servings_unit: string | null;
   timer: number | null;
   kcal: number | null;
   carbs: number | null;
@@ -16,9 +17,72 @@ export interface Recipe {
 }[];
   tags: string[];
   bookmarked: boolean | null;
-  owned: boolean | null;
+  role: RecipeRole | null;
   created_at: string | null;
   updated_at: string | null;
+  nutrition: Nutrition | null;
+  average_rating: number | null;
+  rating_count: number;
+  my_rating: number | null;
+  comment_count: number;
+  forked_from: number | null;
+  fork_count: number;
+  visibility: Visibility;
+}
+
+export type Visibility = 'public' | 'unlisted' | 'private'
+
+export type RecipeRole = 'owner' | 'editor' | 'viewer'
+
+export type Collaborator = {
+  username: string;
+  role: RecipeRole;
+}
+
+export type Comment = {
+  id: number,
+  parent_id: number | null,
+  username: string,
+  body: string,
+  created_at: string,
+  updated_at: string | null,
+  replies: Comment[],
+}
+
+export type Revision = {
+  revision: number,
+  username: string | null,
+  created_at: string,
+}
+
+export type Collection = {
+  id: number,
+  name: string,
+  is_default: boolean,
+  recipe_count: number,
+  created_at: string,
+}
+
+export type Review = {
+  username: string,
+  stars: number,
+  review: string,
+  created_at: string,
+  updated_at: string,
+}
+
+export type NutritionValues = {
+  kcal: number | null,
+  carbs: number | null,
+  proteins: number | null,
+  fats: number | null,
+}
+
+export type Nutrition = {
+  per_recipe: NutritionValues,
+  per_serving: NutritionValues,
+  computed: NutritionValues,
+  unmatched: string[],
 }
 
 export type Pagination<T> = {